@@ -0,0 +1,135 @@
+//! `cargo bench` runs these. Each program is assembled from source, so the
+//! numbers mean the same thing on any checkout.
+
+use chip_8mulator::asm::assemble;
+use chip_8mulator::chip::{Chip8, Engine};
+use chip_8mulator::quirks::Quirks;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+
+///Copies of the body before the jump back to the start, so the jump barely
+///shows in the results
+const REPEATS: usize = 32;
+
+///`setup` once, then `body` over and over
+fn looped(setup: &str, body: &str) -> Chip8 {
+    let source = format!("{setup}\nstart:\n{}\n    jp start\n", body.repeat(REPEATS));
+    let rom = assemble(&source).unwrap_or_else(|e| panic!("{e}\n{source}"));
+    let mut chip = Chip8::new();
+    chip.load_rom_bytes(&rom).unwrap();
+    chip
+}
+
+/// Fetch and dispatch alone: opcodes from each family that decode to
+/// nothing, so the PC moving on is all that happens.
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(1));
+    for (family, opcode) in [
+        ("0NNN", "0x0123"),
+        ("5XY1", "0x5011"),
+        ("8XYF", "0x801F"),
+        ("EXFF", "0xE0FF"),
+        ("FXFF", "0xF0FF"),
+    ] {
+        let mut chip = looped("", &format!("    dw {opcode}\n"));
+        group.bench_function(family, |b| b.iter(|| black_box(&mut chip).cycle()));
+    }
+    group.finish();
+}
+
+/// `cycle` on the kinds of code games spend their time in.
+fn cycle(c: &mut Criterion) {
+    let mixes = [
+        (
+            "arithmetic",
+            "    ld v0, 7\n    ld v1, 3\n",
+            "    add v0, 1\n    add v0, v1\n    sub v0, v1\n    shr v0\n    xor v1, v0\n",
+        ),
+        (
+            "branches",
+            "    ld v0, 1\n",
+            "    se v0, 1\n    add v0, 0\n    sne v0, 1\n    add v0, 0\n    se v0, v1\n    add v0, 0\n",
+        ),
+        (
+            "memory",
+            "    ld v0, 123\n    ld i, 0xE00\n",
+            "    ld b, v0\n    ld [i], v3\n    ld v3, [i]\n    add i, v0\n    ld i, 0xE00\n",
+        ),
+        (
+            "game",
+            "    ld v0, 10\n    ld v1, 5\n",
+            "    ld i, 0x050\n    ld v2, dt\n    sknp v2\n    add v0, 1\n    rnd v3, 0x3F\n    \
+             drw v0, v1, 5\n    ld dt, v2\n    and v3, v0\n",
+        ),
+    ];
+    let mut group = c.benchmark_group("cycle");
+    group.throughput(Throughput::Elements(1));
+    for (name, setup, body) in mixes {
+        let mut chip = looped(setup, body);
+        group.bench_function(name, |b| b.iter(|| black_box(&mut chip).cycle()));
+    }
+    group.finish();
+}
+
+/// `DXYN` alone, by sprite height and by where the sprite lands.
+fn dxyn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ins_dxyn");
+    group.throughput(Throughput::Elements(1));
+    for height in [1, 5, 15] {
+        let mut chip = looped(
+            "    ld v0, 20\n    ld v1, 8\n    ld i, 0x050\n",
+            &format!("    drw v0, v1, {height}\n"),
+        );
+        group.bench_with_input(BenchmarkId::new("inside", height), &height, |b, _| {
+            b.iter(|| black_box(&mut chip).cycle())
+        });
+    }
+    for (edge, clip) in [("wrapped", false), ("clipped", true)] {
+        let mut chip = looped(
+            "    ld v0, 60\n    ld v1, 28\n    ld i, 0x050\n",
+            "    drw v0, v1, 15\n",
+        );
+        chip.set_quirks(Quirks {
+            clip_sprites: clip,
+            ..Quirks::default()
+        });
+        group.bench_function(BenchmarkId::new(edge, 15), |b| {
+            b.iter(|| black_box(&mut chip).cycle())
+        });
+    }
+    group.finish();
+}
+
+/// Whole frames of the programs in `roms/`, headless, as the golden tests
+/// run them, on each engine.
+fn frames(c: &mut Criterion) {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut paths: Vec<_> = fs::read_dir(&roms)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "asm"))
+        .collect();
+    paths.sort();
+
+    let mut group = c.benchmark_group("frame");
+    for path in paths {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let rom = assemble(&fs::read_to_string(&path).unwrap()).unwrap();
+        for &engine in Engine::ALL {
+            let mut chip = Chip8::new();
+            chip.load_rom_bytes(&rom).unwrap();
+            chip.set_engine(engine);
+            group.throughput(Throughput::Elements(chip.tick_rate() as u64));
+            group.bench_function(BenchmarkId::new(engine.name(), &name), |b| {
+                b.iter(|| black_box(&mut chip).run_frame([0; 16]))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, cycle, dxyn, frames);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+//! Runs an arbitrary ROM from an arbitrary machine state with an arbitrary
+//! keypad sequence. The interpreter must not panic, must refuse empty and
+//! oversize ROMs, must keep PC and I inside the 4 KiB address space, and
+//! every other engine must keep up with it exactly.
+
+#![no_main]
+
+use chip_8mulator::chip::{Chip8, Engine, MAX_ROM_SIZE};
+use chip_8mulator::movie::mask_to_keys;
+use chip_8mulator::quirks::Quirks;
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    quirks: u8,
+    pc: u16,
+    i: u16,
+    registers: [u8; 16],
+    ///Keys held down each frame, bit n for key n
+    frames: Vec<u16>,
+    rom: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut chip = Chip8::new();
+    let loaded = chip.load_rom_bytes(&input.rom);
+    assert_eq!(
+        loaded.is_ok(),
+        !input.rom.is_empty() && input.rom.len() <= MAX_ROM_SIZE
+    );
+    if loaded.is_err() {
+        return;
+    }
+    chip.set_quirks(Quirks::from_bits(input.quirks));
+    chip.set_program_counter(input.pc & 0xFFF);
+    chip.set_index_register(input.i & 0xFFF);
+    for (x, value) in input.registers.into_iter().enumerate() {
+        chip.set_register(x, value);
+    }
+    let mut others: Vec<Chip8> = Engine::ALL
+        .iter()
+        .filter(|engine| **engine != Engine::Interpreter)
+        .map(|engine| {
+            let mut other = chip.clone();
+            other.set_engine(*engine);
+            other
+        })
+        .collect();
+
+    for keys in input.frames.iter().take(600) {
+        let keys = mask_to_keys(*keys);
+        chip.get_input(keys);
+        for _ in 0..chip.tick_rate() {
+            chip.cycle();
+            assert!(chip.program_counter() < 0x1000);
+            assert!(chip.index_register() < 0x1000);
+        }
+        chip.tick_timers();
+        for other in &mut others {
+            other.run_frame(keys);
+            assert!(chip.save_state() == other.save_state());
+        }
+    }
+});
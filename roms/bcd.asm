@@ -0,0 +1,23 @@
+; Stores the decimal digits of 137 with FX33, loads them back into V0-V2
+; and draws them
+; frames: 3
+
+    ld v0, 137
+    ld i, digits
+    ld b, v0
+    ld v2, [i]
+    ld v3, 10       ; x
+    ld v4, 10       ; y
+    ld f, v0
+    drw v3, v4, 5
+    add v3, 6
+    ld f, v1
+    drw v3, v4, 5
+    add v3, 6
+    ld f, v2
+    drw v3, v4, 5
+done:
+    jp done
+
+digits:
+    db 0, 0, 0
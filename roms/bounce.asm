@@ -0,0 +1,66 @@
+; Bounces a ball around the screen, erasing and redrawing it every step, and
+; keeps a count of the bounces in the top left corner. It never waits for the
+; delay timer, so it also serves as a busy workload for the benchmarks
+; frames: 60
+
+    ld v5, 10       ; x
+    ld v6, 5        ; y
+    ld v7, 1        ; dx
+    ld v8, 1        ; dy
+    ld v9, 0        ; bounces
+    call score
+loop:
+    ld i, ball
+    drw v5, v6, 2
+    se v5, 0
+    jp right
+    ld v7, 1
+    call bounce
+right:
+    se v5, 62
+    jp top
+    ld v7, 255
+    call bounce
+top:
+    se v6, 0
+    jp bottom
+    ld v8, 1
+    call bounce
+bottom:
+    se v6, 30
+    jp move
+    ld v8, 255
+    call bounce
+move:
+    ld i, ball
+    drw v5, v6, 2   ; erase
+    add v5, v7
+    add v6, v8
+    jp loop
+
+; Erases the count, adds one and falls through to draw it again
+bounce:
+    call score
+    add v9, 1
+
+; Draws v9 as three digits
+score:
+    ld i, digits
+    ld b, v9
+    ld v2, [i]
+    ld va, 1
+    ld vb, 1
+    ld f, v0
+    drw va, vb, 5
+    add va, 5
+    ld f, v1
+    drw va, vb, 5
+    add va, 5
+    ld f, v2
+    drw va, vb, 5
+    ret
+
+ball:
+    db 0b11000000, 0b11000000
+digits:
+    db 0, 0, 0
@@ -0,0 +1,32 @@
+; Draws a box, XORs a second one over half of it and a third one clear of
+; both, shows the two VF results as digits, then draws a box that wraps
+; around the bottom right corner
+; frames: 5
+; quirks: clip_sprites=0
+
+    ld i, box
+    ld v0, 4
+    ld v1, 4
+    drw v0, v1, 4
+    ld v0, 6
+    drw v0, v1, 4   ; overlaps, VF = 1
+    ld v5, vf
+    ld v0, 20
+    drw v0, v1, 4   ; clear, VF = 0
+    ld v6, vf
+    ld v2, 4
+    ld v3, 12
+    ld f, v5
+    drw v2, v3, 5
+    add v2, 6
+    ld f, v6
+    drw v2, v3, 5
+    ld i, box
+    ld v0, 62
+    ld v1, 30
+    drw v0, v1, 4
+done:
+    jp done
+
+box:
+    db 0b11110000, 0b10010000, 0b10010000, 0b11110000
@@ -0,0 +1,20 @@
+; Draws the 16 built-in hex digits in two rows of eight
+; frames: 20
+
+    ld v0, 0        ; digit
+    ld v1, 2        ; x
+    ld v2, 4        ; y
+next:
+    ld f, v0
+    drw v1, v2, 5
+    add v0, 1
+    add v1, 8
+    se v1, 66       ; past the eighth digit
+    jp skip
+    ld v1, 2
+    add v2, 8
+skip:
+    se v0, 16
+    jp next
+done:
+    jp done
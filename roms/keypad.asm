@@ -0,0 +1,28 @@
+; Waits for key 5, draws a 5, waits for it to be let go, then draws an R
+; frames: 10
+; keys 3: 5
+; keys 6:
+
+    ld v0, 5
+    ld v1, 8        ; x
+    ld v2, 8        ; y
+wait_press:
+    sknp v0
+    jp pressed
+    jp wait_press
+pressed:
+    ld f, v0
+    drw v1, v2, 5
+wait_release:
+    skp v0
+    jp released
+    jp wait_release
+released:
+    ld i, letter_r
+    add v1, 8
+    drw v1, v2, 5
+done:
+    jp done
+
+letter_r:
+    db 0b11100000, 0b10010000, 0b11100000, 0b10100000, 0b10010000
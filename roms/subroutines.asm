@@ -0,0 +1,30 @@
+; Draws a staircase of boxes through a subroutine that calls another one
+; frames: 10
+
+    ld v0, 2        ; x
+    ld v1, 2        ; y
+    ld v3, 0        ; pairs drawn
+loop:
+    call draw_pair
+    add v3, 1
+    se v3, 4
+    jp loop
+done:
+    jp done
+
+; Two boxes side by side, then one step down
+draw_pair:
+    call draw_box
+    add v0, 6
+    call draw_box
+    add v0, 6
+    add v1, 2
+    ret
+
+draw_box:
+    ld i, box
+    drw v0, v1, 4
+    ret
+
+box:
+    db 0b11110000, 0b10010000, 0b10010000, 0b11110000
@@ -0,0 +1,287 @@
+//! A small assembler for the classic Cowgod mnemonics, used to build the test
+//! programs under `roms/` from source.
+//!
+//! One instruction per line, `;` starts a comment and `name:` defines a
+//! label. Numbers are decimal, `0x1F` or `0b0110`. `db` emits bytes and `dw`
+//! big-endian words. Code starts at 0x200.
+//!
+//! ```text
+//! loop:
+//!     ld v0, 0x05
+//!     ld f, v0
+//!     drw v1, v2, 5
+//!     jp loop
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+const ORIGIN: u16 = 0x200;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    ///1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand<'a> {
+    V(u16),
+    I,
+    ///`[I]`
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    ///A number or label
+    Value(&'a str),
+}
+
+///Assembles `source` into a ROM image to load at 0x200
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let lines: Vec<(usize, &str)> = source
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.split(';').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+
+    // First pass: where every label lands
+    let mut labels = HashMap::new();
+    let mut address = ORIGIN;
+    for &(n, line) in &lines {
+        let line = take_label(line, n, address, &mut labels)?;
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = split(line);
+        address += match mnemonic.as_str() {
+            "db" => operands.len() as u16,
+            "dw" => operands.len() as u16 * 2,
+            _ => 2,
+        };
+    }
+
+    let mut rom = Vec::new();
+    for &(n, line) in &lines {
+        let fail = |message: String| AsmError { line: n, message };
+        let line = take_label(line, n, 0, &mut HashMap::new())?;
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = split(line);
+        let value = |s: &str| resolve(s, &labels).map_err(fail);
+        match mnemonic.as_str() {
+            "db" => {
+                for operand in &operands {
+                    rom.push(byte(value(operand)?).map_err(fail)?);
+                }
+            }
+            "dw" => {
+                for operand in &operands {
+                    let word = u16::try_from(value(operand)?)
+                        .map_err(|_| fail(format!("`{operand}` doesn't fit in a word")))?;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            _ => {
+                let operands: Vec<Operand> = operands.iter().map(|o| operand(o)).collect();
+                let opcode = encode(line, &mnemonic, &operands, &labels).map_err(fail)?;
+                rom.extend_from_slice(&opcode.to_be_bytes());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+/// The mnemonic for `opcode` in the syntax `assemble` reads, or a `dw` for
+/// words that aren't instructions. Addresses are written in hex.
+pub fn disassemble(opcode: u16) -> String {
+    let x = opcode >> 8 & 0xF;
+    let y = opcode >> 4 & 0xF;
+    let n = opcode & 0xF;
+    let kk = opcode & 0xFF;
+    let nnn = opcode & 0xFFF;
+    match (opcode >> 12, n, kk) {
+        _ if opcode == 0x00E0 => "cls".to_string(),
+        _ if opcode == 0x00EE => "ret".to_string(),
+        (0x1, _, _) => format!("jp {nnn:#05x}"),
+        (0x2, _, _) => format!("call {nnn:#05x}"),
+        (0x3, _, _) => format!("se v{x:x}, {kk:#04x}"),
+        (0x4, _, _) => format!("sne v{x:x}, {kk:#04x}"),
+        (0x5, 0x0, _) => format!("se v{x:x}, v{y:x}"),
+        (0x6, _, _) => format!("ld v{x:x}, {kk:#04x}"),
+        (0x7, _, _) => format!("add v{x:x}, {kk:#04x}"),
+        (0x8, 0x0, _) => format!("ld v{x:x}, v{y:x}"),
+        (0x8, 0x1, _) => format!("or v{x:x}, v{y:x}"),
+        (0x8, 0x2, _) => format!("and v{x:x}, v{y:x}"),
+        (0x8, 0x3, _) => format!("xor v{x:x}, v{y:x}"),
+        (0x8, 0x4, _) => format!("add v{x:x}, v{y:x}"),
+        (0x8, 0x5, _) => format!("sub v{x:x}, v{y:x}"),
+        (0x8, 0x6, _) => format!("shr v{x:x}, v{y:x}"),
+        (0x8, 0x7, _) => format!("subn v{x:x}, v{y:x}"),
+        (0x8, 0xE, _) => format!("shl v{x:x}, v{y:x}"),
+        (0x9, 0x0, _) => format!("sne v{x:x}, v{y:x}"),
+        (0xA, _, _) => format!("ld i, {nnn:#05x}"),
+        (0xB, _, _) => format!("jp v0, {nnn:#05x}"),
+        (0xC, _, _) => format!("rnd v{x:x}, {kk:#04x}"),
+        (0xD, _, _) => format!("drw v{x:x}, v{y:x}, {n}"),
+        (0xE, _, 0x9E) => format!("skp v{x:x}"),
+        (0xE, _, 0xA1) => format!("sknp v{x:x}"),
+        (0xF, _, 0x07) => format!("ld v{x:x}, dt"),
+        (0xF, _, 0x0A) => format!("ld v{x:x}, k"),
+        (0xF, _, 0x15) => format!("ld dt, v{x:x}"),
+        (0xF, _, 0x18) => format!("ld st, v{x:x}"),
+        (0xF, _, 0x1E) => format!("add i, v{x:x}"),
+        (0xF, _, 0x29) => format!("ld f, v{x:x}"),
+        (0xF, _, 0x33) => format!("ld b, v{x:x}"),
+        (0xF, _, 0x55) => format!("ld [i], v{x:x}"),
+        (0xF, _, 0x65) => format!("ld v{x:x}, [i]"),
+        _ => format!("dw {opcode:#06x}"),
+    }
+}
+
+///Strips a leading `name:`, recording it at `address`
+fn take_label<'a>(
+    line: &'a str,
+    n: usize,
+    address: u16,
+    labels: &mut HashMap<String, u16>,
+) -> Result<&'a str, AsmError> {
+    let Some((label, rest)) = line.split_once(':') else {
+        return Ok(line);
+    };
+    let label = label.trim();
+    if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(AsmError {
+            line: n,
+            message: format!("bad label `{label}`"),
+        });
+    }
+    if labels.insert(label.to_string(), address).is_some() {
+        return Err(AsmError {
+            line: n,
+            message: format!("`{label}` is defined twice"),
+        });
+    }
+    Ok(rest.trim())
+}
+
+///Lower case mnemonic and its comma separated operands
+fn split(line: &str) -> (String, Vec<&str>) {
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = rest
+        .split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .collect();
+    (mnemonic.to_ascii_lowercase(), operands)
+}
+
+fn operand(s: &str) -> Operand<'_> {
+    match s.to_ascii_lowercase().as_str() {
+        "i" => Operand::I,
+        "[i]" => Operand::IndirectI,
+        "dt" => Operand::Dt,
+        "st" => Operand::St,
+        "k" => Operand::K,
+        "f" => Operand::F,
+        "b" => Operand::B,
+        lower => match lower.strip_prefix('v').map(|x| u16::from_str_radix(x, 16)) {
+            Some(Ok(x)) if x < 16 && lower.len() == 2 => Operand::V(x),
+            _ => Operand::Value(s),
+        },
+    }
+}
+
+fn resolve(s: &str, labels: &HashMap<String, u16>) -> Result<u32, String> {
+    let lower = s.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2)
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse()
+    } else {
+        return labels
+            .get(s)
+            .map(|a| *a as u32)
+            .ok_or(format!("unknown label `{s}`"));
+    };
+    parsed.map_err(|_| format!("bad number `{s}`"))
+}
+
+fn byte(value: u32) -> Result<u8, String> {
+    u8::try_from(value).map_err(|_| format!("{value:#x} doesn't fit in a byte"))
+}
+
+///`line` is only for the error message
+fn encode(
+    line: &str,
+    mnemonic: &str,
+    operands: &[Operand],
+    labels: &HashMap<String, u16>,
+) -> Result<u16, String> {
+    use Operand::*;
+
+    let address = |s: &str| match resolve(s, labels)? {
+        a if a <= 0xFFF => Ok(a as u16),
+        a => Err(format!("address {a:#x} is out of range")),
+    };
+    let kk = |s: &str| resolve(s, labels).and_then(byte).map(u16::from);
+    let xy = |x: u16, y: u16| x << 8 | y << 4;
+
+    Ok(match (mnemonic, operands) {
+        ("cls", []) => 0x00E0,
+        ("ret", []) => 0x00EE,
+        ("jp", [Value(a)]) => 0x1000 | address(a)?,
+        ("jp", [V(0), Value(a)]) => 0xB000 | address(a)?,
+        ("call", [Value(a)]) => 0x2000 | address(a)?,
+        ("se", [V(x), Value(k)]) => 0x3000 | x << 8 | kk(k)?,
+        ("sne", [V(x), Value(k)]) => 0x4000 | x << 8 | kk(k)?,
+        ("se", [V(x), V(y)]) => 0x5000 | xy(*x, *y),
+        ("sne", [V(x), V(y)]) => 0x9000 | xy(*x, *y),
+        ("ld", [V(x), Value(k)]) => 0x6000 | x << 8 | kk(k)?,
+        ("ld", [V(x), V(y)]) => 0x8000 | xy(*x, *y),
+        ("ld", [I, Value(a)]) => 0xA000 | address(a)?,
+        ("ld", [V(x), Dt]) => 0xF007 | x << 8,
+        ("ld", [V(x), K]) => 0xF00A | x << 8,
+        ("ld", [Dt, V(x)]) => 0xF015 | x << 8,
+        ("ld", [St, V(x)]) => 0xF018 | x << 8,
+        ("ld", [F, V(x)]) => 0xF029 | x << 8,
+        ("ld", [B, V(x)]) => 0xF033 | x << 8,
+        ("ld", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("ld", [V(x), IndirectI]) => 0xF065 | x << 8,
+        ("add", [V(x), Value(k)]) => 0x7000 | x << 8 | kk(k)?,
+        ("add", [V(x), V(y)]) => 0x8004 | xy(*x, *y),
+        ("add", [I, V(x)]) => 0xF01E | x << 8,
+        ("or", [V(x), V(y)]) => 0x8001 | xy(*x, *y),
+        ("and", [V(x), V(y)]) => 0x8002 | xy(*x, *y),
+        ("xor", [V(x), V(y)]) => 0x8003 | xy(*x, *y),
+        ("sub", [V(x), V(y)]) => 0x8005 | xy(*x, *y),
+        ("shr", [V(x)]) => 0x8006 | xy(*x, *x),
+        ("shr", [V(x), V(y)]) => 0x8006 | xy(*x, *y),
+        ("subn", [V(x), V(y)]) => 0x8007 | xy(*x, *y),
+        ("shl", [V(x)]) => 0x800E | xy(*x, *x),
+        ("shl", [V(x), V(y)]) => 0x800E | xy(*x, *y),
+        ("rnd", [V(x), Value(k)]) => 0xC000 | x << 8 | kk(k)?,
+        ("drw", [V(x), V(y), Value(n)]) => match resolve(n, labels)? {
+            n @ 0..=15 => 0xD000 | xy(*x, *y) | n as u16,
+            n => return Err(format!("sprite height {n} is over 15")),
+        },
+        ("skp", [V(x)]) => 0xE09E | x << 8,
+        ("sknp", [V(x)]) => 0xE0A1 | x << 8,
+        _ => return Err(format!("can't assemble `{line}`")),
+    })
+}
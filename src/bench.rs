@@ -0,0 +1,70 @@
+use crate::chip::Chip8;
+use crate::scheduler::FRAME_RATE;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+///Instructions between looks at the clock, so timing stays out of the result
+const CHECK_EVERY: u64 = 1 << 14;
+
+/// How fast the interpreter ran: whole frames back to back with no pacing,
+/// no input and nothing drawn to the host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Throughput {
+    pub instructions: u64,
+    pub frames: u64,
+    pub elapsed: Duration,
+}
+
+impl Throughput {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64()
+    }
+
+    ///How many times faster than a real machine at 60 frames a second
+    pub fn speedup(&self) -> f64 {
+        self.frames_per_second() / FRAME_RATE as f64
+    }
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} instructions in {:.2}s: {:.2}M instructions/s, {:.0} frames/s ({:.0}x real time)",
+            self.instructions,
+            self.elapsed.as_secs_f64(),
+            self.instructions_per_second() / 1e6,
+            self.frames_per_second(),
+            self.speedup()
+        )
+    }
+}
+
+///Runs frames uncapped for at least `duration`
+pub fn measure(chip: &mut Chip8, duration: Duration) -> Throughput {
+    let start = Instant::now();
+    let mut throughput = Throughput {
+        instructions: 0,
+        frames: 0,
+        elapsed: Duration::ZERO,
+    };
+    let mut unchecked = 0;
+    loop {
+        chip.run_frame([0; 16]);
+        throughput.frames += 1;
+        throughput.instructions += chip.tick_rate() as u64;
+        // A tick rate of 0 still runs frames, and still has to stop
+        unchecked += chip.tick_rate().max(1) as u64;
+        if unchecked >= CHECK_EVERY {
+            unchecked = 0;
+            throughput.elapsed = start.elapsed();
+            if throughput.elapsed >= duration {
+                return throughput;
+            }
+        }
+    }
+}
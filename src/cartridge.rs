@@ -0,0 +1,275 @@
+//! Octo cartridges: GIF pictures of a cartridge with the program's name on
+//! its label, carrying the Octo source and the options to run it with.
+//!
+//! The data hides in the palette indices. An index's high nybble picks one
+//! of 16 colours and its low nybble holds 4 bits of data, so the palette
+//! repeats each colour 16 times and the picture doesn't change. The bytes
+//! are a 32-bit big-endian length then that much UTF-8 JSON,
+//! `{"program": "...", "options": {...}}`, two pixels to a byte with the
+//! high nybble first. They run across the rows of as many frames as it
+//! takes, each frame the same picture.
+
+use crate::asm::AsmError;
+use crate::chip::Chip8;
+use crate::gif::{self, Image};
+use crate::octo;
+use crate::osd::Canvas;
+use crate::quirks::Quirks;
+use crate::rng::RandomSource;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::io;
+
+const WIDTH: usize = gif::MAX_SCREEN_WIDTH;
+const HEIGHT: usize = gif::MAX_SCREEN_HEIGHT;
+
+///Octo's defaults, for options a cartridge leaves out
+const TICK_RATE: u32 = 20;
+const COLORS: [u32; 2] = [0x996600, 0xFFCC00];
+
+///Colours of the picture, by the high nybble of their indices
+const BACKGROUND: u32 = 0x202020;
+const SHELL: u32 = 0x707070;
+const SHADOW: u32 = 0x505050;
+const LABEL: u32 = 0xE8E0C8;
+const INK: u32 = 0x181818;
+const ART: [u32; 5] = [BACKGROUND, SHELL, SHADOW, LABEL, INK];
+
+///Characters of the label that fit on a line
+const LABEL_COLUMNS: usize = 16;
+
+///How a cartridge says to run its program
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub tick_rate: Option<u32>,
+    pub quirks: Option<Quirks>,
+    ///Unlit and lit pixels, as 0xRRGGBB
+    pub colors: Option<[u32; 2]>,
+}
+
+impl Options {
+    ///Sets the quirks and tick rate the cartridge wants, where it says
+    pub fn apply<R: RandomSource>(&self, chip: &mut Chip8<R>) {
+        if let Some(quirks) = self.quirks {
+            chip.set_quirks(quirks);
+        }
+        if let Some(tick_rate) = self.tick_rate {
+            chip.set_tick_rate(tick_rate);
+        }
+    }
+}
+
+/// One `Field: value` line per option set, e.g.
+///
+/// ```text
+/// Tick rate: 20
+/// Quirks: shift_uses_vy=1 ...
+/// Colors: #996600 #ffcc00
+/// ```
+impl fmt::Display for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+        if let Some(tick_rate) = self.tick_rate {
+            lines.push(format!("Tick rate: {tick_rate}"));
+        }
+        if let Some(quirks) = &self.quirks {
+            lines.push(format!("Quirks: {quirks}"));
+        }
+        if let Some([off, on]) = self.colors {
+            lines.push(format!("Colors: #{off:06x} #{on:06x}"));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cartridge {
+    ///Octo source
+    pub program: String,
+    pub options: Options,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("cartridge: {message}"))
+}
+
+///`#RRGGBB` as 0xRRGGBB
+fn color(value: &Value) -> Option<u32> {
+    let hex = value.as_str()?.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Octo's quirk flags say what differs from its own defaults, which are
+/// the VIP's shifts and loads without the VF reset or clipping. Missing
+/// flags are off, as in Octo.
+fn quirks(options: &Map<String, Value>) -> Option<Quirks> {
+    const FLAGS: [&str; 5] = [
+        "shiftQuirks",
+        "loadStoreQuirks",
+        "jumpQuirks",
+        "logicQuirks",
+        "clipQuirks",
+    ];
+    if !FLAGS.iter().any(|flag| options.contains_key(*flag)) {
+        return None;
+    }
+    let flag = |name: &str| options.get(name).and_then(Value::as_bool).unwrap_or(false);
+    Some(Quirks {
+        shift_uses_vy: !flag("shiftQuirks"),
+        load_store_increments_i: !flag("loadStoreQuirks"),
+        jump_uses_vx: flag("jumpQuirks"),
+        vf_reset: flag("logicQuirks"),
+        clip_sprites: flag("clipQuirks"),
+    })
+}
+
+pub fn is_cartridge(data: &[u8]) -> bool {
+    gif::is_gif(data)
+}
+
+impl Cartridge {
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let image = gif::decode(data)?;
+        let mut nybbles = image.frames.iter().flatten().map(|index| index & 0xF);
+        let mut bytes = std::iter::from_fn(|| Some(nybbles.next()? << 4 | nybbles.next()?));
+        let mut length = [0; 4];
+        for byte in &mut length {
+            *byte = bytes.next().ok_or_else(|| invalid("no data"))?;
+        }
+        let length = u32::from_be_bytes(length) as usize;
+        let payload: Vec<u8> = bytes.take(length).collect();
+        if payload.len() < length {
+            return Err(invalid("data cut short"));
+        }
+
+        let payload: Value =
+            serde_json::from_slice(&payload).map_err(|e| invalid(&format!("bad JSON: {e}")))?;
+        let program = payload
+            .get("program")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("no program"))?;
+        let options = payload
+            .get("options")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        let colors = options
+            .get("backgroundColor")
+            .and_then(color)
+            .zip(options.get("fillColor").and_then(color))
+            .map(|(off, on)| [off, on]);
+        Ok(Cartridge {
+            program: program.to_string(),
+            options: Options {
+                tick_rate: options
+                    .get("tickrate")
+                    .and_then(Value::as_u64)
+                    .and_then(|rate| rate.try_into().ok()),
+                quirks: quirks(&options),
+                colors,
+            },
+        })
+    }
+
+    pub fn compile(&self) -> Result<Vec<u8>, AsmError> {
+        octo::compile(&self.program)
+    }
+
+    ///A GIF with `label` on the cartridge, Octo's defaults standing in for
+    ///options left unset
+    pub fn write(&self, label: &str) -> Vec<u8> {
+        let quirks = self.options.quirks.unwrap_or(Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+        });
+        let [off, on] = self.options.colors.unwrap_or(COLORS);
+        let hex = |color: u32| format!("#{color:06X}");
+        let payload = json!({
+            "program": self.program,
+            "options": {
+                "tickrate": self.options.tick_rate.unwrap_or(TICK_RATE),
+                "fillColor": hex(on),
+                "fillColor2": "#FF6600",
+                "blendColor": "#662200",
+                "backgroundColor": hex(off),
+                "buzzColor": "#FFAA00",
+                "quietColor": "#000000",
+                "shiftQuirks": !quirks.shift_uses_vy,
+                "loadStoreQuirks": !quirks.load_store_increments_i,
+                "vfOrderQuirks": false,
+                "clipQuirks": quirks.clip_sprites,
+                "vBlankQuirks": false,
+                "jumpQuirks": quirks.jump_uses_vx,
+                "logicQuirks": quirks.vf_reset,
+                "screenRotation": 0,
+                "maxSize": 3584,
+                "touchInputMode": "none",
+                "fontStyle": "octo",
+            },
+        })
+        .to_string();
+        let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(payload.as_bytes());
+
+        let picture = picture(label);
+        let nybbles: Vec<u8> = data
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xF])
+            .collect();
+        let frames = nybbles
+            .chunks(WIDTH * HEIGHT)
+            .map(|chunk| {
+                let mut frame = picture.clone();
+                for (pixel, nybble) in frame.iter_mut().zip(chunk) {
+                    *pixel |= nybble;
+                }
+                frame
+            })
+            .collect();
+        gif::encode(&Image {
+            width: WIDTH,
+            height: HEIGHT,
+            palette: ART.iter().flat_map(|&color| [color; 16]).collect(),
+            frames,
+        })
+    }
+}
+
+///The cartridge as palette indices with the data nybbles clear
+fn picture(label: &str) -> Vec<u8> {
+    let mut pixels = vec![BACKGROUND; WIDTH * HEIGHT];
+    let mut canvas = Canvas::new(&mut pixels, WIDTH, HEIGHT);
+    canvas.fill_rect((16, 8), (128, 112), SHELL, 1.0);
+    // The notch at the top and the grip along the bottom
+    canvas.fill_rect((16, 8), (20, 8), BACKGROUND, 1.0);
+    for y in (100..116).step_by(4) {
+        canvas.fill_rect((40, y), (80, 2), SHADOW, 1.0);
+    }
+    canvas.fill_rect((28, 24), (104, 68), SHADOW, 1.0);
+    canvas.fill_rect((32, 28), (96, 60), LABEL, 1.0);
+
+    let text: String = label
+        .chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .collect();
+    let lines: Vec<&str> = text
+        .as_bytes()
+        .chunks(LABEL_COLUMNS)
+        .take(5)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect();
+    for (i, line) in lines.iter().enumerate() {
+        canvas.draw_text((34, 32 + i * 11), line, 1, INK, 1.0);
+    }
+
+    pixels
+        .iter()
+        .map(|pixel| ART.iter().position(|color| color == pixel).unwrap_or(0) as u8 * 16)
+        .collect()
+}
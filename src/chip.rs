@@ -1,46 +1,238 @@
+use crate::code_map::CodeMap;
 use crate::instructions::Instructions;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
 use std::io::prelude::*;
+#[cfg(feature = "std")]
 use std::io::BufReader;
-use rand::prelude::*;
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, Rng};
+use crate::screen::Screen;
+use crate::video::DISPLAY_WIDTH;
+use core::fmt;
+
+#[cfg(feature = "std")]
+mod cached;
+#[cfg(feature = "std")]
+mod engine;
+#[cfg(all(feature = "jit", target_arch = "x86_64"))]
+mod jit;
+#[cfg(feature = "std")]
+mod state;
+#[cfg(feature = "std")]
+pub use engine::Engine;
+#[cfg(feature = "std")]
+pub use state::{StateError, STATE_SIZE};
+
+///Instructions per frame unless told otherwise, roughly 600 per second
+pub const DEFAULT_TICK_RATE: u32 = 10;
+
+///Programs are loaded at 0x200, leaving this much room
+pub const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+///Why `load_rom_bytes` refused a program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RomError {
+    Empty,
+    ///Holds the ROM's length
+    TooLarge(usize),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "ROM has no data"),
+            RomError::TooLarge(len) => {
+                write!(f, "ROM is {len} bytes, only {MAX_ROM_SIZE} fit")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomError {}
+
+#[cfg(feature = "std")]
+impl From<RomError> for io::Error {
+    fn from(error: RomError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
 
-pub struct Chip8 {
+///Nesting depth of `2NNN` calls
+pub const STACK_SIZE: usize = 16;
+
+///PC and I are 12 bits wide and memory accesses wrap around at 4 KiB
+const ADDRESS_MASK: u16 = 0x0FFF;
+
+/// The interpreter. `R` supplies `CXNN` random bytes; `Rng` is the seedable
+/// generator the frontends use, save states and movies rely on it.
+#[derive(Clone)]
+pub struct Chip8<R = Rng> {
     memory: [u8; 4096],
     display: [u32; 64 * 32],
+    display_changed: bool,
     program_counter: u16,
     index_register: u16,
-    stack: Vec<u16>,
+    stack: [u16; STACK_SIZE],
+    stack_pointer: u8,
     delay_timer: u8,
     sound_timer: u8,
     keypad: [u8;16],
     variable_registers: [u8; 16],
-    opcode:u16
+    opcode:u16,
+    rng: R,
+    quirks: Quirks,
+    tick_rate: u32,
+    code_map: CodeMap,
+    ///Decoded memory while the cached engine is selected
+    #[cfg(feature = "std")]
+    cache: Option<Box<cached::Cache<R>>>,
+    ///Compiled blocks while the JIT is selected
+    #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+    jit: Option<Box<jit::Jit>>,
 }
 
 impl Chip8 {
     pub fn new() -> Self {
+        Self::with_rng(Rng::default())
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    ///Builds a machine whose `CXNN` results come from `rng`
+    pub fn with_rng(rng: R) -> Self {
         let mut init_chip = Chip8 {
             memory: [0x000; 4096],
             display: [0x000u32; 64 * 32],
+            display_changed: true,
             program_counter: 0x200,
             index_register: 0x0,
-            stack: Vec::with_capacity(16),
+            stack: [0x000; STACK_SIZE],
+            stack_pointer: 0,
             delay_timer: 0x000,
             sound_timer: 0x000,
             keypad:[0x000; 16],
             variable_registers: [0x000; 16],
-            opcode: 0x000
+            opcode: 0x000,
+            rng,
+            quirks: Quirks::default(),
+            tick_rate: DEFAULT_TICK_RATE,
+            code_map: CodeMap::default(),
+            #[cfg(feature = "std")]
+            cache: None,
+            #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+            jit: None,
         };
         init_chip.load_font();
-        return init_chip;
+        init_chip
     }
 
     pub fn display(&self) -> &[u32; 64 * 32] {
         &self.display
     }
 
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    ///V0 to VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.variable_registers
+    }
+
+    ///Return addresses, innermost call last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn memory(&self) -> &[u8; 4096] {
+        &self.memory
+    }
+
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    ///Which memory has run as code and which the program wrote
+    pub fn code_map(&self) -> &CodeMap {
+        &self.code_map
+    }
+
+    ///Whether `present` has a new picture to send
+    #[cfg(test)]
+    pub(crate) fn display_changed(&self) -> bool {
+        self.display_changed
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    ///Copies `bytes` into memory at `address`, dropping whatever runs past 4 KiB
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) {
+        let start = (address as usize).min(self.memory.len());
+        let end = (start + bytes.len()).min(self.memory.len());
+        self.memory[start..end].copy_from_slice(&bytes[..end - start]);
+        self.wrote(start as u16, (end - start) as u16);
+    }
+
+    ///Sets VX
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.variable_registers[x] = value;
+    }
+
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.program_counter = address;
+    }
+
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Sends the picture to `screen` if it changed since the last call.
+    /// Returns whether anything was drawn.
+    pub fn present(&mut self, screen: &mut impl Screen) -> bool {
+        if !self.display_changed {
+            return false;
+        }
+        for (i, pixel) in self.display.iter().enumerate() {
+            screen.set_pixel(i % DISPLAY_WIDTH, i / DISPLAY_WIDTH, *pixel != 0);
+        }
+        screen.flush();
+        self.display_changed = false;
+        true
+    }
+
     fn load_font(&mut self) {
-        let font = vec![
+        let font = [
             0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
             0x20, 0x60, 0x20, 0x20, 0x70, // 1
             0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -61,37 +253,213 @@ impl Chip8 {
         self.memory[0x050..=0x09F].copy_from_slice(&font);
     }
 
-    pub fn load_rom(&mut self, filename: String) {
-        let rom: File =
-            File::open(&filename).expect(format!("Could not open file: {filename}\n").as_str());
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, filename: String) -> io::Result<()> {
+        let rom: File = File::open(&filename)?;
         let mut reader = BufReader::new(rom);
         let mut buffer = Vec::new();
 
-        reader
-            .read_to_end(&mut buffer)
-            .expect("Could not read file properly.");
+        reader.read_to_end(&mut buffer)?;
+
+        // Cartridges bring their own settings
+        #[cfg(feature = "octo")]
+        if crate::cartridge::is_cartridge(&buffer) {
+            let cartridge = crate::cartridge::Cartridge::read(&buffer)?;
+            let rom = cartridge
+                .compile()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            self.load_rom_bytes(&rom)?;
+            cartridge.options.apply(self);
+            return Ok(());
+        }
+        self.load_rom_bytes(&buffer)?;
+        #[cfg(feature = "romdb")]
+        if let Some(info) = crate::romdb::Database::bundled().lookup(&buffer) {
+            info.apply(self);
+        }
+        Ok(())
+    }
 
-        if buffer.len() == 0 {
-            panic!("File has no data\n")
+    ///Copies a program in at 0x200, refusing ones that are empty or don't fit
+    pub fn load_rom_bytes(&mut self, buffer: &[u8]) -> Result<(), RomError> {
+        if buffer.is_empty() {
+            return Err(RomError::Empty);
         }
-        for i in 0..buffer.len() {
-            self.memory[0x200 + i] = buffer[i];
+        if buffer.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge(buffer.len()));
         }
+        self.memory[0x200..0x200 + buffer.len()].copy_from_slice(buffer);
+        self.code_map = CodeMap::default();
+        // Everything, since the code map starts over too
+        self.wrote(0, 4096);
+        Ok(())
     }
+
     pub fn get_input(&mut self, inputs:[u8;16]){
         self.keypad.copy_from_slice(&inputs)
     }
 
+    /// One emulated 60 Hz frame: latch the keypad, execute `tick_rate`
+    /// instructions, then count the timers down once.
+    pub fn run_frame(&mut self, inputs: [u8; 16]) {
+        self.get_input(inputs);
+        self.execute(self.tick_rate);
+        self.tick_timers();
+    }
+
+    ///Runs `count` instructions on the selected engine
+    fn execute(&mut self, count: u32) {
+        #[cfg(feature = "std")]
+        if self.cache.is_some() {
+            self.run_cached(count);
+            return;
+        }
+        #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+        if self.jit.is_some() {
+            self.run_jit(count);
+            return;
+        }
+        for _ in 0..count {
+            self.cycle();
+        }
+    }
+
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    ///Instructions executed per frame
+    pub fn set_tick_rate(&mut self, tick_rate: u32) {
+        self.tick_rate = tick_rate;
+    }
+
+    ///Counts the delay and sound timers down, called at 60 Hz
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0{ self.delay_timer -= 1}
+        if self.sound_timer > 0{ self.sound_timer -= 1}
+    }
+
     pub fn cycle(&mut self){
-        self.opcode = (self.memory[self.program_counter as usize] as u16) << 8
-            | (self.memory[(self.program_counter +1) as usize] as u16);
+        // The PC may have been set from outside, by a debugger or a save state
+        let pc = self.program_counter & ADDRESS_MASK;
+        self.opcode = (self.memory[pc as usize] as u16) << 8
+            | (self.memory[((pc + 1) & ADDRESS_MASK) as usize] as u16);
+        self.code_map.fetch(pc);
 
-        self.program_counter = self.program_counter + 2;
+        self.program_counter = (pc + 2) & ADDRESS_MASK;
 
         self.decode();
+    }
 
-        if self.delay_timer > 0{ self.delay_timer = self.delay_timer - 1}
-        if self.sound_timer > 0{ self.sound_timer = self.sound_timer - 1}
+    ///Where I plus `offset` lands in memory
+    fn address(&self, offset: u16) -> usize {
+        (self.index_register.wrapping_add(offset) & ADDRESS_MASK) as usize
+    }
+
+    fn skip(&mut self) {
+        self.program_counter = (self.program_counter + 2) & ADDRESS_MASK;
+    }
+
+    ///Keeps decoded instructions in step with `len` bytes written from `address`
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn wrote(&mut self, address: u16, len: u16) {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &mut self.cache {
+            cache.refresh(&self.memory, address, len);
+        }
+        #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+        if let Some(jit) = &mut self.jit {
+            jit.invalidate(address, len);
+        }
+    }
+
+    ///Notes a store by the program itself
+    fn stored(&mut self, address: u16, len: u16) {
+        self.code_map.store(address, len);
+        self.wrote(address, len);
+    }
+
+    ///Draws the `height` byte sprite at I at (VX, VY)
+    fn draw(&mut self, x: usize, y: usize, height: u16) {
+        let x_coord = (self.variable_registers[x] % 64) as u16;
+        let y_coord = (self.variable_registers[y] % 32) as u16;
+        // After reading the coordinates, VF may be one of them
+        self.variable_registers[0xF] = 0;
+        self.display_changed = true;
+
+        for row in 0..height {
+            let sprite_data = self.memory[self.address(row)];
+
+            for col in 0..8u16 {
+                let pixel_data = sprite_data & (0x80 >> col);
+                if pixel_data != 0 {
+                    let mut x = x_coord + col;
+                    let mut y = y_coord + row;
+                    if x >= 64 || y >= 32 {
+                        if self.quirks.clip_sprites {
+                            continue;
+                        }
+                        x %= 64;
+                        y %= 32;
+                    }
+                    let i = (x + y * 64) as usize;
+                    if self.display[i] == 0xFFFFFFFF {
+                        self.variable_registers[0xF] = 1;
+                    }
+                    self.display[i] ^= 0xFFFFFFFF;
+                }
+            }
+        }
+    }
+
+    ///Pushes the PC and jumps to `address`, unless the stack is full
+    fn call(&mut self, address: u16) {
+        // Calls past the bottom of the stack are dropped
+        if self.stack_pointer as usize == STACK_SIZE {
+            return;
+        }
+        self.stack[self.stack_pointer as usize] = self.program_counter;
+        self.stack_pointer += 1;
+        self.program_counter = address;
+    }
+
+    ///VX as three decimal digits at I
+    fn bcd(&mut self, x: usize) {
+        let data = self.variable_registers[x];
+        self.memory[self.address(0)] = data / 100;
+        self.memory[self.address(1)] = data / 10 % 10;
+        self.memory[self.address(2)] = data % 10;
+        self.stored(self.address(0) as u16, 3);
+    }
+
+    ///V0 to VX into memory at I
+    fn store_registers(&mut self, x: u16) {
+        for r in 0..=x {
+            self.memory[self.address(r)] = self.variable_registers[r as usize];
+        }
+        self.stored(self.address(0) as u16, x + 1);
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.address(x + 1) as u16;
+        }
+    }
+
+    ///V0 to VX from memory at I
+    fn load_registers(&mut self, x: u16) {
+        for r in 0..=x {
+            self.variable_registers[r as usize] = self.memory[self.address(r)];
+        }
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.address(x + 1) as u16;
+        }
+    }
+
+    ///VX becomes the lowest key down, or the instruction runs again
+    fn wait_for_key(&mut self, x: usize) {
+        match self.keypad.iter().position(|key| *key != 0) {
+            Some(key) => self.variable_registers[x] = key as u8,
+            // Run this instruction again until a key is down
+            None => self.program_counter = self.program_counter.wrapping_sub(2) & ADDRESS_MASK,
+        }
     }
 
     fn decode(&mut self){
@@ -99,10 +467,9 @@ impl Chip8 {
 
         match ins {
             0x0000 => {
-                let last_bit = self.opcode & 0x000F;
-                match last_bit{
-                    0x0 => self.ins_00e0(),
-                    0xE => self.ins_00ee(),
+                match self.opcode {
+                    0x00E0 => self.ins_00e0(),
+                    0x00EE => self.ins_00ee(),
                     _   => self.ins_null()
                 }
             },
@@ -110,7 +477,12 @@ impl Chip8 {
             0x2000 => self.ins_2nnn(),
             0x3000 => self.ins_3xnn(),
             0x4000 => self.ins_4xnn(),
-            0x5000 => self.ins_5xy0(),
+            0x5000 => {
+                match self.opcode & 0x000F {
+                    0x0 => self.ins_5xy0(),
+                    _   => self.ins_null()
+                }
+            },
             0x6000 => self.ins_6xnn(),
             0x7000 => self.ins_7xnn(),
             0x8000 => {
@@ -128,7 +500,12 @@ impl Chip8 {
                     _   => self.ins_null()
                 }
             },
-            0x9000 => self.ins_9xy0(),
+            0x9000 => {
+                match self.opcode & 0x000F {
+                    0x0 => self.ins_9xy0(),
+                    _   => self.ins_null()
+                }
+            },
             0xA000 => self.ins_annn(),
             0xB000 => self.ins_bnnn(),
             0xC000 => self.ins_cxnn(),
@@ -146,7 +523,7 @@ impl Chip8 {
                 match last_bit{
                     0x07 => self.ins_fx07(),
                     0x0A => self.ins_fx0a(),
-                    0x15 => self.ins_fx18(),
+                    0x15 => self.ins_fx15(),
                     0x18 => self.ins_fx18(),
                     0x1E => self.ins_fx1e(),
                     0x29 => self.ins_fx29(),
@@ -160,14 +537,18 @@ impl Chip8 {
         }
     }
 }
-#[allow(dead_code)]
-impl Instructions for Chip8 {
-    fn ins_null(&mut self) {
-        return
+impl<R: RandomSource + Default> Default for Chip8<R> {
+    fn default() -> Self {
+        Self::with_rng(R::default())
     }
+}
+
+#[allow(dead_code)]
+impl<R: RandomSource> Instructions for Chip8<R> {
+    fn ins_null(&mut self) {}
     fn ins_00e0(&mut self) {
-        let screen_size = self.display.len();
-        self.display[0..screen_size].copy_from_slice(&vec![0x000u32; screen_size]);
+        self.display.fill(0x000);
+        self.display_changed = true;
     }
 
     fn ins_1nnn(&mut self) {
@@ -190,48 +571,29 @@ impl Instructions for Chip8 {
     }
 
     fn ins_dxyn(&mut self){
-        self.variable_registers[0xF] = 0;
-        let vx:u8 = ((self.opcode & 0x0F00) >> 8u8) as u8;
-        let vy:u8 = ((self.opcode & 0x00F0) >> 4u8) as u8;
-
-        let x_coord:u16 = (self.variable_registers[vx as usize] % 64) as u16;
-        let y_coord:u16 = (self.variable_registers[vy as usize] % 32) as u16;
-
-        let height = self.opcode & 0x000F;
-
-        for row in 0..height {
-            let sprite_data = self.memory[(self.index_register+row) as usize];
-
-            for col in 0..8u16{
-                let pixel_data = sprite_data & (0x80 >> col);
-                if pixel_data != 0{
-                    let mut i:u16 = x_coord + col + ((y_coord + row) * 64);
-                    if i > self.display.len() as u16 {i = self.display.len() as u16 - 1}
-                    if self.display[i as usize] == 0xFFFFFFFF {
-                        self.variable_registers[0xF] = 1;
-                    }
-                    self.display[i as usize] ^= 0xFFFFFFFF;
-                }
-            }
-        }
-
+        let vx = (self.opcode & 0x0F00) >> 8u8;
+        let vy = (self.opcode & 0x00F0) >> 4u8;
+        self.draw(vx as usize, vy as usize, self.opcode & 0x000F);
     }
 
     fn ins_2nnn(&mut self) {
-        let mem_loc = self.opcode & 0x0FFF;
-        self.stack.push(self.program_counter);
-        self.program_counter = mem_loc;
+        self.call(self.opcode & 0x0FFF);
     }
 
     fn ins_00ee(&mut self) {
-        self.program_counter = self.stack.pop().unwrap();
+        // A return with nothing to return to does nothing
+        let Some(pointer) = self.stack_pointer.checked_sub(1) else {
+            return;
+        };
+        self.stack_pointer = pointer;
+        self.program_counter = self.stack[self.stack_pointer as usize];
     }
 
     fn ins_3xnn(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let data:u8 = (self.opcode & 0x00FF) as u8;
         if self.variable_registers[vx as usize] == data {
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
@@ -239,7 +601,7 @@ impl Instructions for Chip8 {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let data:u8 = (self.opcode & 0x00FF) as u8;
         if self.variable_registers[vx as usize] != data {
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
@@ -247,7 +609,7 @@ impl Instructions for Chip8 {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let vy= (self.opcode & 0x00F0) >> 4u8;
         if self.variable_registers[vx as usize] == self.variable_registers[vy as usize] {
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
@@ -255,7 +617,7 @@ impl Instructions for Chip8 {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let vy= (self.opcode & 0x00F0) >> 4u8;
         if self.variable_registers[vx as usize] != self.variable_registers[vy as usize] {
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
@@ -270,21 +632,24 @@ impl Instructions for Chip8 {
     fn ins_8xy1(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let vy= (self.opcode & 0x00F0) >> 4u8;
-        self.variable_registers[vx as usize] = self.variable_registers[vx as usize] | self.variable_registers[vy as usize]
+        self.variable_registers[vx as usize] |= self.variable_registers[vy as usize];
+        if self.quirks.vf_reset { self.variable_registers[0xF] = 0; }
     }
 
 
     fn ins_8xy2(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let vy= (self.opcode & 0x00F0) >> 4u8;
-        self.variable_registers[vx as usize] = self.variable_registers[vx as usize] & self.variable_registers[vy as usize]
+        self.variable_registers[vx as usize] &= self.variable_registers[vy as usize];
+        if self.quirks.vf_reset { self.variable_registers[0xF] = 0; }
     }
 
 
     fn ins_8xy3(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
         let vy= (self.opcode & 0x00F0) >> 4u8;
-        self.variable_registers[vx as usize] = self.variable_registers[vx as usize] ^ self.variable_registers[vy as usize]
+        self.variable_registers[vx as usize] ^= self.variable_registers[vy as usize];
+        if self.quirks.vf_reset { self.variable_registers[0xF] = 0; }
     }
 
 
@@ -324,51 +689,54 @@ impl Instructions for Chip8 {
 
     fn ins_8xye(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let data = self.variable_registers[vx as usize] << 1u8;
-
-        self.variable_registers[0xF] = (self.variable_registers[vx as usize] & 0x80u8) >> 7u8;
+        let src = if self.quirks.shift_uses_vy { (self.opcode & 0x00F0) >> 4u8 } else { vx };
+        let data = self.variable_registers[src as usize] << 1u8;
+        let flag = (self.variable_registers[src as usize] & 0x80u8) >> 7u8;
 
         self.variable_registers[vx as usize] =data;
+        self.variable_registers[0xF] = flag;
     }
 
 
     fn ins_8xy6(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let data = self.variable_registers[vx as usize] >> 1u8;
-
-        self.variable_registers[0xF] = (self.variable_registers[vx as usize] & 0x8u8) >> 7u8;
+        let src = if self.quirks.shift_uses_vy { (self.opcode & 0x00F0) >> 4u8 } else { vx };
+        let data = self.variable_registers[src as usize] >> 1u8;
+        let flag = self.variable_registers[src as usize] & 0x1u8;
 
         self.variable_registers[vx as usize] =data;
+        self.variable_registers[0xF] = flag;
     }
 
 
     fn ins_bnnn(&mut self) {
-        self.index_register = (self.opcode & 0x0FFF) + (self.variable_registers[0x0]) as u16
+        let offset = if self.quirks.jump_uses_vx { (self.opcode & 0x0F00) >> 8u8 } else { 0x0 };
+        self.program_counter = ((self.opcode & 0x0FFF) + (self.variable_registers[offset as usize]) as u16) & ADDRESS_MASK;
     }
 
 
     fn ins_cxnn(&mut self) {
-        let rand_number = random::<u16>();
+        let rand_number = self.rng.next_byte();
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let data = self.opcode & 0x00FF;
-        self.variable_registers[vx as usize] = (data & rand_number) as u8;
+        let data = (self.opcode & 0x00FF) as u8;
+        self.variable_registers[vx as usize] = data & rand_number;
     }
 
 
     fn ins_ex9e(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let key = self.variable_registers[vx as usize];
+        let key = self.variable_registers[vx as usize] & 0xF;
         if self.keypad[key as usize] != 0{
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
 
     fn ins_exa1(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let key = self.variable_registers[vx as usize];
+        let key = self.variable_registers[vx as usize] & 0xF;
         if self.keypad[key as usize] == 0 {
-            self.program_counter = self.program_counter + 2;
+            self.skip();
         }
     }
 
@@ -393,57 +761,38 @@ impl Instructions for Chip8 {
 
     fn ins_fx1e(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        self.index_register = self.index_register + self.variable_registers[vx as usize] as u16;
+        self.index_register = self.index_register.wrapping_add(self.variable_registers[vx as usize] as u16) & ADDRESS_MASK;
     }
 
 
     fn ins_fx0a(&mut self) {
-        self.program_counter = self.program_counter - 2;
+        let vx = (self.opcode & 0x0F00) >> 8u8;
+        self.wait_for_key(vx as usize);
     }
 
 
     fn ins_fx29(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        self.index_register = 0x050 + (self.variable_registers[vx as usize] * 5) as u16;
+        // Only the low digit has a glyph
+        self.index_register = 0x050 + (self.variable_registers[vx as usize] & 0xF) as u16 * 5;
     }
 
 
     fn ins_fx33(&mut self) {
-
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        let data = self.variable_registers[vx as usize];
-        let pos = self.index_register;
-        let ones = data % 10;
-        let tens = ((data % 100) - ones) / 10;
-        let hundreds = (data - (data % 100)) / 100;
-        self.memory[pos as usize] = hundreds;
-        self.memory[(pos + 1) as usize] = tens;
-        self.memory[(pos + 2) as usize] = ones;
+        self.bcd(vx as usize);
     }
 
 
     fn ins_fx55(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        if vx == 0x0 {
-            self.memory[self.index_register as usize] = self.variable_registers[0x0];
-            return;
-        }else{
-            self.memory[self.index_register as usize..=(self.index_register + vx) as usize].
-                copy_from_slice(&self.variable_registers[0x0..vx as usize + 1]);
-        }
+        self.store_registers(vx);
     }
 
 
     fn ins_fx65(&mut self) {
         let vx = (self.opcode & 0x0F00) >> 8u8;
-        if vx == 0x0 {
-            self.variable_registers[0x0] = self.memory[self.index_register as usize];
-            return;
-        }else{
-            self.variable_registers[0x0..=vx as usize]
-                .copy_from_slice(&self.memory[self.index_register as usize..(self.index_register + vx) as usize + 1]);
-        }
-
+        self.load_registers(vx);
     }
 
 }
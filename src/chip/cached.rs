@@ -0,0 +1,284 @@
+//! An alternative to fetching and decoding every instruction as it runs.
+//! Memory is decoded up front into one entry per address, a handler plus the
+//! operands it needs, and any write to memory re-decodes the entries it
+//! touched. Execution goes a block at a time: straight-line code up to and
+//! including the next instruction that reads or sets the PC. The handlers
+//! share the tricky parts (drawing, stores, calls) with the interpreter, and
+//! the results are identical, down to the code map and the save state.
+
+use super::{Chip8, ADDRESS_MASK};
+use crate::instructions::Instructions;
+use crate::rng::RandomSource;
+
+///The fields of an opcode, extracted once when it's decoded
+#[derive(Clone, Copy)]
+struct Operands {
+    x: u8,
+    y: u8,
+    n: u8,
+    nn: u8,
+    nnn: u16,
+}
+
+type Handler<R> = fn(&mut Chip8<R>, Operands);
+
+struct Entry<R> {
+    handler: Handler<R>,
+    operands: Operands,
+    opcode: u16,
+    ///Reads or sets the PC, so the block stops here
+    branch: bool,
+    /// Already run, from bytes the program hasn't written, so fetching it
+    /// again would tell the code map nothing new
+    seen: bool,
+}
+
+// Derived impls would ask for `R: Copy`, which a function pointer doesn't need
+impl<R> Clone for Entry<R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for Entry<R> {}
+
+pub(crate) struct Cache<R> {
+    ///The instruction starting at each address
+    entries: Box<[Entry<R>; 4096]>,
+}
+
+impl<R> Clone for Cache<R> {
+    fn clone(&self) -> Self {
+        Cache {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<R: RandomSource> Cache<R> {
+    pub(super) fn new(memory: &[u8; 4096]) -> Self {
+        let entries: Vec<Entry<R>> = (0..4096).map(|pc| decode(memory, pc)).collect();
+        Cache {
+            entries: entries.into_boxed_slice().try_into().ok().unwrap(),
+        }
+    }
+
+    /// Re-decodes everything that reads the `len` bytes from `address`,
+    /// including the instruction that starts on the byte before.
+    pub(crate) fn refresh(&mut self, memory: &[u8; 4096], address: u16, len: u16) {
+        let first = address.wrapping_sub(1);
+        for offset in 0..=len.min(4096) {
+            let pc = first.wrapping_add(offset) & ADDRESS_MASK;
+            self.entries[pc as usize] = decode(memory, pc);
+        }
+    }
+}
+
+fn decode<R: RandomSource>(memory: &[u8; 4096], pc: u16) -> Entry<R> {
+    let opcode = u16::from_be_bytes([
+        memory[pc as usize],
+        memory[((pc + 1) & ADDRESS_MASK) as usize],
+    ]);
+    let operands = Operands {
+        x: (opcode >> 8 & 0xF) as u8,
+        y: (opcode >> 4 & 0xF) as u8,
+        n: (opcode & 0xF) as u8,
+        nn: opcode as u8,
+        nnn: opcode & ADDRESS_MASK,
+    };
+    let (handler, branch) = handler(opcode);
+    Entry {
+        handler,
+        operands,
+        opcode,
+        branch,
+        seen: false,
+    }
+}
+
+fn step<R>(handler: Handler<R>) -> (Handler<R>, bool) {
+    (handler, false)
+}
+
+fn branch<R>(handler: Handler<R>) -> (Handler<R>, bool) {
+    (handler, true)
+}
+
+///What runs `opcode`, and whether it's a branch
+fn handler<R: RandomSource>(opcode: u16) -> (Handler<R>, bool) {
+    match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+        (0x0, _, _) if opcode == 0x00E0 => step(|chip, _| chip.ins_00e0()),
+        (0x0, _, _) if opcode == 0x00EE => branch(|chip, _| chip.ins_00ee()),
+        (0x1, _, _) => branch(|chip, op| chip.program_counter = op.nnn),
+        (0x2, _, _) => branch(|chip, op| chip.call(op.nnn)),
+        (0x3, _, _) => branch(|chip, op| {
+            if chip.variable_registers[op.x as usize] == op.nn {
+                chip.skip();
+            }
+        }),
+        (0x4, _, _) => branch(|chip, op| {
+            if chip.variable_registers[op.x as usize] != op.nn {
+                chip.skip();
+            }
+        }),
+        (0x5, 0x0, _) => branch(|chip, op| {
+            let v = &chip.variable_registers;
+            if v[op.x as usize] == v[op.y as usize] {
+                chip.skip();
+            }
+        }),
+        (0x6, _, _) => step(|chip, op| chip.variable_registers[op.x as usize] = op.nn),
+        (0x7, _, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            v[op.x as usize] = v[op.x as usize].wrapping_add(op.nn);
+        }),
+        (0x8, 0x0, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            v[op.x as usize] = v[op.y as usize];
+        }),
+        (0x8, 0x1, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            v[op.x as usize] |= v[op.y as usize];
+            if chip.quirks.vf_reset {
+                v[0xF] = 0;
+            }
+        }),
+        (0x8, 0x2, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            v[op.x as usize] &= v[op.y as usize];
+            if chip.quirks.vf_reset {
+                v[0xF] = 0;
+            }
+        }),
+        (0x8, 0x3, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            v[op.x as usize] ^= v[op.y as usize];
+            if chip.quirks.vf_reset {
+                v[0xF] = 0;
+            }
+        }),
+        (0x8, 0x4, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            let (sum, carry) = v[op.x as usize].overflowing_add(v[op.y as usize]);
+            v[op.x as usize] = sum;
+            v[0xF] = carry as u8;
+        }),
+        (0x8, 0x5, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            let (difference, borrow) = v[op.x as usize].overflowing_sub(v[op.y as usize]);
+            v[op.x as usize] = difference;
+            v[0xF] = !borrow as u8;
+        }),
+        (0x8, 0x6, _) => step(|chip, op| {
+            let src = if chip.quirks.shift_uses_vy {
+                op.y
+            } else {
+                op.x
+            };
+            let v = &mut chip.variable_registers;
+            let value = v[src as usize];
+            v[op.x as usize] = value >> 1;
+            v[0xF] = value & 1;
+        }),
+        (0x8, 0x7, _) => step(|chip, op| {
+            let v = &mut chip.variable_registers;
+            let (difference, borrow) = v[op.y as usize].overflowing_sub(v[op.x as usize]);
+            v[op.x as usize] = difference;
+            v[0xF] = !borrow as u8;
+        }),
+        (0x8, 0xE, _) => step(|chip, op| {
+            let src = if chip.quirks.shift_uses_vy {
+                op.y
+            } else {
+                op.x
+            };
+            let v = &mut chip.variable_registers;
+            let value = v[src as usize];
+            v[op.x as usize] = value << 1;
+            v[0xF] = value >> 7;
+        }),
+        (0x9, 0x0, _) => branch(|chip, op| {
+            let v = &chip.variable_registers;
+            if v[op.x as usize] != v[op.y as usize] {
+                chip.skip();
+            }
+        }),
+        (0xA, _, _) => step(|chip, op| chip.index_register = op.nnn),
+        (0xB, _, _) => branch(|chip, op| {
+            let offset = if chip.quirks.jump_uses_vx { op.x } else { 0 };
+            let target = op.nnn + chip.variable_registers[offset as usize] as u16;
+            chip.program_counter = target & ADDRESS_MASK;
+        }),
+        (0xC, _, _) => step(|chip, op| {
+            let random = chip.rng.next_byte();
+            chip.variable_registers[op.x as usize] = op.nn & random;
+        }),
+        (0xD, _, _) => step(|chip, op| chip.draw(op.x as usize, op.y as usize, op.n as u16)),
+        (0xE, _, 0x9E) => branch(|chip, op| {
+            let key = chip.variable_registers[op.x as usize] & 0xF;
+            if chip.keypad[key as usize] != 0 {
+                chip.skip();
+            }
+        }),
+        (0xE, _, 0xA1) => branch(|chip, op| {
+            let key = chip.variable_registers[op.x as usize] & 0xF;
+            if chip.keypad[key as usize] == 0 {
+                chip.skip();
+            }
+        }),
+        (0xF, _, 0x07) => {
+            step(|chip, op| chip.variable_registers[op.x as usize] = chip.delay_timer)
+        }
+        (0xF, _, 0x0A) => branch(|chip, op| chip.wait_for_key(op.x as usize)),
+        (0xF, _, 0x15) => {
+            step(|chip, op| chip.delay_timer = chip.variable_registers[op.x as usize])
+        }
+        (0xF, _, 0x18) => {
+            step(|chip, op| chip.sound_timer = chip.variable_registers[op.x as usize])
+        }
+        (0xF, _, 0x1E) => step(|chip, op| {
+            let i = chip
+                .index_register
+                .wrapping_add(chip.variable_registers[op.x as usize] as u16);
+            chip.index_register = i & ADDRESS_MASK;
+        }),
+        (0xF, _, 0x29) => step(|chip, op| {
+            chip.index_register = 0x050 + (chip.variable_registers[op.x as usize] & 0xF) as u16 * 5;
+        }),
+        (0xF, _, 0x33) => step(|chip, op| chip.bcd(op.x as usize)),
+        (0xF, _, 0x55) => step(|chip, op| chip.store_registers(op.x as u16)),
+        (0xF, _, 0x65) => step(|chip, op| chip.load_registers(op.x as u16)),
+        _ => step(|_, _| {}),
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    pub(super) fn run_cached(&mut self, count: u32) {
+        let mut remaining = count;
+        while remaining > 0 {
+            // Nothing before the branch that ends a block looks at the PC,
+            // so it only needs writing back at the end
+            let mut pc = self.program_counter & ADDRESS_MASK;
+            loop {
+                let Some(cache) = &mut self.cache else {
+                    return;
+                };
+                let entry = &mut cache.entries[pc as usize];
+                if !entry.seen {
+                    self.code_map.fetch(pc);
+                    entry.seen = !self.code_map.written(pc) && !self.code_map.written(pc + 1);
+                }
+                let entry = *entry;
+                self.opcode = entry.opcode;
+                pc = (pc + 2) & ADDRESS_MASK;
+                remaining -= 1;
+                if entry.branch || remaining == 0 {
+                    self.program_counter = pc;
+                    (entry.handler)(self, entry.operands);
+                    break;
+                }
+                (entry.handler)(self, entry.operands);
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use super::cached::Cache;
+use super::Chip8;
+use crate::rng::RandomSource;
+
+///How `run_frame` executes instructions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    ///Fetches and decodes each instruction as it runs
+    #[default]
+    Interpreter,
+    ///Runs from memory decoded ahead of time
+    Cached,
+    ///Compiles blocks to x86-64 machine code
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl Engine {
+    ///Every engine this build has
+    pub const ALL: &'static [Engine] = &[
+        Engine::Interpreter,
+        Engine::Cached,
+        #[cfg(feature = "jit")]
+        Engine::Jit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Interpreter => "interpreter",
+            Engine::Cached => "cached",
+            #[cfg(feature = "jit")]
+            Engine::Jit => "jit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Engine::ALL.iter().copied().find(|e| e.name() == name)
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    pub fn engine(&self) -> Engine {
+        #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+        if self.jit.is_some() {
+            return Engine::Jit;
+        }
+        match self.cache {
+            Some(_) => Engine::Cached,
+            None => Engine::Interpreter,
+        }
+    }
+
+    /// Switching to the cached engine decodes all of memory. The JIT compiles
+    /// as it goes, and off x86-64 it leaves the interpreter in charge.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.cache = None;
+        #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+        {
+            self.jit = None;
+        }
+        match engine {
+            Engine::Interpreter => {}
+            Engine::Cached => self.cache = Some(Box::new(Cache::new(&self.memory))),
+            #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+            Engine::Jit => self.jit = Some(Box::new(super::jit::Jit::new(self.quirks))),
+            #[cfg(all(feature = "jit", not(target_arch = "x86_64")))]
+            Engine::Jit => {}
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! Compiles CHIP-8 to x86-64 a block at a time. A block is straight-line
+//! register, timer and index arithmetic, ended by a jump or skip that sets
+//! the PC natively. Anything with more to it (drawing, key waits, `CXNN`,
+//! calls, returns and every store) is left to the interpreter: the block stops
+//! short of it and `cycle` runs it, so a store that rewrites code always goes
+//! through `wrote`, which drops the blocks compiled from those bytes.
+//!
+//! Compiled code reads and writes the machine's fields in place through their
+//! `offset_of!` offsets, and the quirks are baked in, so changing them starts
+//! over. Like the cached engine it matches the interpreter exactly, code map
+//! and save state included.
+
+use super::{Chip8, ADDRESS_MASK};
+use crate::quirks::Quirks;
+use crate::rng::RandomSource;
+use core::mem::{self, offset_of};
+use dynasmrt::x64::Assembler;
+use dynasmrt::{dynasm, AssemblyOffset, DynasmApi};
+
+///Most instructions compiled into one block
+const MAX_BLOCK: u16 = 64;
+///Blocks start no later than this, so none runs off the end of memory
+const LAST_PC: u16 = 0xFFC;
+///Bytes of machine code before starting over. A block from every address at
+///once fits, so only code left behind by stores can fill it.
+const CODE_LIMIT: usize = 16 << 20;
+
+#[derive(Clone, Copy)]
+enum Slot {
+    ///Not compiled yet, or written over since
+    Unknown,
+    ///Starts with an instruction the interpreter has to run
+    Interpret,
+    Native(Block),
+}
+
+impl Slot {
+    ///How much memory it was compiled from
+    fn bytes(&self) -> usize {
+        match self {
+            Slot::Unknown => 0,
+            Slot::Interpret => 2,
+            Slot::Native(block) => 2 * block.len as usize,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Block {
+    code: AssemblyOffset,
+    ///Instructions, counting the branch at the end
+    len: u16,
+    ///Already run, from bytes the program hasn't written, so its fetches
+    ///would tell the code map nothing new
+    seen: bool,
+}
+
+///What compiling one instruction did
+enum Emitted {
+    Step,
+    ///Set the PC, so the block ends here
+    Branch,
+    ///Nothing, the interpreter has to run it
+    Exit,
+}
+
+///Where the fields compiled code touches live in a `Chip8<R>`
+struct Fields {
+    v: i32,
+    i: i32,
+    pc: i32,
+    opcode: i32,
+    dt: i32,
+    st: i32,
+    keypad: i32,
+}
+
+impl Fields {
+    fn of<R>() -> Self {
+        Fields {
+            v: offset_of!(Chip8<R>, variable_registers) as i32,
+            i: offset_of!(Chip8<R>, index_register) as i32,
+            pc: offset_of!(Chip8<R>, program_counter) as i32,
+            opcode: offset_of!(Chip8<R>, opcode) as i32,
+            dt: offset_of!(Chip8<R>, delay_timer) as i32,
+            st: offset_of!(Chip8<R>, sound_timer) as i32,
+            keypad: offset_of!(Chip8<R>, keypad) as i32,
+        }
+    }
+}
+
+pub(crate) struct Jit {
+    assembler: Assembler,
+    ///The block starting at each address
+    slots: Vec<Slot>,
+    ///What the blocks were compiled for
+    quirks: Quirks,
+}
+
+// Machine code can't be copied, so a copy compiles its own
+impl Clone for Jit {
+    fn clone(&self) -> Self {
+        Jit::new(self.quirks)
+    }
+}
+
+impl Jit {
+    pub(super) fn new(quirks: Quirks) -> Self {
+        Jit {
+            assembler: Assembler::new().expect("Could not map memory for the JIT"),
+            slots: vec![Slot::Unknown; 4096],
+            quirks,
+        }
+    }
+
+    ///Forgets every block compiled from the `len` bytes at `address`
+    pub(super) fn invalidate(&mut self, address: u16, len: u16) {
+        let (start, end) = (address as usize, address as usize + len as usize);
+        // Stores wrap around the end of memory
+        if end > 4096 {
+            self.invalidate(0, (end - 4096) as u16);
+        }
+        let first = start.saturating_sub(2 * MAX_BLOCK as usize - 1);
+        for pc in first..end.min(4096) {
+            if pc + self.slots[pc].bytes() > start {
+                self.slots[pc] = Slot::Unknown;
+            }
+        }
+    }
+
+    ///Compiles as much as it can from `start`
+    fn compile<R>(&mut self, memory: &[u8; 4096], start: u16) -> Slot {
+        let at = Fields::of::<R>();
+        let code = self.assembler.offset();
+        let (mut pc, mut len, mut opcode) = (start, 0, 0);
+        let mut branched = false;
+        while pc <= LAST_PC && len < MAX_BLOCK && !branched {
+            let next = u16::from_be_bytes([memory[pc as usize], memory[pc as usize + 1]]);
+            match self.emit(&at, next, pc) {
+                Emitted::Exit => break,
+                Emitted::Step => {}
+                Emitted::Branch => branched = true,
+            }
+            (pc, len, opcode) = (pc + 2, len + 1, next);
+        }
+        if len == 0 {
+            return Slot::Interpret;
+        }
+
+        let ops = &mut self.assembler;
+        if !branched {
+            dynasm!(ops ; .arch x64 ; mov WORD [rdi + at.pc], pc as i16);
+        }
+        dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + at.opcode], opcode as i16
+            ; ret
+        );
+        match self.assembler.commit() {
+            Ok(()) => Slot::Native(Block {
+                code,
+                len,
+                seen: false,
+            }),
+            Err(_) => Slot::Interpret,
+        }
+    }
+
+    ///Compiles the instruction at `pc`, unless it's one for the interpreter
+    fn emit(&mut self, at: &Fields, opcode: u16, pc: u16) -> Emitted {
+        let x = (opcode >> 8 & 0xF) as i32;
+        let y = (opcode >> 4 & 0xF) as i32;
+        let (vx, vy, vf) = (at.v + x, at.v + y, at.v + 0xF);
+        let nn = opcode as u8 as i8;
+        let nnn = (opcode & ADDRESS_MASK) as i16;
+        let shifted = if self.quirks.shift_uses_vy { vy } else { vx };
+        let vf_reset = self.quirks.vf_reset;
+        let ops = &mut self.assembler;
+
+        match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+            (0x0, _, _) if opcode == 0x00E0 || opcode == 0x00EE => return Emitted::Exit,
+            (0x1, _, _) => {
+                dynasm!(ops ; .arch x64 ; mov WORD [rdi + at.pc], nnn);
+                return Emitted::Branch;
+            }
+            (0x3, _, _) => {
+                dynasm!(ops ; .arch x64 ; cmp BYTE [rdi + vx], nn);
+                return skip(ops, at, pc, true);
+            }
+            (0x4, _, _) => {
+                dynasm!(ops ; .arch x64 ; cmp BYTE [rdi + vx], nn);
+                return skip(ops, at, pc, false);
+            }
+            (0x5 | 0x9, 0x0, _) => {
+                dynasm!(ops
+                    ; .arch x64
+                    ; mov al, BYTE [rdi + vx]
+                    ; cmp al, BYTE [rdi + vy]
+                );
+                return skip(ops, at, pc, opcode >> 12 == 0x5);
+            }
+            (0x6, _, _) => dynasm!(ops ; .arch x64 ; mov BYTE [rdi + vx], nn),
+            (0x7, _, _) => dynasm!(ops ; .arch x64 ; add BYTE [rdi + vx], nn),
+            (0x8, 0x0, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vy]
+                ; mov BYTE [rdi + vx], al
+            ),
+            (0x8, 0x1..=0x3, _) => {
+                dynasm!(ops ; .arch x64 ; mov al, BYTE [rdi + vy]);
+                match opcode & 0xF {
+                    0x1 => dynasm!(ops ; .arch x64 ; or BYTE [rdi + vx], al),
+                    0x2 => dynasm!(ops ; .arch x64 ; and BYTE [rdi + vx], al),
+                    _ => dynasm!(ops ; .arch x64 ; xor BYTE [rdi + vx], al),
+                }
+                if vf_reset {
+                    dynasm!(ops ; .arch x64 ; mov BYTE [rdi + vf], 0);
+                }
+            }
+            // Flags are written after results, so VF as X ends up the flag
+            (0x8, 0x4, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vx]
+                ; add al, BYTE [rdi + vy]
+                ; setc cl
+                ; mov BYTE [rdi + vx], al
+                ; mov BYTE [rdi + vf], cl
+            ),
+            (0x8, 0x5, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vx]
+                ; sub al, BYTE [rdi + vy]
+                ; setnc cl
+                ; mov BYTE [rdi + vx], al
+                ; mov BYTE [rdi + vf], cl
+            ),
+            (0x8, 0x7, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vy]
+                ; sub al, BYTE [rdi + vx]
+                ; setnc cl
+                ; mov BYTE [rdi + vx], al
+                ; mov BYTE [rdi + vf], cl
+            ),
+            (0x8, 0x6, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + shifted]
+                ; mov cl, al
+                ; and cl, 1
+                ; shr al, 1
+                ; mov BYTE [rdi + vx], al
+                ; mov BYTE [rdi + vf], cl
+            ),
+            (0x8, 0xE, _) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + shifted]
+                ; mov cl, al
+                ; shr cl, 7
+                ; shl al, 1
+                ; mov BYTE [rdi + vx], al
+                ; mov BYTE [rdi + vf], cl
+            ),
+            (0xA, _, _) => dynasm!(ops ; .arch x64 ; mov WORD [rdi + at.i], nnn),
+            (0xB..=0xD, _, _) => return Emitted::Exit,
+            (0xE, _, 0x9E | 0xA1) => {
+                dynasm!(ops
+                    ; .arch x64
+                    ; movzx eax, BYTE [rdi + vx]
+                    ; and eax, 0xF
+                    ; cmp BYTE [rdi + rax + at.keypad], 0
+                );
+                // EX9E skips while the key is down, EXA1 while it's up
+                return skip(ops, at, pc, opcode & 0xFF == 0xA1);
+            }
+            (0xF, _, 0x07) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + at.dt]
+                ; mov BYTE [rdi + vx], al
+            ),
+            (0xF, _, 0x15) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vx]
+                ; mov BYTE [rdi + at.dt], al
+            ),
+            (0xF, _, 0x18) => dynasm!(ops
+                ; .arch x64
+                ; mov al, BYTE [rdi + vx]
+                ; mov BYTE [rdi + at.st], al
+            ),
+            (0xF, _, 0x1E) => dynasm!(ops
+                ; .arch x64
+                ; movzx eax, BYTE [rdi + vx]
+                ; add ax, WORD [rdi + at.i]
+                ; and ax, ADDRESS_MASK as i16
+                ; mov WORD [rdi + at.i], ax
+            ),
+            (0xF, _, 0x29) => dynasm!(ops
+                ; .arch x64
+                ; movzx eax, BYTE [rdi + vx]
+                ; and eax, 0xF
+                ; imul eax, eax, 5
+                ; add eax, 0x50
+                ; mov WORD [rdi + at.i], ax
+            ),
+            (0x2, _, _) | (0xF, _, 0x0A | 0x33 | 0x55 | 0x65) => return Emitted::Exit,
+            // Everything else does nothing
+            _ => {}
+        }
+        Emitted::Step
+    }
+}
+
+///Sets the PC to skip the next instruction if the flags say equal, or not
+fn skip(ops: &mut Assembler, at: &Fields, pc: u16, when_equal: bool) -> Emitted {
+    let next = ((pc + 2) & ADDRESS_MASK) as i16;
+    let skipped = ((pc + 4) & ADDRESS_MASK) as i16;
+    dynasm!(ops
+        ; .arch x64
+        ; mov ax, WORD next
+        ; mov cx, WORD skipped
+    );
+    if when_equal {
+        dynasm!(ops ; .arch x64 ; cmove ax, cx);
+    } else {
+        dynasm!(ops ; .arch x64 ; cmovne ax, cx);
+    }
+    dynasm!(ops ; .arch x64 ; mov WORD [rdi + at.pc], ax);
+    Emitted::Branch
+}
+
+impl<R: RandomSource> Chip8<R> {
+    pub(super) fn run_jit(&mut self, count: u32) {
+        let Some(jit) = &mut self.jit else {
+            return;
+        };
+        if jit.quirks != self.quirks {
+            **jit = Jit::new(self.quirks);
+        }
+        let mut executor = jit.assembler.reader();
+        let mut code = executor.lock();
+        let mut remaining = count;
+        while remaining > 0 {
+            let pc = self.program_counter & ADDRESS_MASK;
+            let jit = self.jit.as_mut().expect("the JIT is running");
+            let mut slot = match pc {
+                0..=LAST_PC => jit.slots[pc as usize],
+                _ => Slot::Interpret,
+            };
+            if let Slot::Unknown = slot {
+                // Committing new code needs the buffer unlocked
+                drop(code);
+                if jit.assembler.offset().0 > CODE_LIMIT {
+                    **jit = Jit::new(self.quirks);
+                }
+                slot = jit.compile::<R>(&self.memory, pc);
+                jit.slots[pc as usize] = slot;
+                executor = jit.assembler.reader();
+                code = executor.lock();
+            }
+
+            match slot {
+                Slot::Native(block) if block.len as u32 <= remaining => {
+                    if !block.seen {
+                        self.first_run(pc, block.len);
+                    }
+                    // SAFETY: the block was compiled for a `Chip8<R>`, only
+                    // touches fields inside it and returns with every
+                    // callee-saved register as it found it
+                    let run: extern "sysv64" fn(*mut Chip8<R>) =
+                        unsafe { mem::transmute(code.ptr(block.code)) };
+                    run(self);
+                    remaining -= block.len as u32;
+                }
+                _ => {
+                    self.cycle();
+                    remaining -= 1;
+                }
+            }
+        }
+    }
+
+    ///Notes the fetches a block makes, the first time it runs
+    fn first_run(&mut self, pc: u16, len: u16) {
+        let mut seen = true;
+        for address in (pc..pc + 2 * len).step_by(2) {
+            self.code_map.fetch(address);
+            seen &= !self.code_map.written(address) && !self.code_map.written(address + 1);
+        }
+        if let Some(jit) = &mut self.jit {
+            if let Slot::Native(block) = &mut jit.slots[pc as usize] {
+                block.seen = seen;
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use super::{Chip8, STACK_SIZE};
+use crate::quirks::Quirks;
+use crate::rng::{Rng, RngMode};
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 2;
+
+///Length of every `save_state` blob, whatever the call depth
+pub const STATE_SIZE: usize = 4 + 1 // magic, version
+    + 4096 + 64 * 32 / 8 // memory, display
+    + 2 + 2 + 1 + STACK_SIZE * 2 // PC, I, stack
+    + 1 + 1 + 16 + 16 + 2 // timers, keypad, registers, opcode
+    + 1 + 8 + 8 // RNG
+    + 1 + 4; // quirks, tick rate
+
+///Why a save state was refused
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateError {
+    NotAState,
+    UnsupportedVersion(u8),
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(v) => write!(f, "unsupported save state version {v}"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "save state has an invalid {what}"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl Chip8 {
+    /// Snapshot of the whole machine, RNG and settings included, as a
+    /// little-endian byte blob that `load_state` accepts.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(STATE_SIZE);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.memory);
+        // The display only ever holds on or off, so one bit per pixel
+        for pixels in self.display.chunks(8) {
+            let byte = pixels
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, p)| byte | ((*p != 0) as u8) << i);
+            out.push(byte);
+        }
+        out.extend_from_slice(&self.program_counter.to_le_bytes());
+        out.extend_from_slice(&self.index_register.to_le_bytes());
+        out.push(self.stack_pointer);
+        // Every slot, not just the live ones, so the size never changes
+        for address in self.stack {
+            out.extend_from_slice(&address.to_le_bytes());
+        }
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        out.extend_from_slice(&self.keypad);
+        out.extend_from_slice(&self.variable_registers);
+        out.extend_from_slice(&self.opcode.to_le_bytes());
+        out.push(match self.rng.mode() {
+            RngMode::Xorshift => 0,
+            RngMode::Vip => 1,
+        });
+        out.extend_from_slice(&self.rng.seed().to_le_bytes());
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+        out.push(self.quirks.to_bits());
+        out.extend_from_slice(&self.tick_rate.to_le_bytes());
+        debug_assert_eq!(STATE_SIZE, out.len());
+        out
+    }
+
+    ///Restores a `save_state` snapshot. On error the machine is left untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = Reader { data };
+        if reader.take(4).map_err(|_| StateError::NotAState)? != MAGIC {
+            return Err(StateError::NotAState);
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let mut state = self.clone();
+        state.memory.copy_from_slice(reader.take(4096)?);
+        state.wrote(0, 4096);
+        let bits = reader.take(state.display.len() / 8)?;
+        for (i, pixel) in state.display.iter_mut().enumerate() {
+            *pixel = if bits[i / 8] & (1 << (i % 8)) != 0 {
+                0xFFFFFFFF
+            } else {
+                0x0
+            };
+        }
+        state.program_counter = reader.u16()?;
+        state.index_register = reader.u16()?;
+        let depth = reader.u8()?;
+        if depth as usize > STACK_SIZE {
+            return Err(StateError::Invalid("stack depth"));
+        }
+        state.stack_pointer = depth;
+        for slot in state.stack.iter_mut() {
+            *slot = reader.u16()?;
+        }
+        state.delay_timer = reader.u8()?;
+        state.sound_timer = reader.u8()?;
+        state.keypad.copy_from_slice(reader.take(16)?);
+        state.variable_registers.copy_from_slice(reader.take(16)?);
+        state.opcode = reader.u16()?;
+        let mode = match reader.u8()? {
+            0 => RngMode::Xorshift,
+            1 => RngMode::Vip,
+            _ => return Err(StateError::Invalid("random generator")),
+        };
+        let seed = reader.u64()?;
+        state.rng = Rng::from_parts(seed, mode, reader.u64()?);
+        state.quirks = Quirks::from_bits(reader.u8()?);
+        state.tick_rate = reader.u32()?;
+        if !reader.data.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+
+        state.display_changed = true;
+        *self = state;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! Self-modifying code detection. The interpreter marks every byte it fetches
+//! as an instruction and every byte `FX33`/`FX55` store, and counts stores
+//! over code that already ran and fetches from bytes the program wrote.
+//! Pokes from outside, like ROM loading or a debugger, aren't counted.
+
+use core::fmt;
+
+const WORDS: usize = 4096 / 64;
+
+///Something the program did to its own code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfModification {
+    /// The instruction at `pc` stored `len` bytes from `address`, some of
+    /// them already run as code
+    Overwrote { pc: u16, address: u16, len: u16 },
+    ///The instruction at `pc` was written by the program
+    RanWritten { pc: u16 },
+}
+
+impl fmt::Display for SelfModification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelfModification::Overwrote { pc, address, len } => write!(
+                f,
+                "{pc:#05x} wrote over code at {address:#05x}-{:#05x}",
+                address + len - 1
+            ),
+            SelfModification::RanWritten { pc } => write!(f, "ran written code at {pc:#05x}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeMap {
+    executed: [u64; WORDS],
+    written: [u64; WORDS],
+    overwrites: u32,
+    written_runs: u32,
+}
+
+impl Default for CodeMap {
+    fn default() -> Self {
+        CodeMap {
+            executed: [0; WORDS],
+            written: [0; WORDS],
+            overwrites: 0,
+            written_runs: 0,
+        }
+    }
+}
+
+impl CodeMap {
+    ///Whether the byte at `address` has been fetched as part of an instruction
+    pub fn executed(&self, address: u16) -> bool {
+        get(&self.executed, address)
+    }
+
+    ///Whether the program has stored to `address`
+    pub fn written(&self, address: u16) -> bool {
+        get(&self.written, address)
+    }
+
+    ///Stores that touched code which had already run
+    pub fn overwrites(&self) -> u32 {
+        self.overwrites
+    }
+
+    ///Instructions fetched from bytes the program wrote
+    pub fn written_runs(&self) -> u32 {
+        self.written_runs
+    }
+
+    pub fn self_modifying(&self) -> bool {
+        self.overwrites != 0 || self.written_runs != 0
+    }
+
+    ///What to print at the end of a run
+    pub fn summary(&self) -> Summary<'_> {
+        Summary(self)
+    }
+
+    ///Notes a fetch of the instruction at `pc`
+    pub(crate) fn fetch(&mut self, pc: u16) {
+        if get(&self.written, pc) || get(&self.written, pc + 1) {
+            self.written_runs += 1;
+        }
+        set(&mut self.executed, pc);
+        set(&mut self.executed, pc + 1);
+    }
+
+    ///Notes a store of `len` bytes from `address`
+    pub(crate) fn store(&mut self, address: u16, len: u16) {
+        let range = address..address + len;
+        if range.clone().any(|a| get(&self.executed, a)) {
+            self.overwrites += 1;
+        }
+        for a in range {
+            set(&mut self.written, a);
+        }
+    }
+}
+
+fn get(bits: &[u64; WORDS], address: u16) -> bool {
+    let address = address as usize % 4096;
+    bits[address / 64] & 1 << (address % 64) != 0
+}
+
+fn set(bits: &mut [u64; WORDS], address: u16) {
+    let address = address as usize % 4096;
+    bits[address / 64] |= 1 << (address % 64);
+}
+
+/// Counts, then the address ranges that were both run and written, such as
+/// `self-modifying code: 2 writes over code, 1 run of written code` followed
+/// by `  code and data at 0x2a0-0x2a3, 0x300`.
+pub struct Summary<'a>(&'a CodeMap);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let map = self.0;
+        let plural = |n: u32| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "self-modifying code: {} write{} over code, {} run{} of written code",
+            map.overwrites,
+            plural(map.overwrites),
+            map.written_runs,
+            plural(map.written_runs)
+        )?;
+
+        let both = |a: u16| map.executed(a) && map.written(a);
+        let mut address = 0u16;
+        let mut first = true;
+        while address < 4096 {
+            if !both(address) {
+                address += 1;
+                continue;
+            }
+            let start = address;
+            while address + 1 < 4096 && both(address + 1) {
+                address += 1;
+            }
+            f.write_str(if first { "\n  code and data at " } else { ", " })?;
+            first = false;
+            if start == address {
+                write!(f, "{start:#05x}")?;
+            } else {
+                write!(f, "{start:#05x}-{address:#05x}")?;
+            }
+            address += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Instruction-level execution with breakpoints and watchpoints, shared by
+//! the debugger frontends. Frames keep their usual shape: the keypad is
+//! latched before the first instruction and the timers tick after the last,
+//! even when a frame is spread over several stops.
+
+use crate::chip::Chip8;
+use crate::code_map::SelfModification;
+use std::collections::BTreeMap;
+
+mod access;
+mod expr;
+
+pub use access::{access, Access};
+pub use expr::{Expr, Template};
+
+///Why execution stopped before the end of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    ///About to execute the instruction at this address
+    Breakpoint(u16),
+    ///The instruction at `pc` touched a watched location, and has run
+    Watchpoint {
+        pc: u16,
+        watched: Watched,
+        kind: WatchKind,
+    },
+    /// Self-modifying code, if asked to stop on it: before running written
+    /// code, after writing over code
+    SelfModifying(SelfModification),
+}
+
+/// A PC breakpoint. Each time the PC reaches it with the condition true it
+/// counts a hit; tracepoints then log a line, others stop once the ignore
+/// count is used up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub condition: Option<Expr>,
+    ///Hits to let through before stopping
+    pub ignore: u32,
+    pub hits: u32,
+    ///Log this instead of stopping
+    pub trace: Option<Template>,
+}
+
+impl Breakpoint {
+    ///No condition, ignore count or trace, as gdb's own breakpoints are
+    pub fn is_plain(&self) -> bool {
+        self.condition.is_none() && self.ignore == 0 && self.trace.is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watched {
+    Memory {
+        address: u16,
+        len: u16,
+    },
+    ///A V register
+    Register(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ///Either
+    Access,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub watched: Watched,
+    pub kind: WatchKind,
+    pub hits: u32,
+}
+
+impl Watchpoint {
+    pub fn new(watched: Watched, kind: WatchKind) -> Self {
+        Watchpoint {
+            watched,
+            kind,
+            hits: 0,
+        }
+    }
+
+    fn triggered_by(&self, access: &Access) -> bool {
+        let (read, written) = match self.watched {
+            Watched::Memory { address, len } => (
+                access.reads_memory(address, len),
+                access.writes_memory(address, len),
+            ),
+            Watched::Register(x) => (access.reads_register(x), access.writes_register(x)),
+        };
+        match self.kind {
+            WatchKind::Read => read,
+            WatchKind::Write => written,
+            WatchKind::Access => read || written,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    ///Tracepoint output not yet collected by the frontend
+    log: Vec<String>,
+    stop_on_self_modifying: bool,
+    ///Instructions already executed in the current frame
+    cycle: u32,
+    ///Let the instruction under the PC run even if it has a breakpoint
+    resuming: bool,
+}
+
+impl Debugger {
+    ///Returns false if there already was one at `address`
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        if self.breakpoints.contains_key(&address) {
+            return false;
+        }
+        self.breakpoints.insert(address, Breakpoint::default());
+        true
+    }
+
+    ///Adds or replaces the breakpoint at `address`
+    pub fn set_breakpoint(&mut self, address: u16, breakpoint: Breakpoint) {
+        self.breakpoints.insert(address, breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoint(&self, address: u16) -> Option<&Breakpoint> {
+        self.breakpoints.get(&address)
+    }
+
+    pub fn breakpoint_mut(&mut self, address: u16) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u16, &Breakpoint)> + '_ {
+        self.breakpoints.iter().map(|(address, b)| (*address, b))
+    }
+
+    pub fn add_watchpoint(&mut self, watched: Watched, kind: WatchKind) {
+        self.watchpoints.push(Watchpoint::new(watched, kind));
+    }
+
+    ///Removes one matching watchpoint, returning false if there was none
+    pub fn remove_watchpoint(&mut self, watched: Watched, kind: WatchKind) -> bool {
+        let found = self
+            .watchpoints
+            .iter()
+            .position(|w| w.watched == watched && w.kind == kind);
+        found.map(|n| self.watchpoints.remove(n)).is_some()
+    }
+
+    /// Removes every watchpoint on `watched` whatever its kind, memory ones
+    /// by start address. Returns false if there were none.
+    pub fn clear_watchpoints(&mut self, watched: Watched) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|w| match (w.watched, watched) {
+            (Watched::Memory { address: a, .. }, Watched::Memory { address: b, .. }) => a != b,
+            (a, b) => a != b,
+        });
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn stop_on_self_modifying(&self) -> bool {
+        self.stop_on_self_modifying
+    }
+
+    pub fn set_stop_on_self_modifying(&mut self, stop: bool) {
+        self.stop_on_self_modifying = stop;
+    }
+
+    ///Takes the lines logged by tracepoints since the last call
+    pub fn take_log(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.log)
+    }
+
+    ///Whether execution is part way through a frame
+    pub fn mid_frame(&self) -> bool {
+        self.cycle != 0
+    }
+
+    /// Continue past a breakpoint at the current PC. Call before resuming
+    /// execution after a stop.
+    pub fn resume(&mut self) {
+        self.resuming = true;
+    }
+
+    /// Executes exactly one instruction. `keys` is only used if this starts
+    /// a new frame. Returns the first watchpoint the instruction triggered.
+    pub fn step(&mut self, chip: &mut Chip8, keys: [u8; 16]) -> Option<Stop> {
+        let pc = chip.program_counter();
+        let access = access(chip);
+        let overwrite = access.memory_written.filter(|&(address, len)| {
+            (0..len).any(|n| chip.code_map().executed(address.wrapping_add(n)))
+        });
+        self.resuming = false;
+        if self.cycle == 0 {
+            chip.get_input(keys);
+        }
+        chip.cycle();
+        self.cycle += 1;
+        if self.cycle >= chip.tick_rate() {
+            chip.tick_timers();
+            self.cycle = 0;
+        }
+
+        let mut stop = None;
+        for watchpoint in &mut self.watchpoints {
+            if watchpoint.triggered_by(&access) {
+                watchpoint.hits += 1;
+                stop = stop.or(Some(Stop::Watchpoint {
+                    pc,
+                    watched: watchpoint.watched,
+                    kind: watchpoint.kind,
+                }));
+            }
+        }
+        if let Some((address, len)) = overwrite.filter(|_| self.stop_on_self_modifying) {
+            let overwrote = SelfModification::Overwrote { pc, address, len };
+            stop = stop.or(Some(Stop::SelfModifying(overwrote)));
+        }
+        stop
+    }
+
+    /// Runs to the end of the current frame, or until the PC lands on a
+    /// breakpoint, in which case that instruction has not run yet, or an
+    /// instruction triggers a watchpoint or modifies code.
+    pub fn run_frame(&mut self, chip: &mut Chip8, keys: [u8; 16]) -> Option<Stop> {
+        loop {
+            let pc = chip.program_counter();
+            if !self.resuming && self.breakpoint_hit(chip) {
+                return Some(Stop::Breakpoint(pc));
+            }
+            let written = |a| chip.code_map().written(a);
+            if self.stop_on_self_modifying
+                && !self.resuming
+                && (written(pc) || written(pc.wrapping_add(1)))
+            {
+                let ran = SelfModification::RanWritten { pc };
+                return Some(Stop::SelfModifying(ran));
+            }
+            let last = self.cycle + 1 >= chip.tick_rate();
+            if let Some(stop) = self.step(chip, keys) {
+                return Some(stop);
+            }
+            if last {
+                return None;
+            }
+        }
+    }
+
+    ///Counts a hit on the breakpoint under the PC, logging tracepoints
+    fn breakpoint_hit(&mut self, chip: &Chip8) -> bool {
+        let Some(breakpoint) = self.breakpoints.get_mut(&chip.program_counter()) else {
+            return false;
+        };
+        if !breakpoint.condition.as_ref().is_none_or(|c| c.holds(chip)) {
+            return false;
+        }
+        breakpoint.hits += 1;
+        if let Some(trace) = &breakpoint.trace {
+            self.log.push(trace.render(chip));
+            return false;
+        }
+        breakpoint.hits > breakpoint.ignore
+    }
+}
@@ -0,0 +1,92 @@
+use crate::chip::Chip8;
+use crate::rng::RandomSource;
+
+/// The memory and V registers the instruction under the PC touches, worked
+/// out before it runs. Instruction fetches don't count as reads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    ///Start address and length of the memory read
+    pub memory_read: Option<(u16, u16)>,
+    pub memory_written: Option<(u16, u16)>,
+    ///Bit n is set if Vn is read
+    pub registers_read: u16,
+    ///Bit n is set if Vn is written, VF included when used as a flag
+    pub registers_written: u16,
+}
+
+impl Access {
+    pub fn reads_memory(&self, start: u16, len: u16) -> bool {
+        overlaps(self.memory_read, start, len)
+    }
+
+    pub fn writes_memory(&self, start: u16, len: u16) -> bool {
+        overlaps(self.memory_written, start, len)
+    }
+
+    pub fn reads_register(&self, x: usize) -> bool {
+        self.registers_read & 1 << x != 0
+    }
+
+    pub fn writes_register(&self, x: usize) -> bool {
+        self.registers_written & 1 << x != 0
+    }
+}
+
+fn overlaps(range: Option<(u16, u16)>, start: u16, len: u16) -> bool {
+    range.is_some_and(|(from, n)| {
+        let (from, to) = (from as u32, from as u32 + n as u32);
+        let (start, end) = (start as u32, start as u32 + len as u32);
+        from < end && start < to
+    })
+}
+
+///What the instruction at the PC will touch, given the current quirks
+pub fn access<R: RandomSource>(chip: &Chip8<R>) -> Access {
+    let pc = chip.program_counter() as usize % 4096;
+    let memory = chip.memory();
+    let opcode = u16::from_be_bytes([memory[pc], memory[(pc + 1) % 4096]]);
+    let x = (opcode >> 8 & 0xF) as usize;
+    let y = (opcode >> 4 & 0xF) as usize;
+    let n = opcode & 0xF;
+    let vx = 1 << x;
+    let vy = 1 << y;
+    let vf = 1 << 0xF;
+    // V0 to VX inclusive
+    let up_to_x = (1u32 << (x + 1)) as u16 - 1;
+    let i = chip.index_register();
+    let quirks = chip.quirks();
+
+    let (read, written) = match (opcode >> 12, n, opcode & 0xFF) {
+        (0x3 | 0x4, _, _) | (0xE, _, 0x9E | 0xA1) => (vx, 0),
+        (0x5 | 0x9, 0, _) => (vx | vy, 0),
+        (0x6 | 0xC, _, _) => (0, vx),
+        (0x7, _, _) => (vx, vx),
+        (0x8, 0x0, _) => (vy, vx),
+        (0x8, 0x1..=0x3, _) if quirks.vf_reset => (vx | vy, vx | vf),
+        (0x8, 0x1..=0x3, _) => (vx | vy, vx),
+        (0x8, 0x4 | 0x5 | 0x7, _) => (vx | vy, vx | vf),
+        (0x8, 0x6 | 0xE, _) if quirks.shift_uses_vy => (vy, vx | vf),
+        (0x8, 0x6 | 0xE, _) => (vx, vx | vf),
+        (0xB, _, _) if quirks.jump_uses_vx => (vx, 0),
+        (0xB, _, _) => (1, 0),
+        (0xD, _, _) => (vx | vy, vf),
+        (0xF, _, 0x07 | 0x0A) => (0, vx),
+        (0xF, _, 0x15 | 0x18 | 0x1E | 0x29 | 0x33) => (vx, 0),
+        (0xF, _, 0x55) => (up_to_x, 0),
+        (0xF, _, 0x65) => (0, up_to_x),
+        _ => (0, 0),
+    };
+    let (memory_read, memory_written) = match (opcode >> 12, opcode & 0xFF) {
+        (0xD, _) if n > 0 => (Some((i, n)), None),
+        (0xF, 0x33) => (None, Some((i, 3))),
+        (0xF, 0x55) => (None, Some((i, x as u16 + 1))),
+        (0xF, 0x65) => (Some((i, x as u16 + 1)), None),
+        _ => (None, None),
+    };
+    Access {
+        memory_read,
+        memory_written,
+        registers_read: read,
+        registers_written: written,
+    }
+}
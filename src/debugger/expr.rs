@@ -0,0 +1,373 @@
+//! Expressions for conditional breakpoints and tracepoints, such as
+//! `V3 == 0x10 && I > 0x300` or `[I + 2]`. Values are unsigned; comparisons
+//! and `&&`, `||` and `!` give 1 or 0, and anything non-zero is true.
+
+use crate::chip::Chip8;
+use crate::rng::RandomSource;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Number(u32),
+    Register(Register),
+    ///A byte of memory, `[address]`
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Name(String),
+    Op(Op),
+    Not,
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+}
+
+///A parsed expression, displayed as it was written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn eval<R: RandomSource>(&self, chip: &Chip8<R>) -> u32 {
+        eval(&self.root, chip)
+    }
+
+    pub fn holds<R: RandomSource>(&self, chip: &Chip8<R>) -> bool {
+        self.eval(chip) != 0
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let root = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.next) {
+            return Err(format!("unexpected {token:?} in `{s}`"));
+        }
+        Ok(Expr {
+            source: s.trim().to_string(),
+            root,
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Tracepoint text with `{expr}` placeholders, printed in decimal or, as
+/// `{expr:x}`, in hex. `{{` and `}}` are literal braces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Value { expr: Expr, hex: bool },
+}
+
+impl Template {
+    pub fn render<R: RandomSource>(&self, chip: &Chip8<R>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Value { expr, hex: true } => out.push_str(&format!("{:#x}", expr.eval(chip))),
+                Part::Value { expr, hex: false } => out.push_str(&expr.eval(chip).to_string()),
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => return Err(format!("unclosed `{{` in `{s}`")),
+                        }
+                    }
+                    let (inner, hex) = match inner.strip_suffix(":x") {
+                        Some(inner) => (inner, true),
+                        None => (inner.as_str(), false),
+                    };
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Value {
+                        expr: inner.parse()?,
+                        hex,
+                    });
+                }
+                '}' => return Err(format!("unmatched `}}` in `{s}`")),
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template {
+            source: s.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn eval<R: RandomSource>(node: &Node, chip: &Chip8<R>) -> u32 {
+    match node {
+        Node::Number(n) => *n,
+        Node::Register(register) => match register {
+            Register::V(x) => chip.registers()[*x] as u32,
+            Register::I => chip.index_register() as u32,
+            Register::Pc => chip.program_counter() as u32,
+            Register::Sp => chip.stack().len() as u32,
+            Register::Dt => chip.delay_timer() as u32,
+            Register::St => chip.sound_timer() as u32,
+        },
+        Node::Memory(address) => {
+            let address = eval(address, chip) as usize;
+            chip.memory().get(address).copied().unwrap_or(0) as u32
+        }
+        Node::Not(inner) => (eval(inner, chip) == 0) as u32,
+        Node::Binary(op, left, right) => {
+            let left = eval(left, chip);
+            // Short-circuit so `[..]` past the end isn't even looked at
+            match op {
+                Op::And if left == 0 => return 0,
+                Op::Or if left != 0 => return 1,
+                _ => {}
+            }
+            let right = eval(right, chip);
+            match op {
+                Op::Or | Op::And => (right != 0) as u32,
+                Op::Eq => (left == right) as u32,
+                Op::Ne => (left != right) as u32,
+                Op::Lt => (left < right) as u32,
+                Op::Le => (left <= right) as u32,
+                Op::Gt => (left > right) as u32,
+                Op::Ge => (left >= right) as u32,
+                Op::Add => left.wrapping_add(right),
+                Op::Sub => left.wrapping_sub(right),
+            }
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => continue,
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('[', _) => Token::OpenBracket,
+            (']', _) => Token::CloseBracket,
+            ('+', _) => Token::Op(Op::Add),
+            ('-', _) => Token::Op(Op::Sub),
+            ('&', Some('&')) => Token::Op(Op::And),
+            ('|', Some('|')) => Token::Op(Op::Or),
+            ('=', Some('=')) => Token::Op(Op::Eq),
+            ('!', Some('=')) => Token::Op(Op::Ne),
+            ('<', Some('=')) => Token::Op(Op::Le),
+            ('>', Some('=')) => Token::Op(Op::Ge),
+            ('!', _) => Token::Not,
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            (c, _) if c.is_ascii_alphanumeric() => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                    end = i + c.len_utf8();
+                }
+                let word = &s[start..end];
+                tokens.push(if c.is_ascii_digit() {
+                    Token::Number(parse_number(word)?)
+                } else {
+                    Token::Name(word.to_ascii_lowercase())
+                });
+                continue;
+            }
+            _ => return Err(format!("unexpected `{c}` in `{s}`")),
+        };
+        // The second character of a two character operator
+        if matches!(
+            token,
+            Token::Op(Op::And | Op::Or | Op::Eq | Op::Ne | Op::Le | Op::Ge)
+        ) {
+            chars.next();
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<u32, String> {
+    let parsed = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => word.parse(),
+    };
+    parsed.map_err(|_| format!("bad number `{word}`"))
+}
+
+fn register(name: &str) -> Option<Register> {
+    Some(match name {
+        "i" => Register::I,
+        "pc" => Register::Pc,
+        "sp" => Register::Sp,
+        "dt" => Register::Dt,
+        "st" => Register::St,
+        _ => {
+            let x = name.strip_prefix('v')?;
+            if x.len() != 1 {
+                return None;
+            }
+            Register::V(usize::from_str_radix(x, 16).ok()?)
+        }
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn take(&mut self) -> Result<Token, String> {
+        let token = self.peek().cloned().ok_or("expression ends too soon")?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.take()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected {expected:?}, found {token:?}")),
+        }
+    }
+
+    /// Parses a left-associative chain of `ops` over `operand`
+    fn chain(
+        &mut self,
+        ops: &[Op],
+        operand: fn(&mut Self) -> Result<Node, String>,
+    ) -> Result<Node, String> {
+        let mut left = operand(self)?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.next += 1;
+            left = Node::Binary(op, Box::new(left), Box::new(operand(self)?));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        self.chain(&[Op::Or], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        self.chain(&[Op::And], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        let ops = [Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge];
+        self.chain(&ops, Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        self.chain(&[Op::Add, Op::Sub], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.take()? {
+            Token::Number(n) => Ok(Node::Number(n)),
+            Token::Name(name) => register(&name)
+                .map(Node::Register)
+                .ok_or(format!("unknown register `{name}`")),
+            Token::Not => Ok(Node::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let inner = self.or()?;
+                self.expect(Token::Close)?;
+                Ok(inner)
+            }
+            Token::OpenBracket => {
+                let address = self.or()?;
+                self.expect(Token::CloseBracket)?;
+                Ok(Node::Memory(Box::new(address)))
+            }
+            token => Err(format!("unexpected {token:?}")),
+        }
+    }
+}
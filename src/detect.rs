@@ -0,0 +1,297 @@
+//! Guesses what a ROM was written for when no database knows it. The code
+//! reachable from 0x200 is followed statically, so sprites and other data
+//! aren't mistaken for instructions, and checked for opcodes only SUPER-CHIP
+//! or XO-CHIP have and for habits that give away the quirks it expects.
+
+use crate::chip::DEFAULT_TICK_RATE;
+use crate::quirks::Quirks;
+use std::fmt;
+
+///Below this many percent sure, the guess keeps the interpreter's defaults
+pub const THRESHOLD: u8 = 50;
+
+///Instructions scanned that count as much as one telltale opcode
+const INSTRUCTIONS_PER_CLUE: usize = 32;
+
+///Clues listed by `Display` before the rest are summed up
+const CLUES_SHOWN: usize = 8;
+
+///Platforms a ROM can be detected as, each a superset of the one before
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    ///Its id in the ROM database's `platforms.json`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "originalChip8",
+            Platform::SuperChip => "superchip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::chip8(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks {
+                clip_sprites: false,
+                vf_reset: false,
+                ..Quirks::chip8()
+            },
+        }
+    }
+
+    ///The ROM database's default for the platform
+    pub fn tick_rate(&self) -> u32 {
+        match self {
+            Platform::Chip8 => 15,
+            Platform::SuperChip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hint {
+    ///An opcode only this platform has
+    Needs(Platform),
+    ///`8XY6`/`8XYE` with X and Y different, which only matters if VY is
+    ///the one shifted
+    ShiftsVy,
+    ///I used again straight after `FX55`/`FX65` without being set, as if it
+    ///had moved on past the registers
+    ReusesI,
+}
+
+///Something in the ROM that points one way or another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clue {
+    pub address: u16,
+    pub opcode: u16,
+    pub hint: Hint,
+}
+
+impl fmt::Display for Clue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05x} {:04X}: ", self.address, self.opcode)?;
+        match self.hint {
+            Hint::Needs(platform) => write!(f, "{} only", platform.name()),
+            Hint::ShiftsVy => write!(f, "shifts VY into VX"),
+            Hint::ReusesI => write!(f, "uses I again after a register store or load"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detection {
+    pub platform: Platform,
+    ///How sure the guess is, in percent
+    pub confidence: u8,
+    ///The platform's quirks when at least [`THRESHOLD`] sure, the defaults
+    ///otherwise
+    pub quirks: Quirks,
+    pub tick_rate: u32,
+    ///Instructions reachable from 0x200
+    pub instructions: usize,
+    ///In address order
+    pub clues: Vec<Clue>,
+}
+
+/// One line for the guess, one for the profile it picks, then the clues:
+///
+/// ```text
+/// Detected: SUPER-CHIP, 67% sure from 2 clues in 120 instructions
+/// Profile: shift_uses_vy=0 ... at 30 instructions a frame
+///   0x2a4 00FF: SUPER-CHIP only
+/// ```
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Detected: {}, {}% sure from {} clues in {} instructions",
+            self.platform.name(),
+            self.confidence,
+            self.clues.len(),
+            self.instructions
+        )?;
+        write!(
+            f,
+            "Profile: {} at {} instructions a frame",
+            self.quirks, self.tick_rate
+        )?;
+        for clue in self.clues.iter().take(CLUES_SHOWN) {
+            write!(f, "\n  {clue}")?;
+        }
+        if self.clues.len() > CLUES_SHOWN {
+            write!(f, "\n  and {} more", self.clues.len() - CLUES_SHOWN)?;
+        }
+        Ok(())
+    }
+}
+
+///The opcode at `pc` in a ROM loaded at 0x200
+fn fetch(rom: &[u8], pc: u16) -> Option<u16> {
+    let offset = (pc as usize).checked_sub(0x200)?;
+    let bytes = rom.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+///How many bytes the instruction at `pc` takes, counting XO-CHIP's `F000 NNNN`
+fn length(rom: &[u8], pc: u16) -> u16 {
+    if fetch(rom, pc) == Some(0xF000) {
+        4
+    } else {
+        2
+    }
+}
+
+fn is_skip(opcode: u16) -> bool {
+    matches!(
+        (opcode >> 12, opcode & 0xF, opcode & 0xFF),
+        (0x3 | 0x4, _, _) | (0x5 | 0x9, 0x0, _) | (0xE, _, 0x9E | 0xA1)
+    )
+}
+
+///Addresses of the instructions that can run, starting from 0x200
+fn reachable(rom: &[u8]) -> Vec<u16> {
+    let mut seen = vec![false; 4096];
+    let mut pending = vec![0x200u16];
+    while let Some(pc) = pending.pop() {
+        if pc >= 0x1000 || seen[pc as usize] {
+            continue;
+        }
+        let Some(opcode) = fetch(rom, pc) else {
+            continue;
+        };
+        seen[pc as usize] = true;
+        let next = pc + length(rom, pc);
+        match opcode >> 12 {
+            // Returns and SUPER-CHIP's exit go nowhere we can follow
+            0x0 if opcode == 0x00EE || opcode == 0x00FD => {}
+            0x1 => pending.push(opcode & 0xFFF),
+            0x2 => pending.extend([opcode & 0xFFF, next]),
+            // Computed jumps too
+            0xB => {}
+            _ if is_skip(opcode) => pending.extend([next, next + length(rom, next)]),
+            _ => pending.push(next),
+        }
+    }
+    (0..0x1000).filter(|pc| seen[*pc as usize]).collect()
+}
+
+///The platform `opcode` needs, if it's an extension
+fn needs(opcode: u16) -> Option<Platform> {
+    let (x, n, nn) = (opcode >> 8 & 0xF, opcode & 0xF, opcode & 0xFF);
+    match (opcode >> 12, x, nn) {
+        // Scroll down, scroll left and right, exit, low and high resolution
+        (0x0, 0x0, 0xC0..=0xCF | 0xFB..=0xFF) if opcode != 0x00C0 => Some(Platform::SuperChip),
+        // 16x16 sprites
+        (0xD, _, _) if n == 0 => Some(Platform::SuperChip),
+        // Big font, flag registers
+        (0xF, _, 0x30 | 0x75 | 0x85) => Some(Platform::SuperChip),
+        // Scroll up
+        (0x0, 0x0, 0xD1..=0xDF) => Some(Platform::XoChip),
+        // Register range stores and loads
+        (0x5, _, _) if n == 0x2 || n == 0x3 => Some(Platform::XoChip),
+        // Long I, plane select, audio pattern, pitch
+        (0xF, 0x0, 0x00 | 0x02) => Some(Platform::XoChip),
+        (0xF, _, 0x01 | 0x3A) => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+/// Whether I is read again, by the straight-line code after the store or
+/// load at `pc`, before anything sets it.
+fn reuses_i(rom: &[u8], pc: u16) -> bool {
+    let mut at = pc + 2;
+    while let Some(opcode) = fetch(rom, at) {
+        match (opcode >> 12, opcode & 0xFF) {
+            (0xD, _) | (0xF, 0x33 | 0x55 | 0x65) => return true,
+            // Sets I, or goes somewhere else
+            (0xA | 0x0 | 0x1 | 0x2 | 0xB, _) | (0xF, 0x1E | 0x29 | 0x30) => return false,
+            _ if is_skip(opcode) || opcode == 0xF000 => return false,
+            _ => at += 2,
+        }
+    }
+    false
+}
+
+///Percent sure after this much evidence: 1 is 50, 3 is 75, 9 is 90
+fn sureness(evidence: usize) -> u8 {
+    (100 - 100 / (evidence + 1)) as u8
+}
+
+///Guesses the platform `rom` was written for, and a profile to run it with
+pub fn detect(rom: &[u8]) -> Detection {
+    let code = reachable(rom);
+    let mut clues = Vec::new();
+    for &pc in &code {
+        let Some(opcode) = fetch(rom, pc) else {
+            continue;
+        };
+        let (x, y) = (opcode >> 8 & 0xF, opcode >> 4 & 0xF);
+        let hint = needs(opcode).map(Hint::Needs).or_else(|| {
+            match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+                (0x8, 0x6 | 0xE, _) if x != y => Some(Hint::ShiftsVy),
+                (0xF, _, 0x55 | 0x65) if reuses_i(rom, pc) => Some(Hint::ReusesI),
+                _ => None,
+            }
+        });
+        if let Some(hint) = hint {
+            clues.push(Clue {
+                address: pc,
+                opcode,
+                hint,
+            });
+        }
+    }
+
+    let platform = clues
+        .iter()
+        .filter_map(|clue| match clue.hint {
+            Hint::Needs(platform) => Some(platform),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(Platform::Chip8);
+    // Extension opcodes count for their platform and everything above it.
+    // Plain CHIP-8 is the answer when there are none, and more certain the
+    // more code there was to find them in and the more of the VIP's habits
+    // it shows.
+    let evidence = match platform {
+        Platform::Chip8 => code.len() / INSTRUCTIONS_PER_CLUE + clues.len(),
+        _ => clues
+            .iter()
+            .filter(|clue| matches!(clue.hint, Hint::Needs(_)))
+            .count(),
+    };
+    let confidence = sureness(evidence);
+
+    let (quirks, tick_rate) = if confidence >= THRESHOLD {
+        (platform.quirks(), platform.tick_rate())
+    } else {
+        (Quirks::default(), DEFAULT_TICK_RATE)
+    };
+
+    Detection {
+        platform,
+        confidence,
+        quirks,
+        tick_rate,
+        instructions: code.len(),
+        clues,
+    }
+}
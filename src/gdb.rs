@@ -0,0 +1,638 @@
+//! GDB remote serial protocol stub, so gdb, lldb and the UIs built on them
+//! can debug ROMs.
+//!
+//! The target has 21 registers: V0-VF (8 bit), I and PC (16 bit), then SP,
+//! DT and ST (8 bit), all little-endian on the wire. SP is the call depth
+//! and read-only. Memory is the 4 KiB address space. The ROM runs freely
+//! until a debugger attaches, which halts it.
+//!
+//! Memory watchpoints come through the usual packets. Conditions, ignore
+//! counts, tracepoints and V register watchpoints have no packets, so they
+//! are `monitor` commands; tracepoint lines arrive as console output.
+
+use crate::chip::Chip8;
+use crate::debugger::{Breakpoint, Debugger, Stop, WatchKind, Watched};
+use crate::hex;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+pub const REGISTER_COUNT: usize = 21;
+
+///Served through `qXfer:features:read` so debuggers know register names
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+///SIGTRAP, reported for breakpoints and steps
+const SIGTRAP: u8 = 5;
+///SIGINT, reported when the debugger interrupts a running target
+const SIGINT: u8 = 2;
+///Largest packet body the client is told it may send
+const PACKET_SIZE: usize = 0x1000;
+///Unparsed input kept before it's thrown away as garbage
+const MAX_PENDING: usize = 2 * PACKET_SIZE;
+
+struct Connection {
+    stream: TcpStream,
+    pending: Vec<u8>,
+    no_ack: bool,
+}
+
+impl Connection {
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        let packet = format!("${data}#{checksum:02x}");
+        self.stream.set_nonblocking(false)?;
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.set_nonblocking(true)
+    }
+}
+
+/// Non-blocking stub polled by the frontend loop. While a debugger has the
+/// target halted `running` is false and the frontend should not emulate;
+/// otherwise frames go through `run_frame` so breakpoints are honoured.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<Connection>,
+    debugger: Debugger,
+    running: bool,
+    killed: bool,
+}
+
+impl GdbStub {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbStub {
+            listener,
+            client: None,
+            debugger: Debugger::default(),
+            running: true,
+            killed: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    ///The debugger sent a kill request, the frontend should exit
+    pub fn killed(&self) -> bool {
+        self.killed
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    /// Runs the rest of a frame. If it stops on a breakpoint the target
+    /// halts and the debugger is told.
+    pub fn run_frame(&mut self, chip: &mut Chip8, keys: [u8; 16]) -> io::Result<()> {
+        if !self.running {
+            return Ok(());
+        }
+        let stop = self.debugger.run_frame(chip, keys);
+        for line in self.debugger.take_log() {
+            self.send(&format!("O{}", hex::encode(format!("{line}\n").as_bytes())))?;
+        }
+        if let Some(stop) = stop {
+            self.running = false;
+            if let Stop::SelfModifying(what) = stop {
+                self.send(&format!("O{}", hex::encode(format!("{what}\n").as_bytes())))?;
+            }
+            self.send(&stop_reply(stop))?;
+        }
+        Ok(())
+    }
+
+    ///Accepts a debugger and answers whatever it has sent. Never blocks.
+    pub fn poll(&mut self, chip: &mut Chip8) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(Connection {
+                        stream,
+                        pending: Vec::new(),
+                        no_ack: false,
+                    });
+                    self.running = false;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let Some(client) = &mut self.client else {
+            return Ok(());
+        };
+        let mut buffer = [0u8; 4096];
+        let open = loop {
+            match client.stream.read(&mut buffer) {
+                Ok(0) => break false,
+                Ok(n) => {
+                    client.pending.extend_from_slice(&buffer[..n]);
+                    // Leave the rest in the socket until this much is handled
+                    if client.pending.len() > MAX_PENDING {
+                        break true;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break false,
+            }
+        };
+
+        while let Some(packet) = self.next_packet()? {
+            self.handle(&packet, chip)?;
+            if self.client.is_none() {
+                break;
+            }
+        }
+        if !open {
+            self.detach();
+        }
+        Ok(())
+    }
+
+    ///Pops the next complete packet, acking it and handling interrupts
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let Some(client) = &mut self.client else {
+                return Ok(None);
+            };
+            let Some(&first) = client.pending.first() else {
+                return Ok(None);
+            };
+            match first {
+                0x03 => {
+                    client.pending.remove(0);
+                    if self.running {
+                        self.halt(SIGINT)?;
+                    }
+                }
+                b'$' => {
+                    let Some(hash) = client.pending.iter().position(|b| *b == b'#') else {
+                        // No client sends a packet this long
+                        if client.pending.len() > MAX_PENDING {
+                            client.pending.clear();
+                            if !client.no_ack {
+                                client.stream.write_all(b"-")?;
+                            }
+                        }
+                        return Ok(None);
+                    };
+                    if client.pending.len() < hash + 3 {
+                        return Ok(None);
+                    }
+                    let frame: Vec<u8> = client.pending.drain(..hash + 3).collect();
+                    let body = &frame[1..hash];
+                    let expected = std::str::from_utf8(&frame[hash + 1..])
+                        .ok()
+                        .and_then(|cs| u8::from_str_radix(cs, 16).ok());
+                    let checksum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+                    if expected != Some(checksum) {
+                        if !client.no_ack {
+                            client.stream.write_all(b"-")?;
+                        }
+                        continue;
+                    }
+                    if !client.no_ack {
+                        client.stream.write_all(b"+")?;
+                    }
+                    return Ok(Some(String::from_utf8_lossy(body).into_owned()));
+                }
+                // Acks and line noise
+                _ => {
+                    client.pending.remove(0);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        match &mut self.client {
+            Some(client) => client.send(data),
+            None => Ok(()),
+        }
+    }
+
+    fn halt(&mut self, signal: u8) -> io::Result<()> {
+        self.running = false;
+        self.send(&format!("S{signal:02x}"))
+    }
+
+    ///Drops the debugger and lets the ROM run on
+    fn detach(&mut self) {
+        self.client = None;
+        self.running = true;
+        self.debugger.resume();
+    }
+
+    fn handle(&mut self, packet: &str, chip: &mut Chip8) -> io::Result<()> {
+        if packet == "QStartNoAckMode" {
+            // The OK still gets acked, the mode starts after it
+            self.send("OK")?;
+            if let Some(client) = &mut self.client {
+                client.no_ack = true;
+            }
+            return Ok(());
+        }
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => format!("S{SIGTRAP:02x}"),
+            "g" => (0..REGISTER_COUNT)
+                .map(|n| read_register(chip, n))
+                .collect(),
+            "G" => {
+                let mut rest = args;
+                for n in 0..REGISTER_COUNT {
+                    let width = register_width(n) * 2;
+                    let Some(value) = rest.get(..width) else {
+                        break;
+                    };
+                    rest = &rest[width..];
+                    write_register(chip, n, value);
+                }
+                "OK".into()
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < REGISTER_COUNT => read_register(chip, n),
+                _ => "E01".into(),
+            },
+            "P" => match args.split_once('=').and_then(|(n, value)| {
+                let n = usize::from_str_radix(n, 16).ok()?;
+                (n < REGISTER_COUNT && write_register(chip, n, value)).then_some(())
+            }) {
+                Some(()) => "OK".into(),
+                None => "E01".into(),
+            },
+            "m" => match parse_range(args) {
+                Some((address, length)) if address < chip.memory().len() => {
+                    let end = address.saturating_add(length).min(chip.memory().len());
+                    hex::encode(&chip.memory()[address..end])
+                }
+                _ => "E01".into(),
+            },
+            "M" => {
+                let written = args.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = parse_range(range)?;
+                    let bytes = hex::decode(data)?;
+                    let end = address.checked_add(length)?;
+                    let fits = bytes.len() == length && end <= chip.memory().len();
+                    fits.then(|| chip.write_memory(address as u16, &bytes))
+                });
+                if written.is_some() { "OK" } else { "E01" }.into()
+            }
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                let mut number = || fields.next().and_then(|a| u16::from_str_radix(a, 16).ok());
+                let (address, len) = (number(), number());
+                let watch = match kind {
+                    Some("2") => Some(WatchKind::Write),
+                    Some("3") => Some(WatchKind::Read),
+                    Some("4") => Some(WatchKind::Access),
+                    _ => None,
+                };
+                match (kind, watch, address) {
+                    // Software and hardware breakpoints are the same thing here
+                    (Some("0" | "1"), _, Some(address)) => {
+                        if command == "Z" {
+                            self.debugger.add_breakpoint(address);
+                        } else if self
+                            .debugger
+                            .breakpoint(address)
+                            .is_some_and(Breakpoint::is_plain)
+                        {
+                            // gdb takes its breakpoints out at every stop, but
+                            // ones set up through `monitor` stay
+                            self.debugger.remove_breakpoint(address);
+                        }
+                        "OK".into()
+                    }
+                    (_, Some(kind), Some(address)) => {
+                        let watched = Watched::Memory {
+                            address,
+                            len: len.unwrap_or(1),
+                        };
+                        if command == "Z" {
+                            self.debugger.add_watchpoint(watched, kind);
+                        } else {
+                            self.debugger.remove_watchpoint(watched, kind);
+                        }
+                        "OK".into()
+                    }
+                    _ => String::new(),
+                }
+            }
+            "s" => match self.debugger.step(chip, [0; 16]) {
+                Some(stop) => stop_reply(stop),
+                None => format!("S{SIGTRAP:02x}"),
+            },
+            "c" => {
+                self.debugger.resume();
+                self.running = true;
+                // The stop reply comes later, from run_frame or an interrupt
+                return Ok(());
+            }
+            "D" => {
+                self.send("OK")?;
+                self.detach();
+                return Ok(());
+            }
+            "k" => {
+                self.killed = true;
+                self.detach();
+                return Ok(());
+            }
+            "H" => "OK".into(),
+            "q" | "Q" => self.query(packet, chip),
+            _ => String::new(),
+        };
+        self.send(&reply)
+    }
+
+    fn query(&mut self, packet: &str, chip: &Chip8) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+");
+        }
+        if let Some(request) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = parse_range(request) else {
+                return "E01".into();
+            };
+            let start = offset.min(TARGET_XML.len());
+            let end = start.saturating_add(length).min(TARGET_XML.len());
+            let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+            return format!("{more}{}", &TARGET_XML[start..end]);
+        }
+        if let Some(command) = packet.strip_prefix("qRcmd,") {
+            let Some(command) = hex::decode(command).and_then(|c| String::from_utf8(c).ok()) else {
+                return "E01".into();
+            };
+            let output = self.monitor(&command, chip);
+            if !output.is_empty() {
+                // Errors from sending this show up again on the reply
+                let _ = self.send(&format!("O{}", hex::encode(output.as_bytes())));
+            }
+            return "OK".into();
+        }
+        match packet {
+            "qAttached" => "1".into(),
+            "qC" => "QC1".into(),
+            "qfThreadInfo" => "m1".into(),
+            "qsThreadInfo" => "l".into(),
+            _ => String::new(),
+        }
+    }
+
+    /// `monitor` commands, for what the protocol has no packets for. Returns
+    /// the text to show.
+    fn monitor(&mut self, command: &str, chip: &Chip8) -> String {
+        let (name, args) = command
+            .trim()
+            .split_once(' ')
+            .unwrap_or((command.trim(), ""));
+        let args = args.trim();
+        let result = match name {
+            "break" => self.monitor_break(args),
+            "trace" => args
+                .split_once(' ')
+                .ok_or("usage: trace ADDR TEXT".to_string())
+                .and_then(|(address, text)| {
+                    let breakpoint = Breakpoint {
+                        trace: Some(text.trim().parse()?),
+                        ..Breakpoint::default()
+                    };
+                    self.debugger.set_breakpoint(parse_address(address)?, breakpoint);
+                    Ok(String::new())
+                }),
+            "ignore" => args
+                .split_once(' ')
+                .ok_or("usage: ignore ADDR COUNT".to_string())
+                .and_then(|(address, count)| {
+                    let address = parse_address(address)?;
+                    let count = count.trim().parse().map_err(|_| format!("bad count `{count}`"))?;
+                    let breakpoint = self
+                        .debugger
+                        .breakpoint_mut(address)
+                        .ok_or(format!("no breakpoint at {address:#05x}"))?;
+                    breakpoint.ignore = count;
+                    Ok(String::new())
+                }),
+            "watch" | "rwatch" | "awatch" => parse_watched(args).map(|watched| {
+                let kind = match name {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                self.debugger.add_watchpoint(watched, kind);
+                String::new()
+            }),
+            "delete" => self.monitor_delete(args),
+            "info" => Ok(self.monitor_info()),
+            "smc" => match args {
+                "" => Ok(format!("{}\n", chip.code_map().summary())),
+                "stop" | "ignore" => {
+                    self.debugger.set_stop_on_self_modifying(args == "stop");
+                    Ok(String::new())
+                }
+                _ => Err("usage: smc [stop|ignore]".into()),
+            },
+            _ => Err(format!(
+                "unknown command `{name}`, try break, trace, ignore, watch, rwatch, awatch, delete, info or smc"
+            )),
+        };
+        match result {
+            Ok(output) => output,
+            Err(e) => format!("{e}\n"),
+        }
+    }
+
+    ///`break ADDR [if COND]`
+    fn monitor_break(&mut self, args: &str) -> Result<String, String> {
+        let (address, condition) = match args.split_once(" if ") {
+            Some((address, condition)) => (address, Some(condition.parse()?)),
+            None => (args, None),
+        };
+        let breakpoint = Breakpoint {
+            condition,
+            ..Breakpoint::default()
+        };
+        self.debugger
+            .set_breakpoint(parse_address(address)?, breakpoint);
+        Ok(String::new())
+    }
+
+    ///`delete ADDR|VX` removes the breakpoint and any watchpoints there
+    fn monitor_delete(&mut self, args: &str) -> Result<String, String> {
+        let watched = parse_watched(args)?;
+        let mut found = false;
+        if let Watched::Memory { address, .. } = watched {
+            found |= self.debugger.remove_breakpoint(address);
+        }
+        found |= self.debugger.clear_watchpoints(watched);
+        if !found {
+            return Err(format!("nothing set at `{args}`"));
+        }
+        Ok(String::new())
+    }
+
+    fn monitor_info(&self) -> String {
+        let mut out = String::new();
+        for (address, breakpoint) in self.debugger.breakpoints() {
+            out.push_str(&format!("{address:#05x} hits {}", breakpoint.hits));
+            if let Some(condition) = &breakpoint.condition {
+                out.push_str(&format!(" if {condition}"));
+            }
+            if breakpoint.ignore > 0 {
+                out.push_str(&format!(" ignore {}", breakpoint.ignore));
+            }
+            if let Some(trace) = &breakpoint.trace {
+                out.push_str(&format!(" trace {trace}"));
+            }
+            out.push('\n');
+        }
+        for watchpoint in self.debugger.watchpoints() {
+            let kind = match watchpoint.kind {
+                WatchKind::Read => "rwatch",
+                WatchKind::Write => "watch",
+                WatchKind::Access => "awatch",
+            };
+            let watched = match watchpoint.watched {
+                Watched::Memory { address, len } => format!("{address:#05x} {len}"),
+                Watched::Register(x) => format!("v{x:x}"),
+            };
+            out.push_str(&format!("{kind} {watched} hits {}\n", watchpoint.hits));
+        }
+        if out.is_empty() {
+            out.push_str("no breakpoints or watchpoints\n");
+        }
+        out
+    }
+}
+
+///`S05`, or `T05watch:ADDR;` and friends for memory watchpoints
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Watchpoint {
+            watched: Watched::Memory { address, .. },
+            kind,
+            ..
+        } => {
+            let kind = match kind {
+                WatchKind::Read => "rwatch",
+                WatchKind::Write => "watch",
+                WatchKind::Access => "awatch",
+            };
+            format!("T{SIGTRAP:02x}{kind}:{address:x};")
+        }
+        _ => format!("S{SIGTRAP:02x}"),
+    }
+}
+
+///`0x206` in hex or `518` in decimal, as in expressions
+fn parse_address(s: &str) -> Result<u16, String> {
+    let s = s.trim();
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("bad address `{s}`"))
+}
+
+///`VX`, or `ADDR [LEN]` with the length defaulting to one byte
+fn parse_watched(s: &str) -> Result<Watched, String> {
+    let mut words = s.split_whitespace();
+    let first = words.next().ok_or("expected an address or register")?;
+    if let Some(x) = first.strip_prefix(['v', 'V']) {
+        return match usize::from_str_radix(x, 16) {
+            Ok(x) if x < 16 => Ok(Watched::Register(x)),
+            _ => Err(format!("bad register `{first}`")),
+        };
+    }
+    let address = parse_address(first)?;
+    let len = match words.next() {
+        Some(len) => len.parse().map_err(|_| format!("bad length `{len}`"))?,
+        None => 1,
+    };
+    Ok(Watched::Memory { address, len })
+}
+
+fn register_width(n: usize) -> usize {
+    match n {
+        16 | 17 => 2,
+        _ => 1,
+    }
+}
+
+fn read_register(chip: &Chip8, n: usize) -> String {
+    match n {
+        0..=15 => hex::encode(&[chip.registers()[n]]),
+        16 => hex::encode(&chip.index_register().to_le_bytes()),
+        17 => hex::encode(&chip.program_counter().to_le_bytes()),
+        18 => hex::encode(&[chip.stack().len() as u8]),
+        19 => hex::encode(&[chip.delay_timer()]),
+        _ => hex::encode(&[chip.sound_timer()]),
+    }
+}
+
+///Returns false on bad hex. Writes to SP are ignored.
+fn write_register(chip: &mut Chip8, n: usize, value: &str) -> bool {
+    let Some(bytes) = hex::decode(value).filter(|b| b.len() == register_width(n)) else {
+        return false;
+    };
+    let wide = || u16::from_le_bytes([bytes[0], bytes[1]]);
+    match n {
+        0..=15 => chip.set_register(n, bytes[0]),
+        16 => chip.set_index_register(wide()),
+        17 => chip.set_program_counter(wide()),
+        18 => {}
+        19 => chip.set_delay_timer(bytes[0]),
+        _ => chip.set_sound_timer(bytes[0]),
+    }
+    true
+}
+
+///`addr,length` in hex
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (address, length) = s.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
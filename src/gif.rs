@@ -0,0 +1,329 @@
+//! Bare-bones GIF codec for Octo cartridges, which keep their data in the
+//! palette indices of every frame. Only the indices and the global palette
+//! are read: local palettes, transparency and disposal are skipped over.
+
+use std::collections::HashMap;
+use std::io;
+
+const MAX_CODES: u16 = 4096;
+const MAX_WIDTH: u32 = 12;
+
+///The biggest picture decoded, an Octo cartridge's. Anything larger is
+///refused before memory is set aside for it.
+pub const MAX_SCREEN_WIDTH: usize = 160;
+pub const MAX_SCREEN_HEIGHT: usize = 128;
+///Frames decoded at most, room for far more data than a program needs
+const MAX_FRAMES: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    ///0xRRGGBB, padded to a power of two of at least 2 when encoded
+    pub palette: Vec<u32>,
+    ///Palette indices row by row, each frame the whole image
+    pub frames: Vec<Vec<u8>>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("GIF: {message}"))
+}
+
+///Encodes every frame in full, in order
+pub fn encode(image: &Image) -> Vec<u8> {
+    // log2 of the palette size, which also sets the LZW code size
+    let colors = image.palette.len().clamp(2, 256);
+    let bits = colors.next_power_of_two().trailing_zeros();
+    let mut out = b"GIF89a".to_vec();
+    out.extend_from_slice(&(image.width as u16).to_le_bytes());
+    out.extend_from_slice(&(image.height as u16).to_le_bytes());
+    // Global palette, 8 bits per channel, background colour 0, square pixels
+    out.extend_from_slice(&[0xF0 | (bits as u8 - 1), 0, 0]);
+    for i in 0..1 << bits {
+        let color = image.palette.get(i).copied().unwrap_or(0);
+        out.extend_from_slice(&color.to_be_bytes()[1..]);
+    }
+
+    let min_code_size = bits.max(2);
+    for frame in &image.frames {
+        out.push(0x2C);
+        for value in [0, 0, image.width as u16, image.height as u16] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        // No local palette, not interlaced
+        out.push(0);
+        out.push(min_code_size as u8);
+        for block in lzw_encode(frame, min_code_size).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+    }
+    out.push(0x3B);
+    out
+}
+
+///Packs codes into bytes, least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += width;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw_encode(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut out = BitWriter::default();
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut width = min_code_size + 1;
+    let mut next = end + 1;
+    out.write(clear, width);
+
+    let mut pixels = indices.iter();
+    if let Some(&first) = pixels.next() {
+        let mut prefix = first as u16;
+        for &index in pixels {
+            if let Some(&code) = codes.get(&(prefix, index)) {
+                prefix = code;
+                continue;
+            }
+            out.write(prefix, width);
+            if next < MAX_CODES {
+                codes.insert((prefix, index), next);
+                next += 1;
+                if next as u32 > 1 << width && width < MAX_WIDTH {
+                    width += 1;
+                }
+            } else {
+                // The table is full, start over
+                out.write(clear, width);
+                codes.clear();
+                width = min_code_size + 1;
+                next = end + 1;
+            }
+            prefix = index as u16;
+        }
+        out.write(prefix, width);
+    }
+    out.write(end, width);
+    out.finish()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.at..self.at + count)
+            .ok_or_else(|| invalid("unexpected end of file"))?;
+        self.at += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    ///Data sub-blocks up to the empty one, joined
+    fn blocks(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let len = self.byte()? as usize;
+            if len == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(self.bytes(len)?);
+        }
+    }
+}
+
+pub fn is_gif(data: &[u8]) -> bool {
+    data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+}
+
+///Decodes every image in the file, each drawn over the frame before it
+pub fn decode(data: &[u8]) -> io::Result<Image> {
+    if !is_gif(data) {
+        return Err(invalid("not a GIF file"));
+    }
+    let mut reader = Reader { data, at: 6 };
+    let width = reader.u16()? as usize;
+    let height = reader.u16()? as usize;
+    if width > MAX_SCREEN_WIDTH || height > MAX_SCREEN_HEIGHT {
+        return Err(invalid("screen too big for a cartridge"));
+    }
+    let flags = reader.byte()?;
+    let background = reader.byte()?;
+    reader.byte()?;
+    let mut palette = Vec::new();
+    if flags & 0x80 != 0 {
+        for rgb in reader.bytes(3 << ((flags & 7) + 1))?.chunks(3) {
+            palette.push(u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]));
+        }
+    }
+
+    let mut frames = Vec::new();
+    let mut canvas = vec![background; width * height];
+    loop {
+        match reader.byte()? {
+            0x3B => break,
+            // Extensions: graphic control, comments, application data
+            0x21 => {
+                reader.byte()?;
+                reader.blocks()?;
+            }
+            0x2C => {
+                let (left, top) = (reader.u16()? as usize, reader.u16()? as usize);
+                let (w, h) = (reader.u16()? as usize, reader.u16()? as usize);
+                if w > MAX_SCREEN_WIDTH || h > MAX_SCREEN_HEIGHT {
+                    return Err(invalid("image too big for a cartridge"));
+                }
+                if frames.len() == MAX_FRAMES {
+                    return Err(invalid("too many frames"));
+                }
+                let flags = reader.byte()?;
+                if flags & 0x80 != 0 {
+                    reader.bytes(3 << ((flags & 7) + 1))?;
+                }
+                let min_code_size = reader.byte()? as u32;
+                if !(1..=11).contains(&min_code_size) {
+                    return Err(invalid("bad LZW code size"));
+                }
+                let indices = lzw_decode(&reader.blocks()?, min_code_size, w * h)?;
+                for (i, row) in rows(h, flags & 0x40 != 0).enumerate() {
+                    let (y, source) = (top + row, &indices[i * w..][..w]);
+                    if y >= height || left >= width {
+                        continue;
+                    }
+                    let span = w.min(width - left);
+                    canvas[y * width + left..][..span].copy_from_slice(&source[..span]);
+                }
+                frames.push(canvas.clone());
+            }
+            _ => return Err(invalid("unknown block")),
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        palette,
+        frames,
+    })
+}
+
+///The order rows are stored in, interlaced in four passes or not
+fn rows(height: usize, interlaced: bool) -> Box<dyn Iterator<Item = usize>> {
+    if interlaced {
+        Box::new(
+            [(0, 8), (4, 8), (2, 4), (1, 2)]
+                .into_iter()
+                .flat_map(move |(start, step)| (start..height).step_by(step)),
+        )
+    } else {
+        Box::new(0..height)
+    }
+}
+
+///Decodes exactly `len` indices, padding short data with zeros
+fn lzw_decode(data: &[u8], min_code_size: u32, len: usize) -> io::Result<Vec<u8>> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    // Each code's entry as the code before it and its last index
+    let mut table: Vec<(u16, u8)> = (0..clear).map(|i| (u16::MAX, i as u8)).collect();
+    table.extend([(u16::MAX, 0); 2]);
+    let mut width = min_code_size + 1;
+    let mut previous: Option<u16> = None;
+    let mut out = Vec::with_capacity(len);
+    let mut entry = Vec::new();
+
+    let (mut buffer, mut count, mut bytes) = (0u32, 0u32, data.iter());
+    while out.len() < len {
+        while count < width {
+            match bytes.next() {
+                Some(&byte) => buffer |= (byte as u32) << count,
+                None => break,
+            }
+            count += 8;
+        }
+        if count < width {
+            break;
+        }
+        let code = (buffer & ((1 << width) - 1)) as u16;
+        buffer >>= width;
+        count -= width;
+
+        if code == clear {
+            table.truncate(end as usize + 1);
+            width = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let Some(prev) = previous else {
+            if code >= clear {
+                return Err(invalid("bad LZW code"));
+            }
+            out.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+        let known = (code as usize) < table.len();
+        if !known && code as usize != table.len() {
+            return Err(invalid("bad LZW code"));
+        }
+        // The entry for `code`, unless it's the one being defined now
+        entry.clear();
+        let mut at = if known { code } else { prev };
+        while at != u16::MAX {
+            let (before, index) = table[at as usize];
+            entry.push(index);
+            at = before;
+        }
+        entry.reverse();
+        let first = entry[0];
+        if !known {
+            entry.push(first);
+        }
+        out.extend_from_slice(&entry);
+        if table.len() < MAX_CODES as usize {
+            table.push((prev, first));
+            if table.len() == 1 << width && width < MAX_WIDTH {
+                width += 1;
+            }
+        }
+        previous = Some(code);
+    }
+    out.resize(len, 0);
+    Ok(out)
+}
@@ -0,0 +1,51 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "octo")]
+pub mod cartridge;
+pub mod chip;
+pub mod code_map;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod detect;
+#[cfg(feature = "std")]
+pub mod gdb;
+#[cfg(feature = "std")]
+pub mod gif;
+#[cfg(feature = "std")]
+pub mod hex;
+pub mod instructions;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod octo;
+#[cfg(feature = "std")]
+pub mod osd;
+#[cfg(feature = "std")]
+pub mod png;
+#[cfg(feature = "std")]
+pub mod profiler;
+pub mod quirks;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod rng;
+#[cfg(feature = "romdb")]
+pub mod romdb;
+#[cfg(feature = "std")]
+pub mod scheduler;
+pub mod screen;
+#[cfg(feature = "std")]
+pub mod speed;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod video;
+#[cfg(all(target_arch = "wasm32", feature = "std"))]
+pub mod wasm;
+#[cfg(feature = "std")]
+mod tests;
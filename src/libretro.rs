@@ -0,0 +1,605 @@
+//! libretro core. Built into the `cdylib` with the `libretro` feature, so
+//! RetroArch and other libretro frontends can load the library directly.
+
+use crate::chip::{Chip8, DEFAULT_TICK_RATE, STATE_SIZE};
+use crate::quirks::Quirks;
+use crate::rng::{Rng, RngMode};
+use crate::video::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::sync::Mutex;
+
+pub const RETRO_API_VERSION: c_uint = 1;
+
+pub const RETRO_DEVICE_JOYPAD: c_uint = 1;
+pub const RETRO_DEVICE_KEYBOARD: c_uint = 3;
+
+pub const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+pub const RETRO_DEVICE_ID_JOYPAD_Y: c_uint = 1;
+pub const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+pub const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+pub const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+pub const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+pub const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+pub const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+pub const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+pub const RETRO_DEVICE_ID_JOYPAD_X: c_uint = 9;
+
+pub const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+pub const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+pub const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+pub const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+pub const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+pub const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+pub const RETRO_REGION_NTSC: c_uint = 0;
+
+pub const SAMPLE_RATE: f64 = 44100.0;
+const TONE_HZ: f64 = 440.0;
+const VOLUME: i16 = 0x1000;
+
+pub type RetroEnvironment = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = unsafe extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = unsafe extern "C" fn();
+pub type RetroInputState =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    pub geometry: RetroGameGeometry,
+    pub timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+pub struct RetroVariable {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+const OPTION_QUIRKS: &CStr = c"chip8_quirks";
+const OPTION_TICK_RATE: &CStr = c"chip8_tick_rate";
+const OPTION_LAYOUT: &CStr = c"chip8_joypad_layout";
+
+/// Joypad button to CHIP-8 key for each layout. `wasd` suits most modern
+/// games (5/7/8/9 to move, 6 to act), `numpad` the older 2/4/6/8 ones.
+const LAYOUTS: [(&str, [(c_uint, usize); 9]); 2] = [
+    (
+        "wasd",
+        [
+            (RETRO_DEVICE_ID_JOYPAD_UP, 0x5),
+            (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x7),
+            (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8),
+            (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x9),
+            (RETRO_DEVICE_ID_JOYPAD_A, 0x6),
+            (RETRO_DEVICE_ID_JOYPAD_B, 0x4),
+            (RETRO_DEVICE_ID_JOYPAD_X, 0x1),
+            (RETRO_DEVICE_ID_JOYPAD_Y, 0x2),
+            (RETRO_DEVICE_ID_JOYPAD_START, 0xF),
+        ],
+    ),
+    (
+        "numpad",
+        [
+            (RETRO_DEVICE_ID_JOYPAD_UP, 0x2),
+            (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4),
+            (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8),
+            (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6),
+            (RETRO_DEVICE_ID_JOYPAD_A, 0x5),
+            (RETRO_DEVICE_ID_JOYPAD_B, 0x0),
+            (RETRO_DEVICE_ID_JOYPAD_X, 0xA),
+            (RETRO_DEVICE_ID_JOYPAD_Y, 0xB),
+            (RETRO_DEVICE_ID_JOYPAD_START, 0xF),
+        ],
+    ),
+];
+
+///RETROK codes for the usual 1234/QWER/ASDF/ZXCV keyboard layout
+const KEYBOARD: [(c_uint, usize); 16] = [
+    (b'1' as c_uint, 0x1),
+    (b'2' as c_uint, 0x2),
+    (b'3' as c_uint, 0x3),
+    (b'4' as c_uint, 0xC),
+    (b'q' as c_uint, 0x4),
+    (b'w' as c_uint, 0x5),
+    (b'e' as c_uint, 0x6),
+    (b'r' as c_uint, 0xD),
+    (b'a' as c_uint, 0x7),
+    (b's' as c_uint, 0x8),
+    (b'd' as c_uint, 0x9),
+    (b'f' as c_uint, 0xE),
+    (b'z' as c_uint, 0xA),
+    (b'x' as c_uint, 0x0),
+    (b'c' as c_uint, 0xB),
+    (b'v' as c_uint, 0xF),
+];
+
+///What the frontend handed over. Copied out before calling any of them, so
+///the frontend can call back into the core from inside.
+#[derive(Clone, Copy, Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+#[derive(Default)]
+struct Core {
+    callbacks: Callbacks,
+    chip: Option<Chip8>,
+    rom: Vec<u8>,
+    quirks: Quirks,
+    tick_rate: u32,
+    layout: usize,
+    audio_phase: f64,
+    ///What `retro_get_memory_data` hands out, synced with the machine at
+    ///the start and end of each frame
+    memory: Vec<u8>,
+}
+
+///Core options as the frontend has them, `None` where it has no answer
+#[derive(Clone, Copy, Default)]
+struct Settings {
+    quirks: Option<Quirks>,
+    tick_rate: Option<u32>,
+    layout: Option<usize>,
+}
+
+///libretro has no context pointer, so the core is a process-wide singleton
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+///Never call into the frontend from `f`, it may call straight back in
+fn with_core<R>(f: impl FnOnce(&mut Core) -> R) -> R {
+    let mut guard = CORE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(guard.get_or_insert_with(|| Core {
+        tick_rate: DEFAULT_TICK_RATE,
+        ..Core::default()
+    }))
+}
+
+fn callbacks() -> Callbacks {
+    with_core(|core| core.callbacks)
+}
+
+impl Settings {
+    fn read(environment: Option<RetroEnvironment>) -> Self {
+        let Some(environment) = environment else {
+            return Settings::default();
+        };
+        Settings {
+            quirks: variable(environment, OPTION_QUIRKS).and_then(|v| Quirks::preset(&v)),
+            tick_rate: variable(environment, OPTION_TICK_RATE).and_then(|v| v.parse().ok()),
+            layout: variable(environment, OPTION_LAYOUT)
+                .and_then(|v| LAYOUTS.iter().position(|(name, _)| *name == v)),
+        }
+    }
+}
+
+fn variable(environment: RetroEnvironment, key: &CStr) -> Option<String> {
+    let mut variable = RetroVariable {
+        key: key.as_ptr(),
+        value: std::ptr::null(),
+    };
+    let found = unsafe {
+        environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut _ as *mut c_void,
+        )
+    };
+    if !found || variable.value.is_null() {
+        return None;
+    }
+    let value = unsafe { CStr::from_ptr(variable.value) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+impl Core {
+    fn apply(&mut self, settings: Settings) {
+        self.quirks = settings.quirks.unwrap_or(self.quirks);
+        self.tick_rate = settings.tick_rate.unwrap_or(self.tick_rate);
+        self.layout = settings.layout.unwrap_or(self.layout);
+        if let Some(chip) = &mut self.chip {
+            chip.set_quirks(self.quirks);
+            chip.set_tick_rate(self.tick_rate);
+        }
+    }
+
+    ///False when the ROM is empty or doesn't fit
+    fn start(&mut self) -> bool {
+        let mut chip = Chip8::with_rng(Rng::new(0, RngMode::Xorshift));
+        chip.set_quirks(self.quirks);
+        chip.set_tick_rate(self.tick_rate);
+        if chip.load_rom_bytes(&self.rom).is_err() {
+            return false;
+        }
+        self.chip = Some(chip);
+        self.publish_memory();
+        true
+    }
+
+    ///Copies the machine's memory out to the frontend's copy
+    fn publish_memory(&mut self) {
+        if let Some(chip) = &self.chip {
+            self.memory.resize(chip.memory().len(), 0);
+            self.memory.copy_from_slice(chip.memory());
+        }
+    }
+
+    ///Hands the machine whatever the frontend wrote to its copy since
+    fn take_memory_writes(&mut self) {
+        let Some(chip) = &mut self.chip else {
+            return;
+        };
+        let changed = |(a, b): (&u8, &u8)| a != b;
+        let Some(first) = chip.memory().iter().zip(&self.memory).position(changed) else {
+            return;
+        };
+        let last = chip
+            .memory()
+            .iter()
+            .zip(&self.memory)
+            .rposition(changed)
+            .unwrap_or(first);
+        chip.write_memory(first as u16, &self.memory[first..=last]);
+    }
+
+    ///One frame of stereo square wave while the sound timer runs, else silence
+    fn audio(&mut self, beeping: bool) -> Vec<i16> {
+        let frames = (SAMPLE_RATE / 60.0) as usize;
+        let mut audio = Vec::with_capacity(frames * 2);
+        for _ in 0..frames {
+            let sample = if !beeping {
+                0
+            } else if self.audio_phase < 0.5 {
+                VOLUME
+            } else {
+                -VOLUME
+            };
+            self.audio_phase = (self.audio_phase + TONE_HZ / SAMPLE_RATE).fract();
+            audio.push(sample);
+            audio.push(sample);
+        }
+        audio
+    }
+}
+
+fn options_changed(environment: Option<RetroEnvironment>) -> bool {
+    let Some(environment) = environment else {
+        return false;
+    };
+    let mut updated = false;
+    let ok = unsafe {
+        environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+            &mut updated as *mut bool as *mut c_void,
+        )
+    };
+    ok && updated
+}
+
+fn keys(input_state: Option<RetroInputState>, layout: usize) -> [u8; 16] {
+    let mut keys = [0u8; 16];
+    let Some(input_state) = input_state else {
+        return keys;
+    };
+    for (button, key) in LAYOUTS[layout].1 {
+        if unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, button) } != 0 {
+            keys[key] = 1;
+        }
+    }
+    for (code, key) in KEYBOARD {
+        if unsafe { input_state(0, RETRO_DEVICE_KEYBOARD, 0, code) } != 0 {
+            keys[key] = 1;
+        }
+    }
+    keys
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+/// `info` must point to writable memory for a `RetroSystemInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: c"chip-8mulator".as_ptr(),
+        library_version: c"0.1.0".as_ptr(),
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` must point to writable memory for a `RetroSystemAvInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: DISPLAY_WIDTH as c_uint,
+            base_height: DISPLAY_HEIGHT as c_uint,
+            max_width: DISPLAY_WIDTH as c_uint,
+            max_height: DISPLAY_HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: RetroSystemTiming {
+            fps: 60.0,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+/// # Safety
+/// `environment` must stay callable until `retro_deinit`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    let values = [
+        (OPTION_QUIRKS, c"Quirks; default|chip8|schip"),
+        (
+            OPTION_TICK_RATE,
+            c"Instructions per frame; 10|5|7|15|20|30|50|100|200|500|1000",
+        ),
+        (OPTION_LAYOUT, c"Joypad layout; wasd|numpad"),
+    ];
+    let mut variables: Vec<RetroVariable> = values
+        .iter()
+        .map(|(key, value)| RetroVariable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: std::ptr::null(),
+        value: std::ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+    with_core(|core| core.callbacks.environment = Some(environment));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: RetroVideoRefresh) {
+    with_core(|core| core.callbacks.video_refresh = Some(callback));
+}
+
+///Unused, audio goes out in batches
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: RetroAudioSampleBatch) {
+    with_core(|core| core.callbacks.audio_batch = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: RetroInputPoll) {
+    with_core(|core| core.callbacks.input_poll = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: RetroInputState) {
+    with_core(|core| core.callbacks.input_state = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+///Drops the game but keeps the callbacks, which frontends may set before init
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    with_core(|core| {
+        core.chip = None;
+        core.rom.clear();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(|core| {
+        if core.chip.is_some() {
+            core.start();
+        }
+    });
+}
+
+/// # Safety
+/// `game` must be null or point to a valid `RetroGameInfo` whose `data`
+/// holds `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let rom = std::slice::from_raw_parts(game.data as *const u8, game.size).to_vec();
+    let environment = callbacks().environment;
+    if let Some(environment) = environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        let accepted = environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        );
+        if !accepted {
+            return false;
+        }
+    }
+    let settings = Settings::read(environment);
+    with_core(|core| {
+        core.rom = rom;
+        core.apply(settings);
+        core.start()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    with_core(|core| core.chip = None);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    if let Some(poll) = callbacks.input_poll {
+        unsafe { poll() };
+    }
+    let settings =
+        options_changed(callbacks.environment).then(|| Settings::read(callbacks.environment));
+    let layout = with_core(|core| {
+        if let Some(settings) = settings {
+            core.apply(settings);
+        }
+        core.layout
+    });
+    let keys = keys(callbacks.input_state, layout);
+
+    let Some((display, audio)) = with_core(|core| {
+        core.take_memory_writes();
+        let chip = core.chip.as_mut()?;
+        chip.run_frame(keys);
+        let beeping = chip.sound_timer() > 0;
+        let display = *chip.display();
+        core.publish_memory();
+        Some((display, core.audio(beeping)))
+    }) else {
+        return;
+    };
+    if let Some(refresh) = callbacks.video_refresh {
+        unsafe {
+            refresh(
+                display.as_ptr() as *const c_void,
+                DISPLAY_WIDTH as c_uint,
+                DISPLAY_HEIGHT as c_uint,
+                DISPLAY_WIDTH * 4,
+            )
+        };
+    }
+    if let Some(batch) = callbacks.audio_batch {
+        unsafe { batch(audio.as_ptr(), audio.len() / 2) };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    // Save states are all the same length, so this holds for the whole game
+    with_core(|core| core.chip.as_ref().map_or(0, |_| STATE_SIZE))
+}
+
+/// # Safety
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let state = with_core(|core| {
+        core.take_memory_writes();
+        core.chip.as_ref().map(Chip8::save_state)
+    });
+    let Some(state) = state else {
+        return false;
+    };
+    if data.is_null() || size < STATE_SIZE {
+        return false;
+    }
+    std::ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    true
+}
+
+/// # Safety
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state = std::slice::from_raw_parts(data as *const u8, size);
+    with_core(|core| {
+        let loaded = core
+            .chip
+            .as_mut()
+            .is_some_and(|chip| chip.load_state(state).is_ok());
+        core.publish_memory();
+        loaded
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// A copy of the 4 KiB address space that the core owns, as of the end of
+/// the last frame. Frontends write to it between calls into the core, and
+/// the next `retro_run` hands those writes to the machine.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    with_core(|core| match (&core.chip, id) {
+        (Some(_), RETRO_MEMORY_SYSTEM_RAM) => core.memory.as_mut_ptr() as *mut c_void,
+        _ => std::ptr::null_mut(),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    with_core(|core| match (&core.chip, id) {
+        (Some(chip), RETRO_MEMORY_SYSTEM_RAM) => chip.memory().len(),
+        _ => 0,
+    })
+}
@@ -1,60 +1,491 @@
-mod chip;
-mod instructions;
-mod tests;
-
-use chip::Chip8;
-use instructions::Instructions;
-use minifb::{Key, Scale, ScaleMode, Window, WindowOptions};
+use chip_8mulator::bench;
+use chip_8mulator::cartridge::{self, Cartridge};
+use chip_8mulator::chip::{Chip8, Engine, DEFAULT_TICK_RATE};
+use chip_8mulator::detect::detect;
+use chip_8mulator::gdb::GdbStub;
+use chip_8mulator::movie::{self, Movie, MoviePlayer};
+use chip_8mulator::osd::{Canvas, Osd};
+use chip_8mulator::profiler::Profiler;
+use chip_8mulator::quirks::Quirks;
+use chip_8mulator::remote::{Address, RemoteServer};
+use chip_8mulator::rng::{Rng, RngMode};
+use chip_8mulator::romdb::Database;
+use chip_8mulator::scheduler::Scheduler;
+use chip_8mulator::speed::SpeedControl;
+use chip_8mulator::tui::{self, TuiStyle};
+use chip_8mulator::video::{render, Filter, VideoSettings, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
 use std::env;
-use std::time::SystemTime;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 fn main() {
     print!("hello world!");
-    let inputs = handle_input(env::args().collect());
-
-    let mut chip = Chip8::new();
-    chip.load_rom(inputs.0);
-    let cycles = inputs.1;
-
-    let my_options = WindowOptions {
-        borderless: false,
-        title: true,
-        resize: false,
-        scale: Scale::X16,
-        scale_mode: ScaleMode::Stretch,
-        topmost: false,
+    let mut args: Vec<String> = env::args().collect();
+    if args.get(1).is_some_and(|arg| arg == "info") {
+        info(&args[2..]);
+        return;
+    }
+    if args.get(1).is_some_and(|arg| arg == "cartridge") {
+        write_cartridge(&args[2..]);
+        return;
+    }
+    let bench = args.get(1).is_some_and(|arg| arg == "bench");
+    if bench {
+        args.remove(1);
+    }
+    let mut options = handle_input(args);
+
+    let (rom, cartridge) = load(&options.rom);
+    let cartridge = cartridge.map(|cartridge| cartridge.options);
+
+    // Settings given on the command line win over the cartridge's, and
+    // those over the database's
+    let loaded;
+    let database = match &options.db {
+        Some(dir) => {
+            loaded = load_database(dir);
+            &loaded
+        }
+        None => Database::bundled(),
+    };
+    let info = database.lookup(&rom);
+    // ROMs nothing else describes get a profile guessed from their code
+    let detected = match (info, &cartridge) {
+        (Some(info), _) => {
+            println!("Found {} in the ROM database", info.title);
+            None
+        }
+        (None, Some(_)) => None,
+        (None, None) => {
+            let detection = detect(&rom);
+            println!(
+                "Not in the ROM database, looks like {} ({}% sure)",
+                detection.platform.name(),
+                detection.confidence
+            );
+            Some(detection)
+        }
+    };
+    let keys = info.map(|info| info.keys.clone()).unwrap_or_default();
+    let colors = options
+        .colors
+        .or(cartridge.as_ref().and_then(|cartridge| cartridge.colors))
+        .or(info.and_then(|info| info.colors));
+    if let Some(colors) = colors {
+        options.video.palette = colors;
+    }
+
+    let player = options.play.as_ref().map(|path| {
+        MoviePlayer::new(
+            Movie::load(path).unwrap_or_else(|e| panic!("Could not load movie {path}: {e}")),
+        )
+    });
+    let (seed, rng_mode, tick_rate, quirks) = match &player {
+        Some(player) => {
+            let movie = player.movie();
+            if !movie.matches_rom(&rom) {
+                eprintln!(
+                    "Movie desync: ROM hash {} does not match the recorded {}",
+                    movie::sha1_hex(&rom),
+                    movie.rom_sha1
+                );
+            }
+            (movie.seed, movie.rng_mode, movie.tick_rate, movie.quirks)
+        }
+        None => (
+            options.seed,
+            options.rng_mode,
+            options
+                .tick_rate
+                .or(cartridge.as_ref().and_then(|cartridge| cartridge.tick_rate))
+                .or(info.and_then(|info| info.tick_rate))
+                .or(detected.as_ref().map(|detection| detection.tick_rate))
+                .unwrap_or(DEFAULT_TICK_RATE),
+            options
+                .quirks
+                .or(cartridge.as_ref().and_then(|cartridge| cartridge.quirks))
+                .or(info.and_then(|info| info.quirks))
+                .or(detected.as_ref().map(|detection| detection.quirks))
+                .unwrap_or_default(),
+        ),
+    };
+    println!("seed: {seed}");
+
+    let mut chip = Chip8::with_rng(Rng::new(seed, rng_mode));
+    chip.set_quirks(quirks);
+    chip.set_tick_rate(tick_rate);
+    chip.load_rom_bytes(&rom)
+        .unwrap_or_else(|e| panic!("Could not load {}: {e}", options.rom));
+    chip.set_engine(options.engine);
+
+    if bench {
+        let throughput = bench::measure(&mut chip, Duration::from_secs(options.seconds));
+        println!("{throughput}");
+        return;
+    }
+
+    let recorder = options
+        .record
+        .as_ref()
+        .map(|_| Movie::new(&rom, seed, rng_mode, tick_rate, quirks));
+    let profiler = options.profile.as_ref().map(|_| {
+        if options.gdb.is_some() {
+            panic!("--profile and --gdb can't be used together");
+        }
+        Profiler::new(rom.len())
+    });
+    let mut run = Run {
+        player,
+        recorder,
+        profiler,
+    };
+
+    let mut remote = options.remote.as_ref().map(|address| {
+        RemoteServer::bind(address)
+            .unwrap_or_else(|e| panic!("Could not listen on {address:?}: {e}"))
+    });
+
+    let mut gdb = options.gdb.as_ref().map(|address| {
+        if remote.is_some() {
+            panic!("--gdb and --remote can't be used together");
+        }
+        let stub = GdbStub::bind(address.as_str())
+            .unwrap_or_else(|e| panic!("Could not listen on {address}: {e}"));
+        println!("Waiting for a debugger on {address}");
+        stub
+    });
+
+    if options.headless {
+        run_headless(
+            &mut chip,
+            &mut run,
+            options.frames,
+            remote.as_mut(),
+            gdb.as_mut(),
+        );
+    } else if let Some(style) = options.tui {
+        if remote.is_some() || gdb.is_some() {
+            panic!("--remote and --gdb work with the window or --headless, not --tui");
+        }
+        tui::run(&mut chip, style, |chip, keys| {
+            run_frame(chip, &mut run, None, || keys)
+        })
+        .unwrap_or_else(|e| panic!("Terminal error: {e}"));
+    } else {
+        let speed = SpeedControl::new(options.ff_multiplier, options.slow_divisor);
+        run_window(
+            &mut chip,
+            &mut run,
+            speed,
+            options.video,
+            &keys,
+            remote.as_mut(),
+            gdb.as_mut(),
+        );
+    }
+
+    if chip.code_map().self_modifying() {
+        println!("{}", chip.code_map().summary());
+    }
+
+    if let (Some(prefix), Some(profiler)) = (&options.profile, &run.profiler) {
+        let files = [
+            (format!("{prefix}.txt"), profiler.report(&chip)),
+            (format!("{prefix}.folded"), profiler.folded()),
+            (format!("{prefix}.coverage"), profiler.coverage()),
+        ];
+        for (path, contents) in files {
+            fs::write(&path, contents).unwrap_or_else(|e| panic!("Could not write {path}: {e}"));
+        }
+        println!("Wrote the profile to {prefix}.txt, {prefix}.folded and {prefix}.coverage");
+    }
+
+    if let (Some(path), Some(movie)) = (&options.record, &run.recorder) {
+        movie
+            .save(path)
+            .unwrap_or_else(|e| panic!("Could not save movie {path}: {e}"));
+        println!("Recorded {} frames to {path}", movie.frames.len());
+    }
+}
+
+fn run_window(
+    chip: &mut Chip8,
+    run: &mut Run,
+    mut speed: SpeedControl,
+    mut video: VideoSettings,
+    layout: &[(String, u8)],
+    mut remote: Option<&mut RemoteServer>,
+    mut gdb: Option<&mut GdbStub>,
+) {
+    let mut fullscreen = false;
+    let mut window = open_window(fullscreen);
+    let mut scheduler = Scheduler::default();
+    let mut title = window_title(&speed, &scheduler);
+    let mut frame = Vec::new();
+    let mut osd = Osd::new(2);
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            speed.toggle_pause();
+            let message = if speed.paused() { "Paused" } else { "Resumed" };
+            osd.message(message, Instant::now());
+        }
+        if window.is_key_pressed(Key::N, KeyRepeat::Yes) {
+            speed.advance_frame();
+        }
+        if window.is_key_pressed(Key::L, KeyRepeat::No) {
+            speed.toggle_slow_motion();
+            osd.message(speed.label(), Instant::now());
+        }
+        if window.is_key_pressed(Key::F1, KeyRepeat::No) {
+            osd.toggle();
+        }
+        if window.is_key_pressed(Key::F2, KeyRepeat::No) {
+            video.filter = match video.filter {
+                Filter::Integer => Filter::Smooth,
+                Filter::Smooth => Filter::Integer,
+            };
+            let message = match video.filter {
+                Filter::Integer => "Integer scaling",
+                Filter::Smooth => "Smooth scaling",
+            };
+            osd.message(message, Instant::now());
+        }
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            video.grid = !video.grid;
+        }
+        if window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            fullscreen = !fullscreen;
+            window = open_window(fullscreen);
+            title.clear();
+        }
+        speed.set_fast_forward(window.is_key_down(Key::Tab));
+
+        if let Some(stub) = gdb.as_deref_mut() {
+            stub.poll(chip)
+                .unwrap_or_else(|e| panic!("GDB connection error: {e}"));
+            if stub.killed() {
+                break;
+            }
+        }
+        let mut remote_keys = [0x0; 16];
+        if let Some(server) = remote.as_deref_mut() {
+            server
+                .poll(chip, |chip, keys| run_frame(chip, run, None, || keys))
+                .unwrap_or_else(|e| panic!("Remote control error: {e}"));
+            if server.session().quit_requested() {
+                break;
+            }
+            remote_keys = server.session().keys();
+        }
+        let live = || {
+            let mut keys = set_controls(&window, layout);
+            for (key, remote) in keys.iter_mut().zip(remote_keys) {
+                *key |= remote;
+            }
+            keys
+        };
+
+        let mut emulated = 0;
+        if speed.uncapped() {
+            // Run flat out, but still come up for air to redraw and poll keys
+            let start = Instant::now();
+            while start.elapsed() < scheduler.frame_duration() {
+                run_frame(chip, run, gdb.as_deref_mut(), live);
+                emulated += 1;
+            }
+            scheduler.resync(Instant::now());
+        } else {
+            for _ in 0..scheduler.due_frames(Instant::now()) {
+                for _ in 0..speed.frames_to_run() {
+                    run_frame(chip, run, gdb.as_deref_mut(), live);
+                    emulated += 1;
+                }
+            }
+        }
+        scheduler.record_frames(emulated, Instant::now());
+
+        if window_title(&speed, &scheduler) != title {
+            title = window_title(&speed, &scheduler);
+            window.set_title(&title);
+        }
+        osd.set_status(vec![
+            format!(
+                "FPS {:.0} ({:.0}%)",
+                scheduler.measured_fps(),
+                scheduler.speed() * 100.0
+            ),
+            format!(
+                "IPS {:.0}",
+                scheduler.measured_fps() * chip.tick_rate() as f64
+            ),
+            format!("QUIRKS {}", chip.quirks().preset_name().unwrap_or("custom")),
+            speed.label(),
+        ]);
+        let (width, height) = window.get_size();
+        frame.resize(width * height, 0);
+        render(chip.display(), &mut frame, width, height, &video);
+        osd.draw(&mut Canvas::new(&mut frame, width, height), Instant::now());
+        window.update_with_buffer(&frame, width, height).unwrap();
+
+        if !speed.uncapped() {
+            scheduler.wait();
+        }
+    }
+}
+
+/// A resizable window, or a borderless one as big as the screen allows when
+/// `fullscreen` is set. minifb has no real fullscreen mode, so the latter
+/// is the closest it gets.
+fn open_window(fullscreen: bool) -> Window {
+    let options = WindowOptions {
+        borderless: fullscreen,
+        title: !fullscreen,
+        resize: !fullscreen,
+        scale: if fullscreen {
+            Scale::FitScreen
+        } else {
+            Scale::X16
+        },
+        // Our own scaler fills the whole buffer, minifb just copies it
+        scale_mode: ScaleMode::UpperLeft,
+        topmost: fullscreen,
         transparency: false,
         none: false,
     };
-    let mut window =
-        Window::new("Chip 8mulator - ESC to exit", 64, 32, my_options).unwrap_or_else(|e| {
+    let mut window = Window::new("Chip 8mulator", DISPLAY_WIDTH, DISPLAY_HEIGHT, options)
+        .unwrap_or_else(|e| {
             panic!("{}", e);
         });
+    if fullscreen {
+        window.set_position(0, 0);
+    }
+    // Pacing is the scheduler's job, not minifb's
+    window.set_target_fps(0);
+    window
+}
 
-    // Limit to max ~30 fps update rate
-    window.set_target_fps(180);
+fn window_title(speed: &SpeedControl, scheduler: &Scheduler) -> String {
+    format!(
+        "Chip 8mulator - {} {:.0}% - ESC exit, P pause, N step, Tab fast, L slow, F1 OSD, F2 filter, G grid, F11 fullscreen",
+        speed.label(),
+        scheduler.speed() * 100.0
+    )
+}
 
-    let mut prev_cycle = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
+/// What feeds and watches the emulated frames: a movie being played back or
+/// recorded, and the profiler.
+struct Run {
+    player: Option<MoviePlayer>,
+    recorder: Option<Movie>,
+    profiler: Option<Profiler>,
+}
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        chip.get_input(set_controls(&window));
+/// Runs a frame with movie or live input. Under a debugger frames go through
+/// the stub, which may stop part way; the rest of the frame then runs on
+/// the next call without consuming more input.
+fn run_frame(
+    chip: &mut Chip8,
+    run: &mut Run,
+    gdb: Option<&mut GdbStub>,
+    live: impl FnOnce() -> [u8; 16],
+) {
+    let Some(stub) = gdb else {
+        let keys = next_input(&mut run.player, live);
+        if let Some(movie) = &mut run.recorder {
+            movie.record(keys);
+        }
+        match &mut run.profiler {
+            Some(profiler) => profiler.run_frame(chip, keys),
+            None => chip.run_frame(keys),
+        }
+        return;
+    };
+    if !stub.running() {
+        return;
+    }
+    let mut keys = [0x0; 16];
+    if !stub.debugger().mid_frame() {
+        keys = next_input(&mut run.player, live);
+        if let Some(movie) = &mut run.recorder {
+            movie.record(keys);
+        }
+    }
+    stub.run_frame(chip, keys)
+        .unwrap_or_else(|e| panic!("GDB connection error: {e}"));
+}
+
+/// Runs without a window for `--frames` frames, or until the movie ends.
+/// With a remote attached it then serves requests until one asks to quit;
+/// under a debugger it runs in real time until the debugger kills it.
+fn run_headless(
+    chip: &mut Chip8,
+    run: &mut Run,
+    frames: Option<u64>,
+    remote: Option<&mut RemoteServer>,
+    gdb: Option<&mut GdbStub>,
+) {
+    let frames = frames
+        .or(run.player.as_ref().map(|p| p.movie().frames.len() as u64))
+        .or(remote.as_ref().map(|_| 0))
+        .or(gdb.as_ref().map(|_| 0))
+        .expect("Headless mode needs --frames, --play, --remote or --gdb");
+
+    for _ in 0..frames {
+        run_frame(chip, run, None, || [0x0; 16]);
+    }
+
+    if let Some(server) = remote {
+        while !server.session().quit_requested() {
+            server
+                .poll(chip, |chip, keys| run_frame(chip, run, None, || keys))
+                .unwrap_or_else(|e| panic!("Remote control error: {e}"));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    if let Some(stub) = gdb {
+        let mut scheduler = Scheduler::default();
+        while !stub.killed() {
+            stub.poll(chip)
+                .unwrap_or_else(|e| panic!("GDB connection error: {e}"));
+            for _ in 0..scheduler.due_frames(Instant::now()) {
+                run_frame(chip, run, Some(stub), || [0x0; 16]);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    let display: Vec<u8> = chip
+        .display()
+        .iter()
+        .flat_map(|p| p.to_le_bytes())
+        .collect();
+    println!(
+        "Ran {frames} frames, display sha1 {}",
+        movie::sha1_hex(&display)
+    );
+}
 
-        let curr_cycle = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-        let delta_time = curr_cycle - prev_cycle;
-        if delta_time > cycles {
-            chip.cycle();
-            prev_cycle = curr_cycle;
+///Movie input while one is playing, live input otherwise
+fn next_input(player: &mut Option<MoviePlayer>, live: impl FnOnce() -> [u8; 16]) -> [u8; 16] {
+    if let Some(movie) = player {
+        if let Some(keys) = movie.next_input() {
+            return keys;
         }
-        window.update_with_buffer(chip.display(), 64, 32).unwrap();
+        println!("Movie finished after {} frames", movie.frame());
+        *player = None;
     }
+    live()
 }
-fn set_controls(window: &Window) -> [u8; 16] {
+
+/// The hex keypad on 1-4/Q-R/A-F/Z-V, plus the arrows, space and left
+/// shift for whichever keys the ROM database names `up`, `down`, `left`,
+/// `right`, `a` and `b`.
+fn set_controls(window: &Window, layout: &[(String, u8)]) -> [u8; 16] {
     let mut output: [u8; 16] = [0x0; 16];
     output[0x1] = if window.is_key_down(Key::Key1) { 1 } else { 0 };
     output[0x2] = if window.is_key_down(Key::Key2) { 1 } else { 0 };
@@ -73,15 +504,273 @@ fn set_controls(window: &Window) -> [u8; 16] {
     output[0xB] = if window.is_key_down(Key::C) { 1 } else { 0 };
     output[0xF] = if window.is_key_down(Key::V) { 1 } else { 0 };
 
+    for (control, key) in layout {
+        let host = match control.as_str() {
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "a" => Key::Space,
+            "b" => Key::LeftShift,
+            _ => continue,
+        };
+        if window.is_key_down(host) {
+            output[*key as usize] = 1;
+        }
+    }
+
     output
 }
 
-fn handle_input(args: Vec<String>) -> (String, u128) {
-    if args.len() != 3 {
-        panic!("Error: Wrong number of Arguments \ncargo run <Rom> <Cycles>");
+struct Options {
+    rom: String,
+    ///Unset when the command line doesn't give a number, leaving it to the
+    ///ROM database
+    tick_rate: Option<u32>,
+    ff_multiplier: u32,
+    slow_divisor: u32,
+    video: VideoSettings,
+    seed: u64,
+    rng_mode: RngMode,
+    quirks: Option<Quirks>,
+    ///Unlit and lit pixels, if given
+    colors: Option<[u32; 2]>,
+    ///Directory holding a ROM database to use instead of the bundled one
+    db: Option<String>,
+    record: Option<String>,
+    play: Option<String>,
+    headless: bool,
+    tui: Option<TuiStyle>,
+    frames: Option<u64>,
+    remote: Option<Address>,
+    gdb: Option<String>,
+    profile: Option<String>,
+    ///How long `bench` runs for
+    seconds: u64,
+    engine: Engine,
+}
+
+fn handle_input(args: Vec<String>) -> Options {
+    if args.len() < 3 {
+        panic!("Error: Wrong number of Arguments \ncargo run <Rom> <Cycles> [--seed <N>] [--ff <N|uncapped>] [--slowmo <N>] [--smooth] [--grid] [--border <RRGGBB>] [--vip-rng] [--quirks <preset>] [--record <Movie>] [--play <Movie>] [--tui] [--braille] [--headless] [--frames <N>] [--remote <tcp:HOST:PORT|unix:PATH>] [--gdb <HOST:PORT>] [--profile <PREFIX>] [--engine <interpreter|cached|jit>] [--colors <RRGGBB,RRGGBB>] [--db <Dir>]\ncargo run bench <Rom> <Cycles> [--seconds <N>] [--seed <N>] [--vip-rng] [--quirks <preset>] [--engine <interpreter|cached|jit>] [--db <Dir>]\ncargo run info <Rom> [--db <Dir>]\ncargo run cartridge <Source.8o> <Out.gif> [--label <Text>] [--tick-rate <N>] [--quirks <preset>] [--colors <RRGGBB,RRGGBB>]");
     }
-    let cycles = args[2].parse::<u128>().unwrap_or(4);
+    let tick_rate = args[2].parse::<u32>().ok();
     let filename = args[1].to_string();
 
-    return (filename, cycles);
+    let mut options = Options {
+        rom: filename,
+        tick_rate,
+        ff_multiplier: 4,
+        slow_divisor: 4,
+        video: VideoSettings::default(),
+        seed: time_seed(),
+        rng_mode: RngMode::Xorshift,
+        quirks: None,
+        colors: None,
+        db: None,
+        record: None,
+        play: None,
+        headless: false,
+        tui: None,
+        frames: None,
+        remote: None,
+        gdb: None,
+        profile: None,
+        seconds: 5,
+        engine: Engine::default(),
+    };
+
+    let mut flags = args[3..].iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--seed" => {
+                let value = flags.next().expect("--seed needs a value");
+                options.seed = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid seed: {value}"));
+            }
+            "--vip-rng" => options.rng_mode = RngMode::Vip,
+            "--quirks" => {
+                let value = flags.next().expect("--quirks needs a value");
+                options.quirks = Some(value.parse().unwrap_or_else(|e| panic!("{e}")));
+            }
+            "--record" => {
+                options.record = Some(flags.next().expect("--record needs a file").clone())
+            }
+            "--play" => options.play = Some(flags.next().expect("--play needs a file").clone()),
+            "--ff" => {
+                let value = flags.next().expect("--ff needs a multiplier");
+                let multiplier = match value.as_str() {
+                    "uncapped" => 0,
+                    _ => value
+                        .parse()
+                        .unwrap_or_else(|_| panic!("Invalid multiplier: {value}")),
+                };
+                options.ff_multiplier = multiplier;
+            }
+            "--slowmo" => {
+                let value = flags.next().expect("--slowmo needs a divisor");
+                options.slow_divisor = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid divisor: {value}"));
+            }
+            "--smooth" => options.video.filter = Filter::Smooth,
+            "--grid" => options.video.grid = true,
+            "--border" => {
+                let value = flags.next().expect("--border needs an RRGGBB colour");
+                options.video.border = u32::from_str_radix(value.trim_start_matches('#'), 16)
+                    .unwrap_or_else(|_| panic!("Invalid colour: {value}"));
+            }
+            "--colors" => {
+                let value = flags.next().expect("--colors needs two RRGGBB colours");
+                options.colors = Some(parse_colors(value));
+            }
+            "--db" => options.db = Some(flags.next().expect("--db needs a directory").clone()),
+            "--tui" => options.tui = Some(TuiStyle::HalfBlock),
+            "--braille" => options.tui = Some(TuiStyle::Braille),
+            "--headless" => options.headless = true,
+            "--frames" => {
+                let value = flags.next().expect("--frames needs a value");
+                options.frames = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| panic!("Invalid frame count: {value}")),
+                );
+            }
+            "--remote" => {
+                let value = flags.next().expect("--remote needs an address");
+                options.remote = Some(value.parse().unwrap_or_else(|e| panic!("{e}")));
+            }
+            "--gdb" => options.gdb = Some(flags.next().expect("--gdb needs an address").clone()),
+            "--profile" => {
+                options.profile = Some(flags.next().expect("--profile needs a file prefix").clone())
+            }
+            "--seconds" => {
+                let value = flags.next().expect("--seconds needs a value");
+                options.seconds = value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid duration: {value}"));
+            }
+            "--engine" => {
+                let value = flags.next().expect("--engine needs a name");
+                options.engine =
+                    Engine::from_name(value).unwrap_or_else(|| panic!("Unknown engine: {value}"));
+            }
+            _ => panic!("Unknown argument: {flag}"),
+        }
+    }
+
+    options
+}
+
+///`RRGGBB,RRGGBB`, unlit then lit
+fn parse_colors(value: &str) -> [u32; 2] {
+    let parse = |color: &str| {
+        u32::from_str_radix(color.trim().trim_start_matches('#'), 16)
+            .unwrap_or_else(|_| panic!("Invalid colour: {color}"))
+    };
+    match value.split_once(',') {
+        Some((off, on)) => [parse(off), parse(on)],
+        None => panic!("--colors needs two colours separated by a comma: {value}"),
+    }
+}
+
+///The ROM at `path`, compiled first if it's an Octo cartridge
+fn load(path: &str) -> (Vec<u8>, Option<Cartridge>) {
+    let data = fs::read(path).unwrap_or_else(|_| panic!("Could not open file: {path}\n"));
+    if !cartridge::is_cartridge(&data) {
+        return (data, None);
+    }
+    let cartridge = Cartridge::read(&data).unwrap_or_else(|e| panic!("Could not read {path}: {e}"));
+    let rom = cartridge
+        .compile()
+        .unwrap_or_else(|e| panic!("Could not compile {path}: {e}"));
+    println!("Loaded an Octo cartridge");
+    (rom, Some(cartridge))
+}
+
+///`cartridge <Source.8o> <Out.gif> [--label <Text>] [--tick-rate <N>]
+///[--quirks <preset>] [--colors <RRGGBB,RRGGBB>]`: packs Octo source into a
+///cartridge, checking first that it compiles
+fn write_cartridge(args: &[String]) {
+    let [source, out, flags @ ..] = args else {
+        panic!("Usage: cargo run cartridge <Source.8o> <Out.gif> [--label <Text>] [--tick-rate <N>] [--quirks <preset>] [--colors <RRGGBB,RRGGBB>]");
+    };
+    let program =
+        fs::read_to_string(source).unwrap_or_else(|_| panic!("Could not open file: {source}\n"));
+    let rom = chip_8mulator::octo::compile(&program)
+        .unwrap_or_else(|e| panic!("Could not compile {source}: {e}"));
+
+    let mut label = Path::new(source)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut cartridge = Cartridge {
+        program,
+        options: cartridge::Options::default(),
+    };
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--label" => label = flags.next().expect("--label needs some text").clone(),
+            "--tick-rate" => {
+                let value = flags.next().expect("--tick-rate needs a value");
+                cartridge.options.tick_rate = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| panic!("Invalid tick rate: {value}")),
+                );
+            }
+            "--quirks" => {
+                let value = flags.next().expect("--quirks needs a value");
+                cartridge.options.quirks = Some(value.parse().unwrap_or_else(|e| panic!("{e}")));
+            }
+            "--colors" => {
+                let value = flags.next().expect("--colors needs two RRGGBB colours");
+                cartridge.options.colors = Some(parse_colors(value));
+            }
+            _ => panic!("Unknown argument: {flag}"),
+        }
+    }
+    fs::write(out, cartridge.write(&label))
+        .unwrap_or_else(|e| panic!("Could not write {out}: {e}"));
+    println!("Wrote {out}, {} bytes of code", rom.len());
+}
+
+fn load_database(dir: &str) -> Database {
+    Database::load(dir).unwrap_or_else(|e| panic!("Could not load the ROM database: {e}"))
+}
+
+///`info <Rom> [--db <Dir>]`: prints what the ROM database knows about a
+///ROM, then what its code suggests
+fn info(args: &[String]) {
+    let path = args.first().expect("info needs a ROM");
+    let loaded;
+    let database = match args.get(1..) {
+        Some([flag, dir]) if flag == "--db" => {
+            loaded = load_database(dir);
+            &loaded
+        }
+        Some([]) => Database::bundled(),
+        _ => panic!("Usage: cargo run info <Rom> [--db <Dir>]"),
+    };
+    let (rom, cartridge) = load(path);
+    if let Some(cartridge) = cartridge {
+        println!("Octo cartridge\n{}\n", cartridge.options);
+    }
+    match database.lookup(&rom) {
+        Some(info) => println!("{info}"),
+        None => println!(
+            "{path} is not in the ROM database (SHA-1 {})",
+            movie::sha1_hex(&rom)
+        ),
+    }
+    println!("\n{}", detect(&rom));
+}
+
+fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
 }
@@ -0,0 +1,190 @@
+use crate::quirks::Quirks;
+use crate::rng::RngMode;
+use std::fs;
+use std::io;
+
+const MAGIC: &str = "CHIP8MOVIE";
+///2 added `tick_rate`. Version 1 movies ran one instruction a frame with
+///the timers ticking per instruction, which nothing replays any more.
+const VERSION: u32 = 2;
+
+/// Everything needed to replay a session exactly: the machine setup plus
+/// the keypad state latched at the start of every frame.
+///
+/// Stored as text so movies diff cleanly:
+///
+/// ```text
+/// CHIP8MOVIE 2
+/// rom_sha1 <40 hex digits>
+/// seed <u64>
+/// rng xorshift|vip
+/// tick_rate <instructions per frame>
+/// quirks shift_uses_vy=0 ...
+/// frames
+/// 0000
+/// 0010
+/// ```
+///
+/// Each frame line is the 16 key bitmask in hex, bit N set while key N is down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_sha1: String,
+    pub seed: u64,
+    pub rng_mode: RngMode,
+    pub tick_rate: u32,
+    pub quirks: Quirks,
+    pub frames: Vec<u16>,
+}
+
+///SHA-1 of `bytes` as lowercase hex, used to identify ROMs
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    sha1_smol::Sha1::from(bytes).digest().to_string()
+}
+
+pub fn keys_to_mask(keys: [u8; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .filter(|(_, pressed)| **pressed != 0)
+        .fold(0, |mask, (key, _)| mask | 1 << key)
+}
+
+pub fn mask_to_keys(mask: u16) -> [u8; 16] {
+    let mut keys = [0u8; 16];
+    for (key, pressed) in keys.iter_mut().enumerate() {
+        *pressed = ((mask >> key) & 1) as u8;
+    }
+    keys
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], seed: u64, rng_mode: RngMode, tick_rate: u32, quirks: Quirks) -> Self {
+        Movie {
+            rom_sha1: sha1_hex(rom),
+            seed,
+            rng_mode,
+            tick_rate,
+            quirks,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, keys: [u8; 16]) {
+        self.frames.push(keys_to_mask(keys));
+    }
+
+    ///True when `rom` is the ROM this movie was recorded against
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_sha1 == sha1_hex(rom)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Movie::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix(MAGIC))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| invalid("Not a movie file".to_string()))?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "Movie format {version} is not supported, only {VERSION}; record it again"
+            )));
+        }
+
+        let mut header = |key: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(key))
+                .map(str::trim)
+                .ok_or_else(|| invalid(format!("Missing movie field: {key}")))
+        };
+        let rom_sha1 = header("rom_sha1")?.to_string();
+        let seed = header("seed")?;
+        let seed = seed
+            .parse()
+            .map_err(|_| invalid(format!("Invalid seed: {seed}")))?;
+        let rng = header("rng")?;
+        let rng_mode =
+            RngMode::from_name(rng).ok_or_else(|| invalid(format!("Invalid rng: {rng}")))?;
+        let tick_rate = header("tick_rate")?;
+        let tick_rate = tick_rate
+            .parse()
+            .map_err(|_| invalid(format!("Invalid tick rate: {tick_rate}")))?;
+        let quirks = header("quirks")?.parse().map_err(invalid)?;
+        header("frames")?;
+
+        let frames = lines
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                u16::from_str_radix(line.trim(), 16)
+                    .map_err(|_| invalid(format!("Invalid frame: {line}")))
+            })
+            .collect::<io::Result<Vec<u16>>>()?;
+
+        Ok(Movie {
+            rom_sha1,
+            seed,
+            rng_mode,
+            tick_rate,
+            quirks,
+            frames,
+        })
+    }
+}
+
+impl std::fmt::Display for Movie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{MAGIC} {VERSION}")?;
+        writeln!(f, "rom_sha1 {}", self.rom_sha1)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "rng {}", self.rng_mode.name())?;
+        writeln!(f, "tick_rate {}", self.tick_rate)?;
+        writeln!(f, "quirks {}", self.quirks)?;
+        writeln!(f, "frames")?;
+        for mask in &self.frames {
+            writeln!(f, "{mask:04x}")?;
+        }
+        Ok(())
+    }
+}
+
+///Feeds a movie's frames back one at a time
+pub struct MoviePlayer {
+    movie: Movie,
+    position: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer { movie, position: 0 }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn frame(&self) -> usize {
+        self.position
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.movie.frames.len()
+    }
+
+    pub fn next_input(&mut self) -> Option<[u8; 16]> {
+        let mask = *self.movie.frames.get(self.position)?;
+        self.position += 1;
+        Some(mask_to_keys(mask))
+    }
+}
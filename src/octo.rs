@@ -0,0 +1,683 @@
+//! A compiler for the core of Octo (<https://github.com/JohnEarnest/Octo>),
+//! the language Octo cartridges carry their programs in.
+//!
+//! Covered: `: label`, `:const`, `:alias`, `:next`, `:org`, `:unpack`,
+//! `:byte`, `:call`, every statement including the SUPER-CHIP and XO-CHIP
+//! ones, `if ... then`, `if ... begin ... else ... end`,
+//! `loop ... while ... again` and bare numbers as data. Macros, `:calc`,
+//! `:stringmode` and `{ }` expressions are reported as unsupported.
+//!
+//! Execution starts at `main`: a `jump main` goes first unless `main` is
+//! defined before any code.
+//!
+//! ```text
+//! : main
+//!     v0 := 0
+//!     loop
+//!         i := hex v0
+//!         sprite v1 v2 5
+//!         v0 += 1
+//!         if v0 == 16 then v0 := 0
+//!     again
+//! ```
+
+use crate::asm::AsmError;
+use std::collections::HashMap;
+
+const ORIGIN: u16 = 0x200;
+const VF: u16 = 0xF;
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+///Where a label's address goes once it's known
+#[derive(Clone, Copy)]
+enum Patch {
+    ///The low 12 bits of the instruction
+    Address,
+    ///The word after `F000`
+    Long,
+    ///`v0 := hi` and `v1 := lo`, with a nybble above `hi` unless it's long
+    Unpack(Option<u8>),
+}
+
+struct Fixup<'a> {
+    at: u16,
+    name: &'a str,
+    line: usize,
+    patch: Patch,
+}
+
+enum Block {
+    ///`jump` is the placeholder to point past the branch
+    If {
+        jump: u16,
+        line: usize,
+        has_else: bool,
+    },
+    Loop {
+        start: u16,
+        line: usize,
+        whiles: Vec<u16>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    V(u16),
+    Number(u8),
+}
+
+struct Compiler<'a> {
+    tokens: Vec<Token<'a>>,
+    at: usize,
+    rom: Vec<u8>,
+    here: u32,
+    ///Past the point where `jump main` would go
+    started: bool,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i32>,
+    aliases: HashMap<&'a str, u16>,
+    fixups: Vec<Fixup<'a>>,
+    blocks: Vec<Block>,
+    next: Option<(&'a str, usize)>,
+}
+
+///Compiles Octo `source` into a ROM image to load at 0x200
+pub fn compile(source: &str) -> Result<Vec<u8>, AsmError> {
+    let tokens: Vec<Token> = source
+        .lines()
+        .enumerate()
+        .flat_map(|(n, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace()
+                .map(move |text| Token { text, line: n + 1 })
+        })
+        .collect();
+    let mut compiler = Compiler {
+        tokens,
+        at: 0,
+        rom: Vec::new(),
+        here: ORIGIN as u32,
+        started: false,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        next: None,
+    };
+    while compiler.at < compiler.tokens.len() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+fn error(line: usize, message: impl Into<String>) -> AsmError {
+    AsmError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    if negative {
+        value.checked_neg()
+    } else {
+        Some(value)
+    }
+}
+
+///The skip that does the opposite
+fn invert(skip: u16) -> u16 {
+    match (skip >> 12, skip & 0xFF) {
+        (0x3, _) => skip + 0x1000,
+        (0x4, _) => skip - 0x1000,
+        (0x5, _) => skip + 0x4000,
+        (0x9, _) => skip - 0x4000,
+        (_, 0x9E) => skip + 3,
+        _ => skip - 3,
+    }
+}
+
+impl<'a> Compiler<'a> {
+    fn line(&self) -> usize {
+        let at = self.at.min(self.tokens.len()).saturating_sub(1);
+        self.tokens.get(at).map_or(1, |token| token.line)
+    }
+
+    fn fail<T>(&self, message: impl Into<String>) -> Result<T, AsmError> {
+        Err(error(self.line(), message))
+    }
+
+    fn token(&mut self) -> Result<&'a str, AsmError> {
+        match self.tokens.get(self.at) {
+            Some(token) => {
+                self.at += 1;
+                Ok(token.text)
+            }
+            None => self.fail("unexpected end of program"),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.at).map(|token| token.text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.token()?;
+        if token != text {
+            return self.fail(format!("expected `{text}`, found `{token}`"));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), AsmError> {
+        let offset = (self.here - ORIGIN as u32) as usize;
+        if self.here > 0xFFFF {
+            return self.fail("program runs past the end of memory");
+        }
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn op(&mut self, opcode: u16) -> Result<(), AsmError> {
+        // `:next` names the byte after the opcode's first, its operand
+        let next = self.next.take();
+        for byte in opcode.to_be_bytes() {
+            self.emit(byte)?;
+        }
+        if let Some((name, line)) = next {
+            self.define(name, (self.here - 1) as u16, line)?;
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, name: &'a str, address: u16, line: usize) -> Result<(), AsmError> {
+        if self.labels.insert(name, address).is_some() {
+            return Err(error(line, format!("`{name}` is defined twice")));
+        }
+        Ok(())
+    }
+
+    ///A number, constant or label already defined
+    fn value(&self, text: &str) -> Option<i32> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|&address| address as i32))
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.token()?;
+        match self.as_register(token) {
+            Some(x) => Ok(x),
+            None => self.fail(format!("expected a register, found `{token}`")),
+        }
+    }
+
+    fn as_register(&self, text: &str) -> Option<u16> {
+        if let Some(&x) = self.aliases.get(text) {
+            return Some(x);
+        }
+        let digit = text.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u16::from_str_radix(digit, 16).ok()
+    }
+
+    fn ranged(&mut self, min: i32, max: i32, what: &str) -> Result<i32, AsmError> {
+        let token = self.token()?;
+        match self.value(token) {
+            Some(value) if (min..=max).contains(&value) => Ok(value),
+            Some(value) => self.fail(format!("{what} out of range: {value}")),
+            None => self.fail(format!("expected {what}, found `{token}`")),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        Ok(self.ranged(-128, 255, "a byte")? as u8)
+    }
+
+    fn nybble(&mut self) -> Result<u16, AsmError> {
+        Ok(self.ranged(0, 15, "a nybble")? as u16)
+    }
+
+    fn operand(&mut self) -> Result<Operand, AsmError> {
+        let token = self.peek();
+        match token.and_then(|token| self.as_register(token)) {
+            Some(x) => {
+                self.at += 1;
+                Ok(Operand::V(x))
+            }
+            None => Ok(Operand::Number(self.byte()?)),
+        }
+    }
+
+    ///`base` with a 12-bit address, patched in later if it isn't known yet
+    fn address_op(&mut self, base: u16) -> Result<(), AsmError> {
+        let token = self.token()?;
+        let address = match self.value(token) {
+            Some(address) if (0..=0xFFF).contains(&address) => address as u16,
+            Some(address) => return self.fail(format!("address out of range: {address:#x}")),
+            None if number(token).is_none() => {
+                self.fixup(token, 0, Patch::Address);
+                0
+            }
+            None => unreachable!("numbers always have a value"),
+        };
+        self.op(base | address)
+    }
+
+    ///Notes a reference to `name` at `offset` bytes from here
+    fn fixup(&mut self, name: &'a str, offset: u32, patch: Patch) {
+        self.fixups.push(Fixup {
+            at: (self.here + offset) as u16,
+            name,
+            line: self.line(),
+            patch,
+        });
+    }
+
+    fn set_address(&mut self, at: u16, address: u16) {
+        let offset = (at - ORIGIN) as usize;
+        self.rom[offset] = (self.rom[offset] & 0xF0) | (address >> 8) as u8;
+        self.rom[offset + 1] = address as u8;
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.token()?;
+        let line = self.line();
+        let declaration = matches!(token, ":const" | ":alias" | ":breakpoint" | ":monitor")
+            || (token == ":" && self.peek() == Some("main"));
+        if !self.started && !declaration {
+            self.started = true;
+            if !self.labels.contains_key("main") {
+                self.fixup("main", 0, Patch::Address);
+                self.op(0x1000)?;
+            }
+        }
+        match token {
+            ":" => {
+                let name = self.token()?;
+                self.define(name, self.here as u16, line)?;
+            }
+            ":const" => {
+                let name = self.token()?;
+                let value = self.token()?;
+                match self.value(value) {
+                    Some(value) => {
+                        self.constants.insert(name, value);
+                    }
+                    None => return self.fail(format!("unknown value `{value}`")),
+                }
+            }
+            ":alias" => {
+                let name = self.token()?;
+                let x = self.register()?;
+                self.aliases.insert(name, x);
+            }
+            ":next" => self.next = Some((self.token()?, line)),
+            ":org" => self.here = self.ranged(ORIGIN as i32, 0xFFFF, "an address")? as u32,
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            }
+            ":call" => self.address_op(0x2000)?,
+            ":unpack" => {
+                let nybble = match self.token()? {
+                    "long" => None,
+                    token => match self.value(token) {
+                        Some(value @ 0..=15) => Some(value as u8),
+                        _ => return self.fail(format!("expected a nybble, found `{token}`")),
+                    },
+                };
+                let name = self.token()?;
+                self.fixup(name, 0, Patch::Unpack(nybble));
+                self.op(0x6000)?;
+                self.op(0x6100)?;
+            }
+            ":breakpoint" => {
+                self.token()?;
+            }
+            ":monitor" => {
+                self.token()?;
+                self.token()?;
+            }
+            ":macro" | ":calc" | ":stringmode" | ":assert" | ":pointer" | ":include" | "{" => {
+                return self.fail(format!("`{token}` is not supported"));
+            }
+
+            "clear" => self.op(0x00E0)?,
+            "return" | ";" => self.op(0x00EE)?,
+            "hires" => self.op(0x00FF)?,
+            "lores" => self.op(0x00FE)?,
+            "exit" => self.op(0x00FD)?,
+            "scroll-left" => self.op(0x00FC)?,
+            "scroll-right" => self.op(0x00FB)?,
+            "scroll-down" => {
+                let n = self.nybble()?;
+                self.op(0x00C0 | n)?;
+            }
+            "scroll-up" => {
+                let n = self.nybble()?;
+                self.op(0x00D0 | n)?;
+            }
+            "audio" => self.op(0xF002)?,
+            "plane" => {
+                let n = self.nybble()?;
+                self.op(0xF001 | n << 8)?;
+            }
+            "jump" => self.address_op(0x1000)?,
+            "jump0" => self.address_op(0xB000)?,
+            "sprite" => {
+                let (x, y) = (self.register()?, self.register()?);
+                let n = self.nybble()?;
+                self.op(0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.at += 1;
+                    let y = self.register()?;
+                    let n = if token == "save" { 2 } else { 3 };
+                    self.op(0x5000 | x << 8 | y << 4 | n)?;
+                } else {
+                    let nn = if token == "save" { 0x55 } else { 0x65 };
+                    self.op(0xF000 | x << 8 | nn)?;
+                }
+            }
+            "bcd" => self.register_op(0xF033)?,
+            "saveflags" => self.register_op(0xF075)?,
+            "loadflags" => self.register_op(0xF085)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let nn = match token {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.register_op(0xF000 | nn)?;
+            }
+            "i" => self.index()?,
+
+            "if" => {
+                let (prefix, skip) = self.condition()?;
+                for opcode in prefix {
+                    self.op(opcode)?;
+                }
+                match self.token()? {
+                    "then" => {
+                        self.op(skip)?;
+                        self.statement()?;
+                    }
+                    "begin" => {
+                        self.op(invert(skip))?;
+                        let jump = self.here as u16;
+                        self.op(0x1000)?;
+                        self.blocks.push(Block::If {
+                            jump,
+                            line,
+                            has_else: false,
+                        });
+                    }
+                    other => {
+                        return self.fail(format!("expected `then` or `begin`, found `{other}`"))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If {
+                    jump,
+                    has_else: false,
+                    line,
+                }) => {
+                    let end = self.here as u16;
+                    self.op(0x1000)?;
+                    self.set_address(jump, self.here as u16);
+                    self.blocks.push(Block::If {
+                        jump: end,
+                        line,
+                        has_else: true,
+                    });
+                }
+                _ => return self.fail("`else` without `if ... begin`"),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => self.set_address(jump, self.here as u16),
+                _ => return self.fail("`end` without `if ... begin`"),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here as u16,
+                line,
+                whiles: Vec::new(),
+            }),
+            "while" => {
+                let (prefix, skip) = self.condition()?;
+                for opcode in prefix {
+                    self.op(opcode)?;
+                }
+                self.op(invert(skip))?;
+                let jump = self.here as u16;
+                self.op(0x1000)?;
+                match self.blocks.last_mut() {
+                    Some(Block::Loop { whiles, .. }) => whiles.push(jump),
+                    _ => return self.fail("`while` outside a loop"),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, whiles, .. }) => {
+                    self.op(0x1000 | start)?;
+                    for jump in whiles {
+                        self.set_address(jump, self.here as u16);
+                    }
+                }
+                _ => return self.fail("`again` without `loop`"),
+            },
+
+            _ => {
+                if let Some(x) = self.as_register(token) {
+                    return self.assignment(x);
+                }
+                if number(token).is_some() || self.constants.contains_key(token) {
+                    // Bare numbers are data
+                    self.at -= 1;
+                    let byte = self.byte()?;
+                    return self.emit(byte);
+                }
+                // Anything else calls a label
+                self.at -= 1;
+                self.address_op(0x2000)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_op(&mut self, base: u16) -> Result<(), AsmError> {
+        let x = self.register()?;
+        self.op(base | x << 8)
+    }
+
+    fn index(&mut self) -> Result<(), AsmError> {
+        match self.token()? {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.at += 1;
+                    self.register_op(0xF029)
+                }
+                Some("bighex") => {
+                    self.at += 1;
+                    self.register_op(0xF030)
+                }
+                Some("long") => {
+                    self.at += 1;
+                    let token = self.token()?;
+                    let address = match self.value(token) {
+                        Some(address @ 0..=0xFFFF) => address as u16,
+                        Some(address) => {
+                            return self.fail(format!("address out of range: {address:#x}"))
+                        }
+                        None => {
+                            self.fixup(token, 2, Patch::Long);
+                            0
+                        }
+                    };
+                    self.op(0xF000)?;
+                    self.op(address)
+                }
+                _ => self.address_op(0xA000),
+            },
+            "+=" => self.register_op(0xF01E),
+            other => self.fail(format!("expected `:=` or `+=` after i, found `{other}`")),
+        }
+    }
+
+    fn assignment(&mut self, x: u16) -> Result<(), AsmError> {
+        let operator = self.token()?;
+        let source = match (operator, self.peek()) {
+            (":=", Some("key")) => return self.keyword_op(0xF00A | x << 8),
+            (":=", Some("delay")) => return self.keyword_op(0xF007 | x << 8),
+            (":=", Some("random")) => {
+                self.at += 1;
+                let nn = self.byte()?;
+                return self.op(0xC000 | x << 8 | nn as u16);
+            }
+            _ => self.operand()?,
+        };
+        let opcode = match (operator, source) {
+            (":=", Operand::V(y)) => 0x8000 | x << 8 | y << 4,
+            (":=", Operand::Number(nn)) => 0x6000 | x << 8 | nn as u16,
+            ("+=", Operand::V(y)) => 0x8004 | x << 8 | y << 4,
+            ("+=", Operand::Number(nn)) => 0x7000 | x << 8 | nn as u16,
+            ("-=", Operand::V(y)) => 0x8005 | x << 8 | y << 4,
+            ("-=", Operand::Number(nn)) => 0x7000 | x << 8 | nn.wrapping_neg() as u16,
+            ("=-", Operand::V(y)) => 0x8007 | x << 8 | y << 4,
+            ("|=", Operand::V(y)) => 0x8001 | x << 8 | y << 4,
+            ("&=", Operand::V(y)) => 0x8002 | x << 8 | y << 4,
+            ("^=", Operand::V(y)) => 0x8003 | x << 8 | y << 4,
+            (">>=", Operand::V(y)) => 0x8006 | x << 8 | y << 4,
+            ("<<=", Operand::V(y)) => 0x800E | x << 8 | y << 4,
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", Operand::Number(_)) => {
+                return self.fail(format!("`{operator}` needs a register"));
+            }
+            _ => return self.fail(format!("unknown operator `{operator}`")),
+        };
+        self.op(opcode)
+    }
+
+    fn keyword_op(&mut self, opcode: u16) -> Result<(), AsmError> {
+        self.at += 1;
+        self.op(opcode)
+    }
+
+    /// Instructions to run first, then the skip that passes over the next
+    /// instruction when the condition is false. `<`, `>`, `<=` and `>=`
+    /// subtract into VF, as Octo does.
+    fn condition(&mut self) -> Result<(Vec<u16>, u16), AsmError> {
+        let left = self.operand()?;
+        let operator = self.token()?;
+        let x = match left {
+            Operand::V(x) => x,
+            Operand::Number(_) if matches!(operator, "<" | ">" | "<=" | ">=") => 0,
+            Operand::Number(_) => return self.fail("the left of a condition must be a register"),
+        };
+        let skip = match operator {
+            "key" => return Ok((Vec::new(), 0xE0A1 | x << 8)),
+            "-key" => return Ok((Vec::new(), 0xE09E | x << 8)),
+            "==" | "!=" => {
+                let equal = operator == "==";
+                match self.operand()? {
+                    Operand::V(y) if equal => 0x9000 | x << 8 | y << 4,
+                    Operand::V(y) => 0x5000 | x << 8 | y << 4,
+                    Operand::Number(nn) if equal => 0x4000 | x << 8 | nn as u16,
+                    Operand::Number(nn) => 0x3000 | x << 8 | nn as u16,
+                }
+            }
+            "<" | ">" | "<=" | ">=" => {
+                let right = self.operand()?;
+                // VF's borrow flag says whether a >= b
+                let (a, b) = match operator {
+                    "<" | ">=" => (left, right),
+                    _ => (right, left),
+                };
+                let prefix = match (a, b) {
+                    (Operand::V(VF), _) | (_, Operand::V(VF)) => {
+                        return self.fail("VF can't be compared, it holds the result");
+                    }
+                    (Operand::V(a), Operand::V(b)) => vec![0x8F00 | b << 4, 0x8F07 | a << 4],
+                    (Operand::V(a), Operand::Number(b)) => vec![0x6F00 | b as u16, 0x8F07 | a << 4],
+                    (Operand::Number(a), Operand::V(b)) => vec![0x6F00 | a as u16, 0x8F05 | b << 4],
+                    (Operand::Number(_), Operand::Number(_)) => {
+                        return self.fail("a condition needs a register");
+                    }
+                };
+                // Skip when VF is 0 if the condition needs a >= b, else when it's 1
+                let skip = match operator {
+                    ">=" | "<=" => 0x3F00,
+                    _ => 0x4F00,
+                };
+                return Ok((prefix, skip));
+            }
+            other => return self.fail(format!("unknown condition `{other}`")),
+        };
+        Ok((Vec::new(), skip))
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, what) = match block {
+                Block::If { line, .. } => (*line, "`if ... begin` without `end`"),
+                Block::Loop { line, .. } => (*line, "`loop` without `again`"),
+            };
+            return Err(error(line, what));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&address) = self.labels.get(fixup.name) else {
+                return Err(error(fixup.line, format!("unknown label `{}`", fixup.name)));
+            };
+            let offset = (fixup.at - ORIGIN) as usize;
+            match fixup.patch {
+                Patch::Address if address > 0xFFF => {
+                    return Err(error(
+                        fixup.line,
+                        format!("`{}` is past 0xFFF, use `i := long`", fixup.name),
+                    ));
+                }
+                Patch::Address => self.set_address(fixup.at, address),
+                Patch::Long => self.rom[offset..offset + 2].copy_from_slice(&address.to_be_bytes()),
+                Patch::Unpack(nybble) => {
+                    let high = match nybble {
+                        Some(_) if address > 0xFFF => {
+                            return Err(error(
+                                fixup.line,
+                                format!("`{}` is past 0xFFF, use `:unpack long`", fixup.name),
+                            ));
+                        }
+                        Some(nybble) => nybble << 4 | (address >> 8) as u8,
+                        None => (address >> 8) as u8,
+                    };
+                    self.rom[offset + 1] = high;
+                    self.rom[offset + 3] = address as u8;
+                }
+            }
+        }
+        Ok(self.rom)
+    }
+}
@@ -0,0 +1,243 @@
+use std::time::{Duration, Instant};
+
+///How long a message stays up, including the fade
+pub const MESSAGE_TIMEOUT: Duration = Duration::from_secs(3);
+const FADE: Duration = Duration::from_secs(1);
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const TEXT_COLOR: u32 = 0xFFFFFF;
+const BACKGROUND_COLOR: u32 = 0x000000;
+const BACKGROUND_ALPHA: f32 = 0.6;
+
+/// 5x7 glyphs for ASCII 0x20 (space) to 0x5F (underscore), one byte per
+/// row with the leftmost pixel in bit 4. Lowercase is drawn as uppercase.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_HEIGHT]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x17, 0x15, 0x17, 0x10, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // backslash
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+];
+
+fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    match c {
+        ' '..='_' => &FONT[c as usize - 0x20],
+        _ => &FONT['?' as usize - 0x20],
+    }
+}
+
+/// Text drawn over the scaled output, never over the emulated display.
+///
+/// Status lines are replaced every frame by the frontend and sit in the top
+/// left corner. Messages stack up in the bottom left and fade out after
+/// `MESSAGE_TIMEOUT`.
+pub struct Osd {
+    enabled: bool,
+    scale: usize,
+    status: Vec<String>,
+    messages: Vec<(String, Instant)>,
+}
+
+impl Osd {
+    ///`scale` is the size of one font pixel in output pixels
+    pub fn new(scale: usize) -> Self {
+        Osd {
+            enabled: true,
+            scale: scale.max(1),
+            status: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn set_status(&mut self, lines: Vec<String>) {
+        self.status = lines;
+    }
+
+    pub fn message(&mut self, text: impl Into<String>, now: Instant) {
+        self.messages.push((text.into(), now));
+    }
+
+    ///Messages still on screen at `now`
+    pub fn messages(&self, now: Instant) -> impl Iterator<Item = &str> {
+        self.messages
+            .iter()
+            .filter(move |(_, shown)| now.saturating_duration_since(*shown) < MESSAGE_TIMEOUT)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// Composites the OSD onto `canvas`. Expired messages are dropped even
+    /// while the OSD is hidden.
+    pub fn draw(&mut self, canvas: &mut Canvas, now: Instant) {
+        self.messages
+            .retain(|(_, shown)| now.saturating_duration_since(*shown) < MESSAGE_TIMEOUT);
+        if !self.enabled {
+            return;
+        }
+
+        let line_height = (GLYPH_HEIGHT + 3) * self.scale;
+        let margin = 2 * self.scale;
+        for (i, line) in self.status.iter().enumerate() {
+            let y = margin + i * line_height;
+            self.draw_line(canvas, (margin, y), line, 1.0);
+        }
+
+        let count = self.messages.len();
+        for (i, (text, shown)) in self.messages.iter().enumerate() {
+            let age = now.saturating_duration_since(*shown);
+            let left = MESSAGE_TIMEOUT.saturating_sub(age);
+            let alpha = (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0);
+            let y = canvas
+                .height
+                .saturating_sub(margin + (count - i) * line_height);
+            self.draw_line(canvas, (margin, y), text, alpha);
+        }
+    }
+
+    ///One line of text on a translucent box
+    fn draw_line(&self, canvas: &mut Canvas, (x, y): (usize, usize), text: &str, alpha: f32) {
+        let pad = self.scale;
+        let text_width = text.chars().count() * (GLYPH_WIDTH + 1) * self.scale;
+        canvas.fill_rect(
+            (x.saturating_sub(pad), y.saturating_sub(pad)),
+            (text_width + pad, GLYPH_HEIGHT * self.scale + 2 * pad),
+            BACKGROUND_COLOR,
+            BACKGROUND_ALPHA * alpha,
+        );
+        canvas.draw_text((x, y), text, self.scale, TEXT_COLOR, alpha);
+    }
+}
+
+///A `width` x `height` 0RGB image to draw on, anything outside is clipped
+pub struct Canvas<'a> {
+    pub pixels: &'a mut [u32],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(pixels: &'a mut [u32], width: usize, height: usize) -> Self {
+        Canvas {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    /// Draws `text` with its top left corner at `x`, `y`. Each font pixel
+    /// becomes a `scale` x `scale` block.
+    pub fn draw_text(
+        &mut self,
+        (x, y): (usize, usize),
+        text: &str,
+        scale: usize,
+        color: u32,
+        alpha: f32,
+    ) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> col) != 0 {
+                        let corner = (left + col * scale, y + row * scale);
+                        self.fill_rect(corner, (scale, scale), color, alpha);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn fill_rect(
+        &mut self,
+        (x, y): (usize, usize),
+        (w, h): (usize, usize),
+        color: u32,
+        alpha: f32,
+    ) {
+        for row in y..(y + h).min(self.height) {
+            for col in x..(x + w).min(self.width) {
+                let pixel = &mut self.pixels[row * self.width + col];
+                *pixel = blend(*pixel, color, alpha);
+            }
+        }
+    }
+}
+
+fn blend(under: u32, over: u32, alpha: f32) -> u32 {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let channel = |shift: u32| {
+        let a = ((under >> shift) & 0xFF) as f32;
+        let b = ((over >> shift) & 0xFF) as f32;
+        ((a + (b - a) * alpha).round() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
@@ -0,0 +1,287 @@
+//! Counts where instructions go: per address, per opcode class and per
+//! subroutine, the latter by following `2NNN` calls and `00EE` returns.
+//! Produces a text report, folded stacks for flame graph tools and a map of
+//! which ROM bytes ran, were only read as data, or were never touched.
+
+use crate::chip::Chip8;
+use crate::debugger::access;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+///How many rows each table in the report gets
+const REPORT_ROWS: usize = 20;
+///ROM bytes per line of the coverage map
+const MAP_WIDTH: usize = 32;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    ///Instructions executed at each address
+    counts: Vec<u64>,
+    ///Whether each byte ran or was read, `EXECUTED | READ`
+    touched: Vec<u8>,
+    classes: BTreeMap<&'static str, u64>,
+    ///Entry points of the subroutines being run, innermost last
+    calls: Vec<u16>,
+    ///Instructions executed under each call stack
+    stacks: HashMap<Vec<u16>, u64>,
+    ///Times each subroutine was called
+    entries: BTreeMap<u16, u64>,
+    rom_len: usize,
+    total: u64,
+}
+
+///Cycles spent in a subroutine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+    ///Instructions in the subroutine itself
+    pub own: u64,
+    ///Including everything it called
+    pub total: u64,
+}
+
+impl Profiler {
+    ///`rom_len` bytes from 0x200 are covered by the coverage map
+    pub fn new(rom_len: usize) -> Self {
+        Profiler {
+            counts: vec![0; 4096],
+            touched: vec![0; 4096],
+            classes: BTreeMap::new(),
+            calls: Vec::new(),
+            stacks: HashMap::new(),
+            entries: BTreeMap::new(),
+            rom_len,
+            total: 0,
+        }
+    }
+
+    ///`Chip8::run_frame`, counting every instruction
+    pub fn run_frame(&mut self, chip: &mut Chip8, keys: [u8; 16]) {
+        chip.get_input(keys);
+        for _ in 0..chip.tick_rate() {
+            self.step(chip);
+        }
+        chip.tick_timers();
+    }
+
+    ///Counts the instruction under the PC, then runs it
+    pub fn step(&mut self, chip: &mut Chip8) {
+        let pc = chip.program_counter() as usize % 4096;
+        // An instruction at 0xFFF takes its second byte from 0x000
+        let next = (pc + 1) % 4096;
+        let memory = chip.memory();
+        let opcode = u16::from_be_bytes([memory[pc], memory[next]]);
+        let access = access(chip);
+
+        self.total += 1;
+        self.counts[pc] += 1;
+        self.touched[pc] |= EXECUTED;
+        self.touched[next] |= EXECUTED;
+        if let Some((address, len)) = access.memory_read {
+            for a in address..address.saturating_add(len) {
+                if let Some(t) = self.touched.get_mut(a as usize) {
+                    *t |= READ;
+                }
+            }
+        }
+        *self.classes.entry(class(opcode)).or_default() += 1;
+        *self.stacks.entry(self.calls.clone()).or_default() += 1;
+
+        let depth = chip.stack().len();
+        chip.cycle();
+        let after = chip.stack().len();
+
+        // Only count calls the machine took; a full stack drops them
+        if opcode & 0xF000 == 0x2000 && after > depth {
+            let target = opcode & 0x0FFF;
+            self.calls.push(target);
+            *self.entries.entry(target).or_default() += 1;
+        }
+        // Returns, and subroutines that never return, follow the real stack
+        self.calls.truncate(after);
+    }
+
+    ///Instructions counted so far
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn count(&self, address: u16) -> u64 {
+        self.counts.get(address as usize).copied().unwrap_or(0)
+    }
+
+    ///Instructions per class, such as `DXYN` or `8XY4`
+    pub fn classes(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.classes.iter().map(|(class, n)| (*class, *n))
+    }
+
+    ///Per subroutine entry point
+    pub fn subroutines(&self) -> BTreeMap<u16, Subroutine> {
+        let mut subroutines = BTreeMap::new();
+        for (address, calls) in &self.entries {
+            let calls = *calls;
+            let subroutine = Subroutine {
+                calls,
+                ..Subroutine::default()
+            };
+            subroutines.insert(*address, subroutine);
+        }
+        for (stack, n) in &self.stacks {
+            if let Some(innermost) = stack.last() {
+                subroutines.entry(*innermost).or_default().own += n;
+            }
+            // Recursive calls only count once towards the total
+            let mut seen: Vec<u16> = Vec::new();
+            for frame in stack {
+                if !seen.contains(frame) {
+                    seen.push(*frame);
+                    subroutines.entry(*frame).or_default().total += n;
+                }
+            }
+        }
+        subroutines
+    }
+
+    /// One line per call stack, `main;sub_2a0;sub_300 1234`, as flamegraph.pl
+    /// and inferno expect
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, n)| {
+                let mut line = String::from("main");
+                for frame in stack {
+                    write!(line, ";sub_{frame:03x}").unwrap();
+                }
+                format!("{line} {n}")
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// A row of characters for every 32 ROM bytes: `#` ran as code, `r` was
+    /// only read as data, `.` was never touched
+    pub fn coverage(&self) -> String {
+        let rom = &self.touched[0x200..(0x200 + self.rom_len).min(4096)];
+        let executed = rom.iter().filter(|t| **t & EXECUTED != 0).count();
+        let read = rom.iter().filter(|t| **t == READ).count();
+        let mut out = format!(
+            "ROM coverage: {executed} of {} bytes executed, {read} read as data, {} untouched\n",
+            rom.len(),
+            rom.len() - executed - read
+        );
+        for (row, bytes) in rom.chunks(MAP_WIDTH).enumerate() {
+            write!(out, "{:#05x} ", 0x200 + row * MAP_WIDTH).unwrap();
+            for touched in bytes {
+                out.push(match *touched {
+                    0 => '.',
+                    READ => 'r',
+                    _ => '#',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    ///Busiest addresses, opcode classes and subroutines, busiest first
+    pub fn report(&self, chip: &Chip8) -> String {
+        let percent = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+        let mut out = format!("{} instructions\n", self.total);
+
+        out.push_str("\nHotspots\n  address        count       %  opcode\n");
+        let mut hot: Vec<(usize, u64)> = self
+            .counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, n)| *n > 0)
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (address, n) in hot.into_iter().take(REPORT_ROWS) {
+            let memory = chip.memory();
+            let opcode = u16::from_be_bytes([memory[address], memory[(address + 1) % 4096]]);
+            writeln!(
+                out,
+                "  {address:#05x}   {n:>12} {:>6.2}%  {opcode:04X}",
+                percent(n)
+            )
+            .unwrap();
+        }
+
+        out.push_str("\nOpcode classes\n  class          count       %\n");
+        let mut classes: Vec<(&str, u64)> = self.classes().collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (class, n) in classes {
+            writeln!(out, "  {class:<5} {n:>15} {:>6.2}%", percent(n)).unwrap();
+        }
+
+        out.push_str(
+            "\nSubroutines\n  address    calls          own       %        total       %\n",
+        );
+        let mut subroutines: Vec<(u16, Subroutine)> = self.subroutines().into_iter().collect();
+        subroutines.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(&b.0)));
+        for (address, s) in subroutines.into_iter().take(REPORT_ROWS) {
+            writeln!(
+                out,
+                "  {address:#05x} {:>10} {:>12} {:>6.2}% {:>12} {:>6.2}%",
+                s.calls,
+                s.own,
+                percent(s.own),
+                s.total,
+                percent(s.total)
+            )
+            .unwrap();
+        }
+
+        out.push('\n');
+        out.push_str(self.coverage().lines().next().unwrap_or_default());
+        out.push('\n');
+        out
+    }
+}
+
+///The opcode's pattern, `DXYN`, `FX33` and so on, or `????` if it's not one
+pub fn class(opcode: u16) -> &'static str {
+    match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+        (0x0, _, _) if opcode == 0x00E0 => "00E0",
+        (0x0, _, _) if opcode == 0x00EE => "00EE",
+        (0x1, _, _) => "1NNN",
+        (0x2, _, _) => "2NNN",
+        (0x3, _, _) => "3XNN",
+        (0x4, _, _) => "4XNN",
+        (0x5, 0x0, _) => "5XY0",
+        (0x6, _, _) => "6XNN",
+        (0x7, _, _) => "7XNN",
+        (0x8, 0x0, _) => "8XY0",
+        (0x8, 0x1, _) => "8XY1",
+        (0x8, 0x2, _) => "8XY2",
+        (0x8, 0x3, _) => "8XY3",
+        (0x8, 0x4, _) => "8XY4",
+        (0x8, 0x5, _) => "8XY5",
+        (0x8, 0x6, _) => "8XY6",
+        (0x8, 0x7, _) => "8XY7",
+        (0x8, 0xE, _) => "8XYE",
+        (0x9, 0x0, _) => "9XY0",
+        (0xA, _, _) => "ANNN",
+        (0xB, _, _) => "BNNN",
+        (0xC, _, _) => "CXNN",
+        (0xD, _, _) => "DXYN",
+        (0xE, _, 0x9E) => "EX9E",
+        (0xE, _, 0xA1) => "EXA1",
+        (0xF, _, 0x07) => "FX07",
+        (0xF, _, 0x0A) => "FX0A",
+        (0xF, _, 0x15) => "FX15",
+        (0xF, _, 0x18) => "FX18",
+        (0xF, _, 0x1E) => "FX1E",
+        (0xF, _, 0x29) => "FX29",
+        (0xF, _, 0x33) => "FX33",
+        (0xF, _, 0x55) => "FX55",
+        (0xF, _, 0x65) => "FX65",
+        _ => "????",
+    }
+}
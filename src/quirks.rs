@@ -0,0 +1,159 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use core::str::FromStr;
+
+///Behaviours that differ between CHIP-8 interpreters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    ///8XY6/8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    ///FX55/FX65 leave I pointing one past the last register touched
+    pub load_store_increments_i: bool,
+    ///BNNN adds VX (X taken from the opcode) instead of V0
+    pub jump_uses_vx: bool,
+    ///8XY1/8XY2/8XY3 clear VF
+    pub vf_reset: bool,
+    ///Sprites are cut off at the screen edge instead of wrapping around
+    pub clip_sprites: bool,
+}
+
+const NAMES: [&str; 5] = [
+    "shift_uses_vy",
+    "load_store_increments_i",
+    "jump_uses_vx",
+    "vf_reset",
+    "clip_sprites",
+];
+
+impl Quirks {
+    ///COSMAC VIP behaviour
+    pub fn chip8() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+        }
+    }
+
+    ///SUPER-CHIP 1.1 behaviour
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Quirks::default()),
+            "chip8" => Some(Quirks::chip8()),
+            "schip" => Some(Quirks::schip()),
+            _ => None,
+        }
+    }
+
+    ///Name of the preset these quirks match, if any
+    pub fn preset_name(&self) -> Option<&'static str> {
+        ["default", "chip8", "schip"]
+            .into_iter()
+            .find(|name| Quirks::preset(name).as_ref() == Some(self))
+    }
+
+    ///Packs the flags into a byte, bit N being the Nth field
+    pub fn to_bits(&self) -> u8 {
+        self.flags()
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, flag)| bits | (*flag as u8) << i)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let mut quirks = Quirks::default();
+        for (i, flag) in quirks.flags_mut().into_iter().enumerate() {
+            *flag = bits & (1 << i) != 0;
+        }
+        quirks
+    }
+
+    fn flags(&self) -> [bool; 5] {
+        [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.jump_uses_vx,
+            self.vf_reset,
+            self.clip_sprites,
+        ]
+    }
+
+    fn flags_mut(&mut self) -> [&mut bool; 5] {
+        [
+            &mut self.shift_uses_vy,
+            &mut self.load_store_increments_i,
+            &mut self.jump_uses_vx,
+            &mut self.vf_reset,
+            &mut self.clip_sprites,
+        ]
+    }
+}
+
+/// The interpreter's original behaviour: shifts work on VX in place, I is
+/// left alone by FX55/FX65 and BNNN uses V0.
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: true,
+        }
+    }
+}
+
+/// Written as `name=0|1` pairs separated by spaces, e.g.
+/// `shift_uses_vy=1 load_store_increments_i=0 ...`.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in NAMES.iter().zip(self.flags()).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{name}={}", value as u8)?;
+        }
+        Ok(())
+    }
+}
+
+/// Accepts a preset name or the `Display` form. Flags left out keep their
+/// default value.
+#[cfg(feature = "std")]
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(preset) = Quirks::preset(s.trim()) {
+            return Ok(preset);
+        }
+        let mut quirks = Quirks::default();
+        for pair in s.split_whitespace() {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid quirk: {pair}"))?;
+            let index = NAMES
+                .iter()
+                .position(|known| *known == name)
+                .ok_or_else(|| format!("Unknown quirk: {name}"))?;
+            *quirks.flags_mut()[index] = match value {
+                "0" | "false" => false,
+                "1" | "true" => true,
+                _ => return Err(format!("Invalid value for {name}: {value}")),
+            };
+        }
+        Ok(quirks)
+    }
+}
@@ -0,0 +1,439 @@
+//! Remote control over a local socket for test automation.
+//!
+//! Clients send one JSON object per line and get one back per request, e.g.
+//! `{"cmd": "step", "frames": 10}` answered by `{"ok": true, "frames": 10}`.
+//! Failures come back as `{"ok": false, "error": "..."}`. Byte strings such
+//! as memory dumps and save states travel as hex.
+
+use crate::chip::Chip8;
+use crate::hex;
+use crate::png;
+use crate::rng::Rng;
+use crate::video::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+///Most frames one `step` runs, a minute of emulated time, so a single
+///request can't hold the server for long
+const MAX_STEP_FRAMES: u64 = 60 * 60;
+///Longest request line kept; a client that sends more without a newline is
+///dropped. The biggest real request, a hex save state, is far shorter
+const MAX_PENDING: usize = 0x10000;
+
+///Where the server listens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// `tcp:127.0.0.1:4000`, `unix:/tmp/chip8.sock`, or a bare `host:port`
+/// which is taken as TCP.
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Address::Unix(PathBuf::from(path)));
+        }
+        let tcp = s.strip_prefix("tcp:").unwrap_or(s);
+        tcp.parse()
+            .map(Address::Tcp)
+            .map_err(|_| format!("Invalid remote address: {s}"))
+    }
+}
+
+/// Protocol state shared by every connection: the keys the remote is
+/// holding down and whether it asked to quit.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    keys: [u8; 16],
+    quit: bool,
+}
+
+impl Session {
+    ///Keys currently pressed by the remote, to merge with local input
+    pub fn keys(&self) -> [u8; 16] {
+        self.keys
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// Runs one request line against `chip` and returns the response line,
+    /// without the newline. `step` runs a single frame, so the frontend can
+    /// route remote frames through its movie recording.
+    pub fn handle(
+        &mut self,
+        line: &str,
+        chip: &mut Chip8,
+        step: &mut impl FnMut(&mut Chip8, [u8; 16]),
+    ) -> String {
+        let response = serde_json::from_str::<Value>(line)
+            .map_err(|e| format!("invalid JSON: {e}"))
+            .and_then(|request| self.execute(&request, chip, step));
+        let value = match response {
+            Ok(mut fields) => {
+                fields.insert("ok".into(), true.into());
+                Value::Object(fields)
+            }
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+        value.to_string()
+    }
+
+    fn execute(
+        &mut self,
+        request: &Value,
+        chip: &mut Chip8,
+        step: &mut impl FnMut(&mut Chip8, [u8; 16]),
+    ) -> Result<Map<String, Value>, String> {
+        let command = request
+            .get("cmd")
+            .and_then(Value::as_str)
+            .ok_or("missing \"cmd\"")?;
+        let mut reply = Map::new();
+        match command {
+            "load_rom" => {
+                let rom = bytes_argument(request, "data")?;
+                // Keep the settings, start over with the same seed
+                let mut fresh = Chip8::with_rng(Rng::new(chip.rng().seed(), chip.rng().mode()));
+                fresh.set_quirks(chip.quirks());
+                fresh.set_tick_rate(chip.tick_rate());
+                fresh.load_rom_bytes(&rom).map_err(|e| e.to_string())?;
+                fresh.set_engine(chip.engine());
+                *chip = fresh;
+                self.keys = [0; 16];
+                reply.insert("size".into(), rom.len().into());
+            }
+            "press" | "release" => {
+                let key = number(request, "key", 0xF)? as usize;
+                self.keys[key] = (command == "press") as u8;
+            }
+            "release_all" => self.keys = [0; 16],
+            "step" => {
+                let frames = match request.get("frames") {
+                    Some(_) => number(request, "frames", u64::MAX)?.min(MAX_STEP_FRAMES),
+                    None => 1,
+                };
+                for _ in 0..frames {
+                    step(chip, self.keys);
+                }
+                reply.insert("frames".into(), frames.into());
+            }
+            "read_memory" => {
+                let address = number(request, "address", 0xFFF)? as usize;
+                let length = match request.get("length") {
+                    Some(_) => number(request, "length", 0x1000)? as usize,
+                    None => 1,
+                };
+                let end = (address + length).min(chip.memory().len());
+                reply.insert(
+                    "data".into(),
+                    hex::encode(&chip.memory()[address..end]).into(),
+                );
+            }
+            "write_memory" => {
+                let address = number(request, "address", 0xFFF)? as usize;
+                let data = decode_hex(string(request, "data")?)?;
+                if address + data.len() > chip.memory().len() {
+                    return Err("write runs past the end of memory".into());
+                }
+                chip.write_memory(address as u16, &data);
+            }
+            "read_registers" => {
+                reply.insert("v".into(), chip.registers().to_vec().into());
+                reply.insert("i".into(), chip.index_register().into());
+                reply.insert("pc".into(), chip.program_counter().into());
+                reply.insert("sp".into(), chip.stack().len().into());
+                reply.insert("stack".into(), chip.stack().to_vec().into());
+                reply.insert("dt".into(), chip.delay_timer().into());
+                reply.insert("st".into(), chip.sound_timer().into());
+            }
+            "write_register" => {
+                let name = string(request, "name")?.to_ascii_lowercase();
+                match name.as_str() {
+                    "i" => chip.set_index_register(number(request, "value", 0xFFF)? as u16),
+                    "pc" => chip.set_program_counter(number(request, "value", 0xFFF)? as u16),
+                    "dt" => chip.set_delay_timer(number(request, "value", 0xFF)? as u8),
+                    "st" => chip.set_sound_timer(number(request, "value", 0xFF)? as u8),
+                    _ => {
+                        let x = name
+                            .strip_prefix('v')
+                            .filter(|x| x.len() == 1)
+                            .and_then(|x| usize::from_str_radix(x, 16).ok())
+                            .ok_or(format!("unknown register: {name}"))?;
+                        chip.set_register(x, number(request, "value", 0xFF)? as u8);
+                    }
+                }
+            }
+            "screenshot" => {
+                let rows: Vec<Value> = chip
+                    .display()
+                    .chunks(DISPLAY_WIDTH)
+                    .map(|row| {
+                        row.iter()
+                            .map(|p| if *p != 0 { '#' } else { '.' })
+                            .collect::<String>()
+                            .into()
+                    })
+                    .collect();
+                if let Some(path) = request.get("path") {
+                    let path = path.as_str().ok_or("\"path\" must be a string")?;
+                    let image = png::encode(chip.display(), DISPLAY_WIDTH, DISPLAY_HEIGHT);
+                    fs::write(path, image).map_err(|e| format!("{path}: {e}"))?;
+                }
+                reply.insert("width".into(), DISPLAY_WIDTH.into());
+                reply.insert("height".into(), DISPLAY_HEIGHT.into());
+                reply.insert("rows".into(), rows.into());
+            }
+            "save_state" => {
+                let state = chip.save_state();
+                match request.get("path") {
+                    Some(path) => {
+                        let path = path.as_str().ok_or("\"path\" must be a string")?;
+                        fs::write(path, state).map_err(|e| format!("{path}: {e}"))?;
+                    }
+                    None => {
+                        reply.insert("state".into(), hex::encode(&state).into());
+                    }
+                }
+            }
+            "load_state" => {
+                let state = bytes_argument(request, "state")?;
+                chip.load_state(&state).map_err(|e| e.to_string())?;
+            }
+            "quit" => self.quit = true,
+            _ => return Err(format!("unknown command: {command}")),
+        }
+        Ok(reply)
+    }
+}
+
+fn string<'a>(request: &'a Value, name: &str) -> Result<&'a str, String> {
+    request
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or(format!("missing string \"{name}\""))
+}
+
+fn decode_hex(data: &str) -> Result<Vec<u8>, String> {
+    hex::decode(data).ok_or_else(|| "invalid hex string".to_string())
+}
+
+fn number(request: &Value, name: &str, max: u64) -> Result<u64, String> {
+    let value = request
+        .get(name)
+        .and_then(Value::as_u64)
+        .ok_or(format!("missing number \"{name}\""))?;
+    if value > max {
+        return Err(format!("\"{name}\" must be at most {max}"));
+    }
+    Ok(value)
+}
+
+///Bytes given inline as hex under `name`, or read from the file at `path`
+fn bytes_argument(request: &Value, name: &str) -> Result<Vec<u8>, String> {
+    if let Some(path) = request.get("path").and_then(Value::as_str) {
+        return fs::read(path).map_err(|e| format!("{path}: {e}"));
+    }
+    match request.get(name) {
+        Some(_) => decode_hex(string(request, name)?),
+        None => Err(format!("needs \"{name}\" or \"path\"")),
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+struct Client {
+    stream: Stream,
+    pending: Vec<u8>,
+}
+
+/// Non-blocking server the frontends poll once per loop. Any number of
+/// clients may connect; their requests are handled in arrival order.
+pub struct RemoteServer {
+    listener: Listener,
+    clients: Vec<Client>,
+    session: Session,
+}
+
+impl RemoteServer {
+    pub fn bind(address: &Address) -> io::Result<Self> {
+        let listener = match address {
+            Address::Tcp(address) => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                Listener::Tcp(listener)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                // A stale socket from a previous run would make bind fail,
+                // anything else there is left for bind to refuse
+                if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                    let _ = fs::remove_file(path);
+                }
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                Listener::Unix(listener, path.clone())
+            }
+        };
+        Ok(RemoteServer {
+            listener,
+            clients: Vec::new(),
+            session: Session::default(),
+        })
+    }
+
+    ///The bound TCP address, handy after binding port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.listener {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            #[cfg(unix)]
+            Listener::Unix(..) => None,
+        }
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Accepts new clients and answers every complete request line that
+    /// has arrived. Never blocks waiting for input.
+    pub fn poll(
+        &mut self,
+        chip: &mut Chip8,
+        mut step: impl FnMut(&mut Chip8, [u8; 16]),
+    ) -> io::Result<()> {
+        while let Some(stream) = self.accept()? {
+            stream.set_nonblocking(true)?;
+            self.clients.push(Client {
+                stream,
+                pending: Vec::new(),
+            });
+        }
+
+        let session = &mut self.session;
+        self.clients.retain_mut(|client| {
+            let mut buffer = [0u8; 4096];
+            let open = loop {
+                match client.stream.read(&mut buffer) {
+                    Ok(0) => break false,
+                    Ok(n) => {
+                        client.pending.extend_from_slice(&buffer[..n]);
+                        // Leave the rest in the socket until this much is handled
+                        if client.pending.len() > MAX_PENDING {
+                            break true;
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break true,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break false,
+                }
+            };
+            while let Some(end) = client.pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = client.pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let mut response = session.handle(line.trim(), chip, &mut step);
+                response.push('\n');
+                // Replies can be bigger than the socket buffer, so block for them
+                let sent = client.stream.set_nonblocking(false).is_ok()
+                    && client.stream.write_all(response.as_bytes()).is_ok()
+                    && client.stream.set_nonblocking(true).is_ok();
+                if !sent {
+                    return false;
+                }
+            }
+            if client.pending.len() > MAX_PENDING {
+                return false;
+            }
+            open
+        });
+        Ok(())
+    }
+
+    fn accept(&self) -> io::Result<Option<Stream>> {
+        let accepted = match &self.listener {
+            Listener::Tcp(listener) => listener.accept().map(|(s, _)| Stream::Tcp(s)),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().map(|(s, _)| Stream::Unix(s)),
+        };
+        match accepted {
+            Ok(stream) => Ok(Some(stream)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+/// Where `CXNN` gets its random bytes. Boards with a hardware RNG can
+/// implement this instead of using `Rng`.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+}
+
+/// The COSMAC VIP's CHIP-8 interpreter from 0x100 to 0x1FF. Its RND routine
+/// runs from this page and reads it as a table, so VIP mode needs the bytes
+/// rather than whatever a ROM keeps at the same addresses.
+const INTERPRETER_PAGE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+    0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+    0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+    0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+    0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+    0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+    0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+    0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+    0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+    0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+    0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+    0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+    0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+    0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x00, 0x4B,
+];
+
+///Which generator feeds `CXNN`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RngMode {
+    ///xorshift64* seeded through splitmix64
+    Xorshift,
+    ///Mimics the COSMAC VIP interpreter's RND routine
+    Vip,
+}
+
+impl RngMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RngMode::Xorshift => "xorshift",
+            RngMode::Vip => "vip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xorshift" => Some(RngMode::Xorshift),
+            "vip" => Some(RngMode::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// Seedable random source owned by the `Chip8`.
+///
+/// Everything the generator needs lives in `state`, so cloning it (or the
+/// machine that owns it) captures the random sequence exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    mode: RngMode,
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64, mode: RngMode) -> Self {
+        let state = match mode {
+            RngMode::Xorshift => splitmix64(seed),
+            // The VIP kept its seed in the 16 bit register R9.
+            RngMode::Vip => seed & 0xFFFF,
+        };
+        Rng { mode, seed, state }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn mode(&self) -> RngMode {
+        self.mode
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    ///Rebuilds a generator from a previously captured `state()`
+    pub fn from_parts(seed: u64, mode: RngMode, state: u64) -> Self {
+        Rng { mode, seed, state }
+    }
+}
+
+impl RandomSource for Rng {
+    fn next_byte(&mut self) -> u8 {
+        match self.mode {
+            RngMode::Xorshift => {
+                let mut x = self.state;
+                x ^= x >> 12;
+                x ^= x << 25;
+                x ^= x >> 27;
+                self.state = x;
+                (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            RngMode::Vip => {
+                // The routine at 0x1D9: R9 is bumped, the interpreter byte at
+                // 0x100 + R9.0 is added to R9.1, and the sum plus itself
+                // shifted right through the carry becomes R9.1 and the result.
+                let [low, high] = (self.state as u16).wrapping_add(1).to_le_bytes();
+                let (sum, carry) = INTERPRETER_PAGE[low as usize].overflowing_add(high);
+                let result = sum.wrapping_add(sum >> 1 | (carry as u8) << 7);
+                self.state = u16::from_le_bytes([low, result]) as u64;
+                result
+            }
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0, RngMode::Xorshift)
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z = z ^ (z >> 31);
+    // xorshift gets stuck on zero
+    if z == 0 {
+        0x1
+    } else {
+        z
+    }
+}
@@ -0,0 +1,315 @@
+//! ROM metadata in the layout of the community chip-8-database
+//! (<https://github.com/chip-8/chip-8-database>): `programs.json` lists
+//! programs and the ROMs of each keyed by SHA-1, `sha1-hashes.json` maps a
+//! SHA-1 to its program's index, and `platforms.json` gives each platform's
+//! quirks and tick rate.
+//!
+//! A small database in that layout, covering the programs in `roms/`, is
+//! compiled in. [`Database::load`] reads a checkout of the full one.
+
+use crate::chip::Chip8;
+use crate::movie::sha1_hex;
+use crate::quirks::Quirks;
+use crate::rng::RandomSource;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const PROGRAMS: &str = include_str!("../db/programs.json");
+const HASHES: &str = include_str!("../db/sha1-hashes.json");
+const PLATFORMS: &str = include_str!("../db/platforms.json");
+
+///What the database knows about one ROM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub description: Option<String>,
+    ///Platform ids, the one it's best run as first
+    pub platforms: Vec<String>,
+    ///The first platform's, with this ROM's differences applied
+    pub quirks: Option<Quirks>,
+    pub tick_rate: Option<u32>,
+    ///Unlit and lit pixels, as 0xRRGGBB
+    pub colors: Option<[u32; 2]>,
+    ///Controls (`up`, `a`, ...) and the CHIP-8 key each one presses
+    pub keys: Vec<(String, u8)>,
+}
+
+impl RomInfo {
+    ///Sets the quirks and tick rate the ROM wants, where it says
+    pub fn apply<R: RandomSource>(&self, chip: &mut Chip8<R>) {
+        if let Some(quirks) = self.quirks {
+            chip.set_quirks(quirks);
+        }
+        if let Some(tick_rate) = self.tick_rate {
+            chip.set_tick_rate(tick_rate);
+        }
+    }
+}
+
+/// One `Field: value` line per thing known, e.g.
+///
+/// ```text
+/// Title: Bounce
+/// Authors: chip-8mulator
+/// Platform: modernChip8
+/// Quirks: shift_uses_vy=0 ...
+/// ```
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Title: {}", self.title)?;
+        if !self.authors.is_empty() {
+            writeln!(f, "Authors: {}", self.authors.join(", "))?;
+        }
+        if let Some(release) = &self.release {
+            writeln!(f, "Release: {release}")?;
+        }
+        if let Some(description) = &self.description {
+            writeln!(f, "Description: {description}")?;
+        }
+        if !self.platforms.is_empty() {
+            writeln!(f, "Platform: {}", self.platforms.join(", "))?;
+        }
+        if let Some(quirks) = &self.quirks {
+            writeln!(f, "Quirks: {quirks}")?;
+        }
+        if let Some(tick_rate) = self.tick_rate {
+            writeln!(f, "Tick rate: {tick_rate}")?;
+        }
+        if let Some([off, on]) = self.colors {
+            writeln!(f, "Colors: #{off:06x} #{on:06x}")?;
+        }
+        if !self.keys.is_empty() {
+            let keys: Vec<String> = self
+                .keys
+                .iter()
+                .map(|(control, key)| format!("{control}={key:X}"))
+                .collect();
+            writeln!(f, "Keys: {}", keys.join(" "))?;
+        }
+        write!(f, "SHA-1: {}", self.sha1)
+    }
+}
+
+///A platform from `platforms.json`
+struct Platform {
+    quirks: Map<String, Value>,
+    tick_rate: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn json(name: &str, text: &str) -> io::Result<Value> {
+    serde_json::from_str(text).map_err(|e| invalid(format!("{name}: {e}")))
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn tick_rate(value: &Value, key: &str) -> Option<u32> {
+    value
+        .get(key)
+        .and_then(Value::as_u64)
+        .and_then(|rate| rate.try_into().ok())
+}
+
+/// The database's quirk flags say what a platform does differently from
+/// the COSMAC VIP where ours say what it does, so most are inverted.
+/// `memoryIncrementByX` (CHIP-48's I += X) has no quirk of its own and runs
+/// as the VIP's I += X + 1.
+fn quirks(flags: &Map<String, Value>) -> Quirks {
+    let flag = |name: &str| flags.get(name).and_then(Value::as_bool).unwrap_or(false);
+    Quirks {
+        shift_uses_vy: !flag("shift"),
+        load_store_increments_i: !flag("memoryLeaveIUnchanged"),
+        jump_uses_vx: flag("jump"),
+        vf_reset: flag("logic"),
+        clip_sprites: !flag("wrap"),
+    }
+}
+
+///`#RRGGBB` as 0xRRGGBB
+fn color(value: &Value) -> Option<u32> {
+    let hex = value.as_str()?.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+impl Database {
+    ///The database compiled into the crate, parsed the first time it's used
+    pub fn bundled() -> &'static Database {
+        static BUNDLED: OnceLock<Database> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            Database::parse(PROGRAMS, HASHES, PLATFORMS).expect("the bundled ROM database is valid")
+        })
+    }
+
+    ///Reads `programs.json`, `sha1-hashes.json` and `platforms.json` from `dir`
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let read = |name: &str| {
+            let path = dir.as_ref().join(name);
+            fs::read_to_string(&path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+        };
+        Database::parse(
+            &read("programs.json")?,
+            &read("sha1-hashes.json")?,
+            &read("platforms.json")?,
+        )
+    }
+
+    pub fn parse(programs: &str, hashes: &str, platforms: &str) -> io::Result<Self> {
+        let programs = json("programs.json", programs)?;
+        let programs = programs
+            .as_array()
+            .ok_or_else(|| invalid("programs.json: expected a list".to_string()))?;
+        let hashes = json("sha1-hashes.json", hashes)?;
+        let hashes = hashes
+            .as_object()
+            .ok_or_else(|| invalid("sha1-hashes.json: expected an object".to_string()))?;
+        let platforms = json("platforms.json", platforms)?;
+        let platforms: HashMap<&str, Platform> = platforms
+            .as_array()
+            .ok_or_else(|| invalid("platforms.json: expected a list".to_string()))?
+            .iter()
+            .filter_map(|platform| {
+                Some((
+                    platform.get("id")?.as_str()?,
+                    Platform {
+                        quirks: platform.get("quirks")?.as_object()?.clone(),
+                        tick_rate: tick_rate(platform, "defaultTickrate"),
+                    },
+                ))
+            })
+            .collect();
+
+        let mut roms = HashMap::new();
+        for (sha1, index) in hashes {
+            let program = index
+                .as_u64()
+                .and_then(|index| programs.get(index as usize))
+                .ok_or_else(|| invalid(format!("sha1-hashes.json: bad index for {sha1}")))?;
+            let rom = program
+                .get("roms")
+                .and_then(|roms| roms.get(sha1))
+                .ok_or_else(|| invalid(format!("programs.json: no ROM {sha1}")))?;
+            let sha1 = sha1.to_ascii_lowercase();
+            roms.insert(sha1.clone(), rom_info(sha1, program, rom, &platforms));
+        }
+        Ok(Database { roms })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.get(&sha1_hex(rom))
+    }
+
+    ///By SHA-1 as hex
+    pub fn get(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+fn rom_info(
+    sha1: String,
+    program: &Value,
+    rom: &Value,
+    platforms: &HashMap<&str, Platform>,
+) -> RomInfo {
+    let ids: Vec<String> = rom
+        .get("platforms")
+        .and_then(Value::as_array)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let platform = ids.first().and_then(|id| platforms.get(id.as_str()));
+
+    // A quirky platform entry only lists what this ROM needs changed
+    let quirky = ids.first().and_then(|id| {
+        rom.get("quirkyPlatforms")
+            .and_then(|quirky| quirky.get(id))
+            .and_then(Value::as_object)
+    });
+    let flags = match (platform, quirky) {
+        (None, None) => None,
+        (platform, quirky) => {
+            let mut flags = platform.map(|p| p.quirks.clone()).unwrap_or_default();
+            flags.extend(
+                quirky
+                    .into_iter()
+                    .flatten()
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+            Some(flags)
+        }
+    };
+
+    let colors = rom
+        .get("colors")
+        .and_then(|colors| colors.get("pixels"))
+        .and_then(Value::as_array)
+        .and_then(|pixels| Some([color(pixels.first()?)?, color(pixels.get(1)?)?]));
+    let keys = rom
+        .get("keys")
+        .and_then(Value::as_object)
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|(control, key)| {
+                    let key = key.as_u64().filter(|key| *key < 16)?;
+                    Some((control.clone(), key as u8))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    RomInfo {
+        sha1,
+        title: string(program, "title").unwrap_or_default(),
+        authors: program
+            .get("authors")
+            .and_then(Value::as_array)
+            .map(|authors| {
+                authors
+                    .iter()
+                    .filter_map(|author| author.as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        release: string(program, "release"),
+        description: string(rom, "description").or_else(|| string(program, "description")),
+        quirks: flags.as_ref().map(quirks),
+        tick_rate: tick_rate(rom, "tickrate").or_else(|| platform.and_then(|p| p.tick_rate)),
+        platforms: ids,
+        colors,
+        keys,
+    }
+}
@@ -0,0 +1,114 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+///Emulated frames per second, the rate the timers count down at
+pub const FRAME_RATE: u32 = 60;
+
+///Sleep this close to a deadline, then spin the rest of the way
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Paces emulation against the monotonic clock.
+///
+/// Frames are due on a fixed grid of deadlines `frame_duration` apart, so
+/// rounding never accumulates into drift. After a hitch the missed frames
+/// are run back to back, up to `max_catch_up`; anything beyond that is
+/// dropped and the grid restarts from now.
+#[derive(Clone, Debug)]
+pub struct Scheduler {
+    frame_duration: Duration,
+    next_frame: Instant,
+    max_catch_up: u32,
+    stats_start: Instant,
+    stats_frames: u32,
+    measured_fps: f64,
+}
+
+impl Scheduler {
+    pub fn new(frame_rate: u32, max_catch_up: u32) -> Self {
+        Scheduler::starting_at(Instant::now(), frame_rate, max_catch_up)
+    }
+
+    pub fn starting_at(now: Instant, frame_rate: u32, max_catch_up: u32) -> Self {
+        Scheduler {
+            frame_duration: Duration::from_secs(1) / frame_rate.max(1),
+            next_frame: now,
+            max_catch_up: max_catch_up.max(1),
+            stats_start: now,
+            stats_frames: 0,
+            measured_fps: 0.0,
+        }
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    pub fn target_fps(&self) -> f64 {
+        1.0 / self.frame_duration.as_secs_f64()
+    }
+
+    /// How many frames have come due by `now`. Moves the deadline past them,
+    /// so calling it again straight away returns 0.
+    pub fn due_frames(&mut self, now: Instant) -> u32 {
+        if now < self.next_frame {
+            return 0;
+        }
+        let behind = now - self.next_frame;
+        let due = (behind.as_nanos() / self.frame_duration.as_nanos()) as u32 + 1;
+        if due > self.max_catch_up {
+            self.next_frame = now + self.frame_duration;
+            return self.max_catch_up;
+        }
+        self.next_frame += self.frame_duration * due;
+        due
+    }
+
+    ///Forgets any backlog, e.g. after running uncapped or unpausing
+    pub fn resync(&mut self, now: Instant) {
+        self.next_frame = now;
+    }
+
+    ///Blocks until the next frame is due
+    pub fn wait(&self) {
+        loop {
+            let now = Instant::now();
+            if now >= self.next_frame {
+                return;
+            }
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_MARGIN {
+                thread::sleep(remaining - SPIN_MARGIN);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Counts emulated frames towards the speed measurement, which is
+    /// refreshed about once a second.
+    pub fn record_frames(&mut self, frames: u32, now: Instant) {
+        self.stats_frames += frames;
+        let elapsed = now.saturating_duration_since(self.stats_start);
+        if elapsed >= Duration::from_secs(1) {
+            self.measured_fps = self.stats_frames as f64 / elapsed.as_secs_f64();
+            self.stats_frames = 0;
+            self.stats_start = now;
+        }
+    }
+
+    ///Emulated frames per second over the last measurement window
+    pub fn measured_fps(&self) -> f64 {
+        self.measured_fps
+    }
+
+    ///Real speed relative to the target, 1.0 being full speed
+    pub fn speed(&self) -> f64 {
+        self.measured_fps / self.target_fps()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(FRAME_RATE, 6)
+    }
+}
@@ -0,0 +1,8 @@
+/// Output device for the 64x32 picture, such as a small OLED or LCD on an
+/// embedded board. `Chip8::present` only calls it when the picture changed.
+pub trait Screen {
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool);
+
+    ///Called once a whole frame has been set, for displays that buffer
+    fn flush(&mut self) {}
+}
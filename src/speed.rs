@@ -0,0 +1,103 @@
+/// Pause, frame advance, fast-forward and slow motion for a frontend.
+///
+/// The frontend calls `frames_to_run` once per host frame (60 Hz) and runs
+/// that many emulated frames. The timers tick once per emulated frame, so
+/// they speed up and slow down together with everything else.
+#[derive(Clone, Debug)]
+pub struct SpeedControl {
+    paused: bool,
+    advance: bool,
+    fast_forward: bool,
+    slow_motion: bool,
+    ///Emulated frames per host frame while fast-forwarding, 0 for uncapped
+    ff_multiplier: u32,
+    ///Host frames per emulated frame in slow motion
+    slow_divisor: u32,
+    slow_counter: u32,
+}
+
+impl SpeedControl {
+    pub fn new(ff_multiplier: u32, slow_divisor: u32) -> Self {
+        SpeedControl {
+            paused: false,
+            advance: false,
+            fast_forward: false,
+            slow_motion: false,
+            ff_multiplier,
+            slow_divisor: slow_divisor.max(1),
+            slow_counter: 0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    ///Runs exactly one frame on the next update, only while paused
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance = true;
+        }
+    }
+
+    ///Fast-forward is held, not toggled
+    pub fn set_fast_forward(&mut self, held: bool) {
+        self.fast_forward = held;
+    }
+
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+        self.slow_counter = 0;
+    }
+
+    ///True while fast-forwarding with no frame cap
+    pub fn uncapped(&self) -> bool {
+        !self.paused && self.fast_forward && self.ff_multiplier == 0
+    }
+
+    /// Emulated frames to run during this host frame. Meaningless while
+    /// `uncapped`, where the frontend runs as many as it can.
+    pub fn frames_to_run(&mut self) -> u32 {
+        if self.paused {
+            let frames = self.advance as u32;
+            self.advance = false;
+            return frames;
+        }
+        if self.fast_forward {
+            return self.ff_multiplier.max(1);
+        }
+        if self.slow_motion {
+            self.slow_counter += 1;
+            if self.slow_counter < self.slow_divisor {
+                return 0;
+            }
+            self.slow_counter = 0;
+        }
+        1
+    }
+
+    ///Short description for the window title
+    pub fn label(&self) -> String {
+        if self.paused {
+            "Paused".to_string()
+        } else if self.fast_forward && self.ff_multiplier == 0 {
+            "Fast-forward (uncapped)".to_string()
+        } else if self.fast_forward {
+            format!("Fast-forward x{}", self.ff_multiplier)
+        } else if self.slow_motion {
+            format!("Slow motion 1/{}", self.slow_divisor)
+        } else {
+            "Running".to_string()
+        }
+    }
+}
+
+impl Default for SpeedControl {
+    fn default() -> Self {
+        SpeedControl::new(4, 4)
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::{assemble, AsmError};
+
+    fn words(source: &str) -> Vec<u16> {
+        assemble(source)
+            .unwrap()
+            .chunks(2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn encodes_every_mnemonic() {
+        let source = "
+            cls
+            ret
+            jp 0x234
+            jp v0, 0x234
+            call 0x234
+            se v1, 0x22
+            sne v1, 0x22
+            se v1, v2
+            sne v1, v2
+            ld v1, 0x22
+            ld v1, v2
+            ld i, 0x234
+            ld v1, dt
+            ld v1, k
+            ld dt, v1
+            ld st, v1
+            ld f, v1
+            ld b, v1
+            ld [i], v1
+            ld v1, [i]
+            add v1, 0x22
+            add v1, v2
+            add i, v1
+            or v1, v2
+            and v1, v2
+            xor v1, v2
+            sub v1, v2
+            shr v1
+            shr v1, v2
+            subn v1, v2
+            shl v1
+            shl v1, v2
+            rnd v1, 0x22
+            drw v1, v2, 5
+            skp v1
+            sknp v1
+        ";
+        assert_eq!(
+            vec![
+                0x00E0, 0x00EE, 0x1234, 0xB234, 0x2234, 0x3122, 0x4122, 0x5120, 0x9120, 0x6122,
+                0x8120, 0xA234, 0xF107, 0xF10A, 0xF115, 0xF118, 0xF129, 0xF133, 0xF155, 0xF165,
+                0x7122, 0x8124, 0xF11E, 0x8121, 0x8122, 0x8123, 0x8125, 0x8116, 0x8126, 0x8127,
+                0x811E, 0x812E, 0xC122, 0xD125, 0xE19E, 0xE1A1,
+            ],
+            words(source)
+        );
+    }
+
+    #[test]
+    fn labels_data_and_numbers() {
+        let source = "
+            start: jp end  ; forward reference
+            sprite:
+                db 0b11110000, 0x90, 144
+                dw 0xABCD
+            END:
+            end: LD I, sprite
+                jp start
+        ";
+        assert_eq!(
+            vec![0x12, 0x07, 0xF0, 0x90, 0x90, 0xAB, 0xCD, 0xA2, 0x02, 0x12, 0x00],
+            assemble(source).unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_the_line() {
+        let error = |source: &str| assemble(source).unwrap_err();
+        assert_eq!(
+            AsmError {
+                line: 3,
+                message: "unknown label `nowhere`".to_string()
+            },
+            error("cls\n\njp nowhere")
+        );
+        assert_eq!(
+            "line 1: can't assemble `ld v1, v2, v3`",
+            error("ld v1, v2, v3").to_string()
+        );
+        assert_eq!(
+            "line 2: `a` is defined twice",
+            error("a:\na: cls").to_string()
+        );
+        assert_eq!(
+            "line 1: 0x100 doesn't fit in a byte",
+            error("ld v0, 256").to_string()
+        );
+        assert_eq!(
+            "line 1: address 0x1000 is out of range",
+            error("jp 0x1000").to_string()
+        );
+        assert_eq!(
+            "line 1: sprite height 16 is over 15",
+            error("drw v0, v1, 16").to_string()
+        );
+        assert_eq!("line 1: bad number `0xZZ`", error("db 0xZZ").to_string());
+    }
+}
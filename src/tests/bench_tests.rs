@@ -0,0 +1,44 @@
+#[cfg(test)]
+mod tests {
+    use crate::bench::{measure, Throughput};
+    use crate::chip::Chip8;
+    use std::time::Duration;
+
+    #[test]
+    fn counts_whole_frames() {
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(7);
+        // jp 0x200
+        chip.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        let throughput = measure(&mut chip, Duration::from_millis(20));
+        assert!(throughput.elapsed >= Duration::from_millis(20));
+        assert!(throughput.frames > 0);
+        assert_eq!(throughput.frames * 7, throughput.instructions);
+    }
+
+    #[test]
+    fn stops_with_a_tick_rate_of_zero() {
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(0);
+        chip.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        let throughput = measure(&mut chip, Duration::from_millis(5));
+        assert_eq!(0, throughput.instructions);
+    }
+
+    #[test]
+    fn rates() {
+        let throughput = Throughput {
+            instructions: 30_000_000,
+            frames: 3_000_000,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(15e6, throughput.instructions_per_second());
+        assert_eq!(1.5e6, throughput.frames_per_second());
+        assert_eq!(25_000.0, throughput.speedup());
+        assert_eq!(
+            "30000000 instructions in 2.00s: 15.00M instructions/s, \
+             1500000 frames/s (25000x real time)",
+            throughput.to_string()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::{is_cartridge, Cartridge, Options};
+    use crate::chip::Chip8;
+    use crate::gif::{self, Image};
+    use crate::octo::compile;
+    use crate::quirks::Quirks;
+    use std::env;
+    use std::fs;
+
+    const PROGRAM: &str = "
+        : main
+            i := smile
+            sprite v0 v0 4
+        : end
+            jump end
+        : smile 0x66 0x00 0x81 0x7E
+    ";
+
+    fn cartridge(options: Options) -> Cartridge {
+        Cartridge {
+            program: PROGRAM.to_string(),
+            options,
+        }
+    }
+
+    /// A cartridge holding `payload` as is, for JSON the writer wouldn't
+    /// produce
+    fn raw(payload: &str) -> Vec<u8> {
+        let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(payload.as_bytes());
+        let mut frame: Vec<u8> = data.iter().flat_map(|b| [b >> 4, b & 0xF]).collect();
+        frame.resize(64 * 64, 0);
+        gif::encode(&Image {
+            width: 64,
+            height: 64,
+            palette: vec![0; 16],
+            frames: vec![frame],
+        })
+    }
+
+    #[test]
+    fn round_trips() {
+        let original = cartridge(Options {
+            tick_rate: Some(200),
+            quirks: Some(Quirks::schip()),
+            colors: Some([0x101010, 0xE0C080]),
+        });
+        let gif = original.write("Smile");
+        assert!(is_cartridge(&gif));
+        assert_eq!(original, Cartridge::read(&gif).unwrap());
+    }
+
+    #[test]
+    fn unset_options_get_octos_defaults() {
+        let read = Cartridge::read(&cartridge(Options::default()).write("")).unwrap();
+        let expected = Options {
+            tick_rate: Some(20),
+            quirks: Some(Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                vf_reset: false,
+                clip_sprites: false,
+            }),
+            colors: Some([0x996600, 0xFFCC00]),
+        };
+        assert_eq!(expected, read.options);
+    }
+
+    #[test]
+    fn reads_what_octo_writes() {
+        let gif = raw(r##"{"program": ": main jump main", "options": {
+                "tickrate": 7, "fillColor": "#FFFFFF", "backgroundColor": "#000000",
+                "shiftQuirks": true, "loadStoreQuirks": true, "clipQuirks": true,
+                "fontStyle": "schip", "maxSize": 3583
+            }}"##);
+        let read = Cartridge::read(&gif).unwrap();
+        assert_eq!(": main jump main", read.program);
+        let expected = Options {
+            tick_rate: Some(7),
+            quirks: Some(Quirks {
+                shift_uses_vy: false,
+                load_store_increments_i: false,
+                jump_uses_vx: false,
+                vf_reset: false,
+                clip_sprites: true,
+            }),
+            colors: Some([0x000000, 0xFFFFFF]),
+        };
+        assert_eq!(expected, read.options);
+
+        // Options left out stay unset
+        let read = Cartridge::read(&raw(r#"{"program": "", "options": {}}"#)).unwrap();
+        assert_eq!(Options::default(), read.options);
+        let read = Cartridge::read(&raw(r#"{"program": ""}"#)).unwrap();
+        assert_eq!(Options::default(), read.options);
+    }
+
+    #[test]
+    fn long_programs_take_more_frames() {
+        let program = format!(": main\n{}", "    v0 += 1\n".repeat(2000));
+        let original = Cartridge {
+            program,
+            options: Options::default(),
+        };
+        let gif = original.write("Long");
+        assert_eq!(3, gif::decode(&gif).unwrap().frames.len());
+        assert_eq!(original.program, Cartridge::read(&gif).unwrap().program);
+    }
+
+    #[test]
+    fn data_doesnt_show() {
+        let image = gif::decode(&cartridge(Options::default()).write("Smile")).unwrap();
+        let frame = &image.frames[0];
+        assert!(frame.iter().any(|index| index & 0xF != 0));
+        for &index in frame {
+            assert_eq!(
+                image.palette[(index & 0xF0) as usize],
+                image.palette[index as usize]
+            );
+        }
+        // The label's text takes a colour of its own
+        let colors: std::collections::HashSet<u8> = frame.iter().map(|i| i >> 4).collect();
+        assert_eq!(5, colors.len());
+    }
+
+    #[test]
+    fn rejects_other_gifs() {
+        let plain = gif::encode(&Image {
+            width: 8,
+            height: 8,
+            palette: vec![0, 0xFFFFFF],
+            frames: vec![vec![1; 64]],
+        });
+        assert!(Cartridge::read(&plain).is_err());
+        assert!(Cartridge::read(&raw("{\"options\": {}}")).is_err());
+        assert!(Cartridge::read(b"\x12\x00").is_err());
+        assert!(!is_cartridge(b"\x12\x00"));
+    }
+
+    #[test]
+    fn load_rom_compiles_and_applies() {
+        let path = env::temp_dir().join(format!("cartridge-{}.gif", std::process::id()));
+        let options = Options {
+            tick_rate: Some(3),
+            quirks: Some(Quirks::schip()),
+            colors: None,
+        };
+        fs::write(&path, cartridge(options).write("Smile")).unwrap();
+
+        let mut chip = Chip8::new();
+        chip.load_rom(path.to_string_lossy().into_owned()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let rom = compile(PROGRAM).unwrap();
+        assert_eq!(rom[..], chip.memory()[0x200..0x200 + rom.len()]);
+        assert_eq!(3, chip.tick_rate());
+        assert_eq!(Quirks::schip(), chip.quirks());
+    }
+}
@@ -1,51 +1,66 @@
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use crate::chip::Chip8;
-    use crate::instructions::Instructions;
+    use crate::asm::assemble;
+    use crate::chip::{Chip8, RomError, MAX_ROM_SIZE};
 
     #[test]
     fn ins_00e0_test() {
-        let mut chip = Chip8::new(8);
-        chip.ins_00e0();
+        let mut chip = Chip8::new();
+        // Draw the 0 glyph, then clear it
+        chip.load_rom_bytes(&[0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0])
+            .unwrap();
+        chip.cycle();
+        chip.cycle();
+        assert!(chip.display().iter().any(|p| *p != 0));
+        chip.cycle();
         assert_eq!(&[0x000u32; 64 * 32], chip.display());
     }
 
     #[test]
     fn ins_1nnn_test() {
-        let mut chip = Chip8::new(8);
-        chip.ins_1nnn();
-        assert_eq!(chip.opcode(),chip.program_counter());
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&[0x12, 0x34]).unwrap();
+        chip.cycle();
+        assert_eq!(0x234, chip.program_counter());
     }
 
     #[test]
-    fn cycle_test(){
-        let mut chip = Chip8::new(8);
-        chip.load_rom("roms/test_opcode.ch8".to_string());
-        assert_ne!(0x000,chip.memory()[0x200+1]);
-        for i in 0..100{
-            chip.cycle();
-        }
-        assert!(true)
+    fn refuses_roms_that_dont_fit() {
+        let mut chip = Chip8::new();
+        assert_eq!(Err(RomError::Empty), chip.load_rom_bytes(&[]));
+        let rom = vec![0x12; MAX_ROM_SIZE + 1];
+        assert_eq!(
+            Err(RomError::TooLarge(MAX_ROM_SIZE + 1)),
+            chip.load_rom_bytes(&rom)
+        );
+        // Nothing was written
+        assert_eq!(0, chip.memory()[0x200]);
+        assert_eq!(Ok(()), chip.load_rom_bytes(&rom[1..]));
+        assert_eq!(0x12, chip.memory()[0xFFF]);
     }
 
     #[test]
-    fn print_rom(){
-        let mut chip = Chip8::new(8);
-        chip.load_rom("test_opcode.ch8".to_string());
-        assert_ne!(0x000,chip.memory()[0x200+1]);
-        //self.opcode = self.memory[(self.program_counter + 1) as usize] as u16;
-        for i in 0x200..0x600{
-            print!("{:#04x} ", chip.memory()[i]);
-            if i % 5 == 0 {println!()}
+    fn cycle_test() {
+        let rom = assemble(
+            "
+                ld v0, 10
+            loop:
+                add v1, 3
+                add v0, 0xFF
+                se v0, 0
+                jp loop
+            done:
+                jp done
+            ",
+        )
+        .unwrap();
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&rom).unwrap();
+        for _ in 0..100 {
+            chip.cycle();
         }
-        assert!(true)
+        assert_eq!(0, chip.registers()[0]);
+        assert_eq!(30, chip.registers()[1]);
+        assert_eq!(0x20A, chip.program_counter());
     }
-
-    #[test]
-    fn bit_test(){
-        let data = (0xD014 & 0x000f);
-        println!("{}, {:#04x}",data, data);
-    }
-
-}
\ No newline at end of file
+}
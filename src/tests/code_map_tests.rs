@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::code_map::SelfModification;
+
+    /// Stores `V2 = 7` at 0x20C with FX55, then jumps there
+    const WRITES_THEN_RUNS: [u8; 16] = [
+        0xA2, 0x0C, 0x60, 0x62, 0x61, 0x07, 0xF1, 0x55, 0x12, 0x0C, 0x00, 0x00, 0x00, 0x00, 0x12,
+        0x0E,
+    ];
+
+    ///Stores a BCD over its own first instructions, then spins
+    const RUNS_THEN_WRITES: [u8; 6] = [0xA2, 0x00, 0xF0, 0x33, 0x12, 0x04];
+
+    fn run(rom: &[u8], frames: u32) -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(rom).unwrap();
+        for _ in 0..frames {
+            chip.run_frame([0; 16]);
+        }
+        chip
+    }
+
+    #[test]
+    fn tracks_executed_and_written_bytes() {
+        let chip = run(&WRITES_THEN_RUNS, 1);
+        let map = chip.code_map();
+        assert!(map.executed(0x200) && map.executed(0x201) && map.executed(0x20F));
+        assert!(!map.executed(0x20A));
+        assert!(map.written(0x20C) && map.written(0x20D) && !map.written(0x20E));
+        assert_eq!(7, chip.registers()[2]);
+        assert_eq!(1, map.written_runs());
+        assert_eq!(0, map.overwrites());
+        assert!(map.self_modifying());
+        assert_eq!(
+            "self-modifying code: 0 writes over code, 1 run of written code\n  code and data at 0x20c-0x20d",
+            map.summary().to_string()
+        );
+    }
+
+    #[test]
+    fn counts_writes_over_code() {
+        let chip = run(&RUNS_THEN_WRITES, 2);
+        let map = chip.code_map();
+        assert_eq!(1, map.overwrites());
+        assert_eq!(0, map.written_runs());
+        assert_eq!(
+            "self-modifying code: 1 write over code, 0 runs of written code\n  code and data at 0x200-0x202",
+            map.summary().to_string()
+        );
+        let event = SelfModification::Overwrote {
+            pc: 0x202,
+            address: 0x200,
+            len: 3,
+        };
+        assert_eq!("0x202 wrote over code at 0x200-0x202", event.to_string());
+    }
+
+    #[test]
+    fn outside_writes_and_reloads_do_not_count() {
+        let mut chip = run(&RUNS_THEN_WRITES, 1);
+        chip.load_rom_bytes(&RUNS_THEN_WRITES).unwrap();
+        assert!(!chip.code_map().self_modifying());
+        assert!(!chip.code_map().executed(0x200));
+
+        // A debugger poking in a jump to itself
+        chip.write_memory(0x300, &[0x13, 0x00]);
+        chip.set_program_counter(0x300);
+        chip.run_frame([0; 16]);
+        assert!(chip.code_map().executed(0x300));
+        assert!(!chip.code_map().written(0x300));
+        assert!(!chip.code_map().self_modifying());
+    }
+}
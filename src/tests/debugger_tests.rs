@@ -0,0 +1,252 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::debugger::{access, Breakpoint, Debugger, Expr, Stop, Template, WatchKind, Watched};
+    use crate::code_map::SelfModification;
+    use crate::quirks::Quirks;
+
+    // V0 = 3, ST = V0, then count V1 up forever
+    const ROM: [u8; 8] = [0x60, 0x03, 0xF0, 0x18, 0x71, 0x01, 0x12, 0x04];
+
+    fn chip(tick_rate: u32) -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(tick_rate);
+        chip.load_rom_bytes(&ROM).unwrap();
+        chip
+    }
+
+    #[test]
+    fn frames_match_the_plain_interpreter() {
+        let mut plain = chip(7);
+        let mut debugged = chip(7);
+        let mut debugger = Debugger::default();
+        for _ in 0..5 {
+            plain.run_frame([0; 16]);
+            assert_eq!(None, debugger.run_frame(&mut debugged, [0; 16]));
+            assert_eq!(plain.save_state(), debugged.save_state());
+        }
+    }
+
+    #[test]
+    fn timers_tick_after_a_whole_frame_of_steps() {
+        let mut chip = chip(4);
+        let mut debugger = Debugger::default();
+        for _ in 0..3 {
+            debugger.step(&mut chip, [0; 16]);
+        }
+        assert!(debugger.mid_frame());
+        assert_eq!(3, chip.sound_timer());
+        debugger.step(&mut chip, [0; 16]);
+        assert!(!debugger.mid_frame());
+        assert_eq!(2, chip.sound_timer());
+    }
+
+    #[test]
+    fn stops_before_breakpoints_and_resumes() {
+        let mut chip = chip(10);
+        let mut debugger = Debugger::default();
+        assert!(debugger.add_breakpoint(0x204));
+        assert!(!debugger.add_breakpoint(0x204));
+
+        assert_eq!(
+            Some(Stop::Breakpoint(0x204)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(0x204, chip.program_counter());
+        assert!(debugger.mid_frame());
+        assert_eq!(
+            Some(Stop::Breakpoint(0x204)),
+            debugger.run_frame(&mut chip, [0; 16]),
+            "still stopped until resumed"
+        );
+
+        debugger.resume();
+        assert_eq!(
+            Some(Stop::Breakpoint(0x204)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(1, chip.registers()[1], "ran one loop before stopping again");
+
+        assert!(debugger.remove_breakpoint(0x204));
+        assert_eq!(None, debugger.run_frame(&mut chip, [0; 16]));
+        assert!(!debugger.mid_frame());
+    }
+
+    #[test]
+    fn keys_latch_at_frame_start() {
+        let mut chip = chip(2);
+        let mut debugger = Debugger::default();
+        let mut held = [0; 16];
+        held[5] = 1;
+        debugger.step(&mut chip, [0; 16]);
+        // Mid-frame: the new keys are ignored until the next frame
+        debugger.step(&mut chip, held);
+        let state = chip.save_state();
+        debugger.step(&mut chip, held);
+        assert_ne!(state, chip.save_state());
+    }
+
+    #[test]
+    fn conditional_breakpoints_count_hits() {
+        let mut chip = chip(10);
+        let mut debugger = Debugger::default();
+        let breakpoint = Breakpoint {
+            condition: Some("V1 == 3 && I < 0x300".parse().unwrap()),
+            ..Breakpoint::default()
+        };
+        debugger.set_breakpoint(0x204, breakpoint);
+        assert_eq!(
+            Some(Stop::Breakpoint(0x204)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(3, chip.registers()[1]);
+        assert_eq!(1, debugger.breakpoint(0x204).unwrap().hits);
+
+        let mut chip = self::chip(10);
+        let mut debugger = Debugger::default();
+        debugger.add_breakpoint(0x204);
+        debugger.breakpoint_mut(0x204).unwrap().ignore = 2;
+        assert_eq!(
+            Some(Stop::Breakpoint(0x204)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(2, chip.registers()[1], "let two hits through");
+        assert_eq!(3, debugger.breakpoint(0x204).unwrap().hits);
+    }
+
+    #[test]
+    fn tracepoints_log_without_stopping() {
+        let mut chip = chip(8);
+        let mut debugger = Debugger::default();
+        let breakpoint = Breakpoint {
+            trace: Some("v1={V1} pc={PC:x} {{raw}}".parse().unwrap()),
+            ..Breakpoint::default()
+        };
+        debugger.set_breakpoint(0x204, breakpoint);
+        assert_eq!(None, debugger.run_frame(&mut chip, [0; 16]));
+        assert_eq!(
+            vec!["v1=0 pc=0x204 {raw}", "v1=1 pc=0x204 {raw}", "v1=2 pc=0x204 {raw}"],
+            debugger.take_log()
+        );
+        assert!(debugger.take_log().is_empty());
+        assert_eq!(3, debugger.breakpoint(0x204).unwrap().hits);
+    }
+
+    #[test]
+    fn watchpoints_stop_after_the_access() {
+        // I = 0x300, V0 = 123, BCD of V0 to I, draw 5 rows from I, spin
+        let rom = [0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0xD0, 0x15, 0x12, 0x08];
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&rom).unwrap();
+        let mut debugger = Debugger::default();
+        debugger.add_watchpoint(Watched::Register(0), WatchKind::Write);
+        let tens = Watched::Memory {
+            address: 0x301,
+            len: 1,
+        };
+        debugger.add_watchpoint(tens, WatchKind::Write);
+        let sprite_end = Watched::Memory {
+            address: 0x304,
+            len: 4,
+        };
+        debugger.add_watchpoint(sprite_end, WatchKind::Read);
+
+        let stop = |pc, watched, kind| Some(Stop::Watchpoint { pc, watched, kind });
+        assert_eq!(
+            stop(0x202, Watched::Register(0), WatchKind::Write),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(123, chip.registers()[0]);
+        assert_eq!(
+            stop(0x204, tens, WatchKind::Write),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!([1, 2, 3], chip.memory()[0x300..0x303]);
+        assert_eq!(
+            stop(0x206, sprite_end, WatchKind::Read),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(0x208, chip.program_counter());
+        assert_eq!(None, debugger.run_frame(&mut chip, [0; 16]));
+
+        assert!(debugger.clear_watchpoints(Watched::Register(0)));
+        assert!(!debugger.remove_watchpoint(tens, WatchKind::Read));
+        assert!(debugger.remove_watchpoint(tens, WatchKind::Write));
+        assert_eq!(1, debugger.watchpoints().len());
+    }
+
+    #[test]
+    fn access_follows_the_quirks() {
+        let mut chip = Chip8::new();
+        // 8126: shift into V1, from V2 if the quirk says so
+        chip.load_rom_bytes(&[0x81, 0x26]).unwrap();
+        chip.set_quirks(Quirks::chip8());
+        let shift = access(&chip);
+        assert!(shift.reads_register(2) && !shift.reads_register(1));
+        assert!(shift.writes_register(1) && shift.writes_register(0xF));
+        chip.set_quirks(Quirks::schip());
+        assert!(access(&chip).reads_register(1));
+        assert_eq!(None, access(&chip).memory_read);
+    }
+
+    #[test]
+    fn expressions() {
+        let mut chip = chip(10);
+        chip.set_register(3, 0x10);
+        chip.set_index_register(0x50);
+        let eval = |s: &str| s.parse::<Expr>().unwrap().eval(&chip);
+        assert_eq!(1, eval("V3 == 0x10 && I > 0x30"));
+        assert_eq!(1, eval("v3 == 16 || [0x9999] == 1"));
+        assert_eq!(0, eval("!(V3 == 16)"));
+        // Font 0 is 0xF0 0x90 ...
+        assert_eq!(0x90, eval("[I + 1]"));
+        assert_eq!(0x1E, eval("3 + V3 - 1 + 0xC"));
+        assert_eq!(1, eval("1 < 2 == 1"));
+        assert_eq!(0x200, eval("PC"));
+        assert_eq!("V3 == 0x10", "  V3 == 0x10 ".parse::<Expr>().unwrap().to_string());
+
+        for bad in ["", "V3 ==", "VG", "V10", "(1", "[1", "1 2", "0xZZ", "V1 = 2", "$"] {
+            assert!(bad.parse::<Expr>().is_err(), "{bad}");
+        }
+        assert!("{V1".parse::<Template>().is_err());
+        assert!("V1}".parse::<Template>().is_err());
+        assert!("{nope}".parse::<Template>().is_err());
+    }
+
+    #[test]
+    fn stops_on_self_modifying_code_when_asked() {
+        // Stores `V2 = 7` at 0x20C with FX55, then jumps there
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&[
+            0xA2, 0x0C, 0x60, 0x62, 0x61, 0x07, 0xF1, 0x55, 0x12, 0x0C, 0x00, 0x00, 0x00, 0x00,
+            0x12, 0x0E,
+        ]).unwrap();
+        let mut debugger = Debugger::default();
+        assert_eq!(None, debugger.run_frame(&mut chip.clone(), [0; 16]));
+
+        debugger.set_stop_on_self_modifying(true);
+        let ran = SelfModification::RanWritten { pc: 0x20C };
+        assert_eq!(
+            Some(Stop::SelfModifying(ran)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(0, chip.registers()[2], "stopped before running it");
+        debugger.resume();
+        assert_eq!(None, debugger.run_frame(&mut chip, [0; 16]));
+        assert_eq!(7, chip.registers()[2]);
+
+        // Stores a BCD over its own first instructions
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&[0xA2, 0x00, 0xF0, 0x33, 0x12, 0x04]).unwrap();
+        let overwrote = SelfModification::Overwrote {
+            pc: 0x202,
+            address: 0x200,
+            len: 3,
+        };
+        assert_eq!(
+            Some(Stop::SelfModifying(overwrote)),
+            debugger.run_frame(&mut chip, [0; 16])
+        );
+        assert_eq!(0x204, chip.program_counter());
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::chip::DEFAULT_TICK_RATE;
+    use crate::detect::{detect, Clue, Hint, Platform, THRESHOLD};
+    use crate::quirks::Quirks;
+    use std::fs;
+    use std::path::Path;
+
+    fn rom(source: &str) -> Vec<u8> {
+        assemble(source).unwrap_or_else(|e| panic!("{e}\n{source}"))
+    }
+
+    fn hints(source: &str) -> Vec<Hint> {
+        detect(&rom(source)).clues.iter().map(|c| c.hint).collect()
+    }
+
+    #[test]
+    fn test_programs_are_plain_chip8() {
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        for entry in fs::read_dir(&roms).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "asm") {
+                continue;
+            }
+            let detection = detect(&rom(&fs::read_to_string(&path).unwrap()));
+            assert_eq!(Platform::Chip8, detection.platform, "{}", path.display());
+            assert!(
+                !detection
+                    .clues
+                    .iter()
+                    .any(|c| matches!(c.hint, Hint::Needs(_))),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn super_chip_opcodes() {
+        for opcode in ["0x00FF", "0x00C4", "0x00FB", "0xD120", "0xF330", "0xF275"] {
+            let detection = detect(&rom(&format!("    dw {opcode}\nloop:\n    jp loop\n")));
+            assert_eq!(Platform::SuperChip, detection.platform, "{opcode}");
+            assert_eq!(50, detection.confidence, "{opcode}");
+        }
+        // More of them, more certain
+        let detection = detect(&rom(
+            "    dw 0x00FF\n    dw 0x00FE\n    dw 0xF030\n    dw 0x00FD\n",
+        ));
+        assert_eq!(80, detection.confidence);
+        assert_eq!(Quirks::schip(), detection.quirks);
+        assert_eq!(30, detection.tick_rate);
+    }
+
+    #[test]
+    fn xo_chip_outranks_super_chip() {
+        let detection = detect(&rom(
+            "    dw 0x00FF\n    dw 0x5122\n    dw 0xF201\n    jp 0x200\n",
+        ));
+        assert_eq!(Platform::XoChip, detection.platform);
+        assert_eq!(75, detection.confidence);
+        assert!(!detection.quirks.clip_sprites);
+    }
+
+    #[test]
+    fn long_i_carries_its_address() {
+        // F000 NNNN loads I from the next word, which mustn't be read as 00FF
+        let detection = detect(&rom("    dw 0xF000\n    dw 0x00FF\n    jp 0x200\n"));
+        let expected = vec![Clue {
+            address: 0x200,
+            opcode: 0xF000,
+            hint: Hint::Needs(Platform::XoChip),
+        }];
+        assert_eq!(expected, detection.clues);
+        assert_eq!(2, detection.instructions);
+
+        // Nor must a skip over it land in the middle
+        let source = "    se v0, 1\n    dw 0xF000\n    dw 0x00FF\nloop:\n    jp loop\n";
+        assert_eq!(3, detect(&rom(source)).instructions);
+    }
+
+    #[test]
+    fn data_is_not_code() {
+        // A sprite that happens to read as 00FF, jumped over and only drawn
+        let source = "
+            ld i, sprite
+            drw v0, v0, 2
+        loop:
+            jp loop
+        sprite:
+            dw 0x00FF
+        ";
+        let detection = detect(&rom(source));
+        assert_eq!(Platform::Chip8, detection.platform);
+        assert!(detection.clues.is_empty());
+        assert_eq!(3, detection.instructions);
+    }
+
+    #[test]
+    fn follows_calls_and_both_sides_of_skips() {
+        let source = "
+            sne v0, 0
+            call sub
+            jp v0, 0x300
+            dw 0x00FF       ; past a computed jump, so never found
+        sub:
+            dw 0x00FB
+            ret
+            dw 0x00FC       ; past the return
+        ";
+        let detection = detect(&rom(source));
+        let addresses: Vec<u16> = detection.clues.iter().map(|c| c.address).collect();
+        assert_eq!(vec![0x208], addresses);
+        assert_eq!(5, detection.instructions);
+    }
+
+    #[test]
+    fn quirk_habits() {
+        assert_eq!(vec![Hint::ShiftsVy], hints("    shr v1, v2\n    shl v3\n"));
+        assert_eq!(
+            vec![Hint::ReusesI],
+            hints("    ld i, 0x300\n    ld [i], v3\n    ld v3, [i]\n")
+        );
+        // I set again in between, or control going elsewhere, says nothing
+        assert!(hints("    ld [i], v3\n    ld i, 0x300\n    ld v3, [i]\n").is_empty());
+        assert!(hints("    ld [i], v3\n    se v0, 1\n    ld v3, [i]\n").is_empty());
+        assert_eq!(
+            vec![Hint::ReusesI],
+            hints("    ld v3, [i]\n    add v0, 1\n    drw v0, v1, 3\n")
+        );
+    }
+
+    #[test]
+    fn unsure_guesses_keep_the_defaults() {
+        let detection = detect(&rom("    ld v0, 1\n    add v0, v1\n"));
+        assert_eq!(Platform::Chip8, detection.platform);
+        assert_eq!(0, detection.confidence);
+        assert_eq!(Quirks::default(), detection.quirks);
+        assert_eq!(DEFAULT_TICK_RATE, detection.tick_rate);
+    }
+
+    #[test]
+    fn vip_habits_count_for_chip8() {
+        let detection = detect(&rom("    ld v0, 1\n    shr v0, v1\n"));
+        assert!(detection.confidence >= THRESHOLD);
+        assert_eq!(Quirks::chip8(), detection.quirks);
+
+        // Not once a later platform is certain
+        let detection = detect(&rom("    dw 0x00FF\n    shr v0, v1\n    shr v0, v2\n"));
+        assert_eq!(Platform::SuperChip, detection.platform);
+        assert_eq!(50, detection.confidence);
+    }
+
+    #[test]
+    fn sure_chip8_guesses_take_the_vip_profile() {
+        let detection = detect(&rom(&"    add v0, 1\n".repeat(64)));
+        assert_eq!(Platform::Chip8, detection.platform);
+        assert_eq!(67, detection.confidence);
+        assert_eq!(Quirks::chip8(), detection.quirks);
+        assert_eq!(15, detection.tick_rate);
+    }
+
+    #[test]
+    fn report() {
+        let detection = detect(&rom(&"    dw 0x00FF\n".repeat(10)));
+        let text = detection.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            "Detected: SUPER-CHIP, 91% sure from 10 clues in 10 instructions",
+            lines[0]
+        );
+        assert!(lines[1].starts_with("Profile: shift_uses_vy=0"));
+        assert!(lines[1].ends_with(" at 30 instructions a frame"));
+        assert_eq!("  0x200 00FF: SUPER-CHIP only", lines[2]);
+        assert_eq!("  and 2 more", lines[10]);
+        assert_eq!(11, lines.len());
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::disassemble;
+    use crate::chip::Chip8;
+    use crate::movie::mask_to_keys;
+    use crate::profiler::class;
+    use crate::quirks::Quirks;
+    use crate::rng::{Rng, RngMode};
+    use crate::tests::reference::Reference;
+    use crate::tests::snapshot::State;
+    use crate::tests::{key_schedule, test_programs};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    ///Quicker than `State::diff` for the usual case of no difference
+    fn agree(chip: &Chip8, reference: &Reference) -> bool {
+        chip.program_counter() == reference.pc
+            && chip.index_register() == reference.i
+            && chip.registers() == &reference.v
+            && chip.stack() == reference.stack
+            && chip.delay_timer() == reference.dt
+            && chip.sound_timer() == reference.st
+            && chip.memory() == &reference.memory
+            && chip
+                .display()
+                .iter()
+                .zip(reference.display.as_flattened())
+                .all(|(ours, theirs)| (*ours != 0) == *theirs)
+    }
+
+    /// Runs both machines in lockstep, one frame per entry in `frames` (the
+    /// keys held, bit n for key n). Describes the first instruction after
+    /// which they disagree, if there is one.
+    fn first_difference(
+        chip: &mut Chip8,
+        reference: &mut Reference,
+        frames: &[u16],
+    ) -> Option<String> {
+        let mut count = 0;
+        for keys in frames {
+            chip.get_input(mask_to_keys(*keys));
+            reference.keys = core::array::from_fn(|k| keys >> k & 1 != 0);
+            for _ in 0..chip.tick_rate() {
+                let (pc, opcode) = (reference.pc, reference.opcode());
+                chip.cycle();
+                reference.step();
+                count += 1;
+
+                if !agree(chip, reference) {
+                    return Some(format!(
+                        "instruction {count} at {pc:#05x} ({opcode:04X} `{}`):\n  {}",
+                        disassemble(opcode),
+                        State::of_reference(reference)
+                            .diff(&State::of(chip))
+                            .join("\n  ")
+                    ));
+                }
+            }
+            chip.tick_timers();
+            reference.tick_timers();
+        }
+        None
+    }
+
+    fn check(name: &str, rom: &[u8], quirks: Quirks, rng: Rng, frames: &[u16]) {
+        let mut chip = Chip8::with_rng(rng.clone());
+        chip.set_quirks(quirks);
+        chip.load_rom_bytes(rom).unwrap();
+        let mut reference = Reference::new(rom, quirks, rng);
+        if let Some(difference) = first_difference(&mut chip, &mut reference, frames) {
+            panic!("{name} with {quirks} differs from the reference after {difference}");
+        }
+    }
+
+    ///Every combination of quirk flags
+    fn all_quirks() -> impl Iterator<Item = Quirks> {
+        (0..32).map(Quirks::from_bits)
+    }
+
+    #[test]
+    fn test_programs_match_the_reference() {
+        let frames = key_schedule();
+        for (name, rom) in test_programs() {
+            for quirks in all_quirks() {
+                check(&name, &rom, quirks, Rng::default(), &frames);
+            }
+        }
+    }
+
+    ///Opcodes `Chip8` knows, so random programs mostly run code
+    fn instruction() -> impl Strategy<Value = u16> {
+        any::<u16>().prop_filter("not an instruction", |opcode| class(*opcode) != "????")
+    }
+
+    fn rng() -> impl Strategy<Value = Rng> {
+        (any::<u64>(), any::<bool>()).prop_map(|(seed, vip)| {
+            Rng::new(seed, if vip { RngMode::Vip } else { RngMode::Xorshift })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn random_bytes_match_the_reference(
+            rom in vec(any::<u8>(), 1..512),
+            quirks in 0u8..32,
+            rng in rng(),
+            frames in vec(any::<u16>(), 1..30),
+        ) {
+            check("random bytes", &rom, Quirks::from_bits(quirks), rng, &frames);
+        }
+
+        #[test]
+        fn random_instructions_match_the_reference(
+            opcodes in vec(instruction(), 1..256),
+            quirks in 0u8..32,
+            rng in rng(),
+            frames in vec(any::<u16>(), 1..30),
+        ) {
+            let rom: Vec<u8> = opcodes.iter().flat_map(|op| op.to_be_bytes()).collect();
+            check("random instructions", &rom, Quirks::from_bits(quirks), rng, &frames);
+        }
+    }
+
+    #[test]
+    fn draw_with_vf_as_a_coordinate() {
+        // VF = 10, then draw at (VF, VF)
+        let rom = [0x6F, 0x0A, 0xA0, 0x50, 0xDF, 0xF5, 0x12, 0x06];
+        for quirks in all_quirks() {
+            check("DFF5", &rom, quirks, Rng::default(), &[0]);
+        }
+    }
+
+    #[test]
+    fn reports_the_first_difference() {
+        let rom = [0x61, 0x05, 0x62, 0x03, 0x81, 0x26, 0x12, 0x06];
+        let mut chip = Chip8::new();
+        chip.set_quirks(Quirks {
+            shift_uses_vy: true,
+            ..Quirks::default()
+        });
+        chip.load_rom_bytes(&rom).unwrap();
+        let mut reference = Reference::new(&rom, Quirks::default(), Rng::default());
+        assert_eq!(
+            Some(
+                "instruction 3 at 0x204 (8126 `shr v1, v2`):\n  \
+                 V1 should be 0x02, is 0x01"
+                    .to_string()
+            ),
+            first_difference(&mut chip, &mut reference, &[0])
+        );
+    }
+}
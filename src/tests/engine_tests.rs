@@ -0,0 +1,257 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::chip::{Chip8, Engine};
+    use crate::movie::mask_to_keys;
+    use crate::quirks::Quirks;
+    use crate::rng::{Rng, RngMode};
+    use crate::tests::{key_schedule, test_programs};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+
+    ///Every engine but the interpreter they're checked against
+    fn engines() -> impl Iterator<Item = Engine> {
+        Engine::ALL
+            .iter()
+            .copied()
+            .filter(|e| *e != Engine::Interpreter)
+    }
+
+    fn machines(
+        rom: &[u8],
+        quirks: Quirks,
+        tick_rate: u32,
+        rng: Rng,
+        engine: Engine,
+    ) -> (Chip8, Chip8) {
+        let mut plain = Chip8::with_rng(rng);
+        plain.set_quirks(quirks);
+        plain.set_tick_rate(tick_rate);
+        plain.load_rom_bytes(rom).unwrap();
+        let mut other = plain.clone();
+        other.set_engine(engine);
+        (plain, other)
+    }
+
+    /// Runs the interpreter and another engine a frame at a time, the keys
+    /// held for each frame given as bit masks, and checks they agree after
+    /// every frame.
+    fn check(name: &str, plain: &mut Chip8, other: &mut Chip8, frames: &[u16]) {
+        for (frame, keys) in frames.iter().enumerate() {
+            plain.run_frame(mask_to_keys(*keys));
+            other.run_frame(mask_to_keys(*keys));
+            assert!(
+                plain.save_state() == other.save_state(),
+                "{name} with {} at {} instructions a frame: the {} engine differs \
+                 after frame {frame}, PC {:#05x} interpreted and {:#05x} there",
+                plain.quirks(),
+                plain.tick_rate(),
+                other.engine().name(),
+                plain.program_counter(),
+                other.program_counter()
+            );
+            assert_eq!(
+                plain.code_map(),
+                other.code_map(),
+                "{name} on {}: code maps differ after frame {frame}",
+                other.engine().name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_programs_run_the_same() {
+        let frames = key_schedule();
+        for (name, rom) in test_programs() {
+            for engine in engines() {
+                for bits in 0..32 {
+                    // Odd tick rates end frames part way through blocks
+                    for tick_rate in [1, 7, 10] {
+                        let quirks = Quirks::from_bits(bits);
+                        let (mut plain, mut other) =
+                            machines(&rom, quirks, tick_rate, Rng::default(), engine);
+                        check(&name, &mut plain, &mut other, &frames);
+                    }
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn random_programs_run_the_same(
+            rom in vec(any::<u8>(), 1..512),
+            quirks in 0u8..32,
+            tick_rate in 1u32..40,
+            seed in any::<u64>(),
+            frames in vec(any::<u16>(), 1..30),
+        ) {
+            for engine in engines() {
+                let rng = Rng::new(seed, RngMode::Vip);
+                let (mut plain, mut other) =
+                    machines(&rom, Quirks::from_bits(quirks), tick_rate, rng, engine);
+                check("random bytes", &mut plain, &mut other, &frames);
+            }
+        }
+    }
+
+    #[test]
+    fn stores_over_the_rest_of_a_block() {
+        // FX55 rewrites the instruction after it, FX33 the one after that,
+        // all in one straight line
+        let source = "
+            ld i, patch
+            ld v0, 0x61
+            ld v1, 0x2A
+            ld [i], v1
+        patch:
+            ld v1, 0x00
+            ld v5, 123
+            ld i, digits
+            ld b, v5
+        digits:
+            dw 0
+            dw 0
+        done:
+            jp done
+        ";
+        let rom = assemble(source).unwrap();
+        for engine in engines() {
+            let (mut plain, mut other) =
+                machines(&rom, Quirks::default(), 10, Rng::default(), engine);
+            check("self-modifying", &mut plain, &mut other, &[0; 4]);
+            assert_eq!(0x2A, other.registers()[1]);
+            assert!(other.code_map().self_modifying());
+        }
+    }
+
+    #[test]
+    fn stores_into_a_running_loop() {
+        // The loop body rewrites its own `add v0, 1` to `add v0, 2` on the
+        // first pass, so the second runs the new instruction
+        let source = "
+            ld v1, 0x02
+            ld i, patch
+        loop:
+        patch:
+            add v0, 1
+            ld v2, 0x70
+            ld [i], v2
+            jp loop
+        ";
+        let rom = assemble(source).unwrap();
+        for engine in engines() {
+            let (mut plain, mut other) =
+                machines(&rom, Quirks::default(), 9, Rng::default(), engine);
+            check("rewritten loop", &mut plain, &mut other, &[0; 6]);
+        }
+    }
+
+    #[test]
+    fn writes_from_outside_are_seen() {
+        for engine in engines() {
+            // ld v0, 1 then loop
+            let (mut plain, mut other) = machines(
+                &[0x60, 0x01, 0x12, 0x00],
+                Quirks::default(),
+                10,
+                Rng::default(),
+                engine,
+            );
+            check("poke", &mut plain, &mut other, &[0]);
+            // Now ld v0, 2
+            for chip in [&mut plain, &mut other] {
+                chip.write_memory(0x201, &[0x02]);
+            }
+            check("poke", &mut plain, &mut other, &[0]);
+            assert_eq!(2, other.registers()[0]);
+        }
+    }
+
+    #[test]
+    fn load_state_keeps_the_engine_and_redecodes() {
+        let (mut source, _) = machines(
+            &[0x60, 0x07, 0x12, 0x00],
+            Quirks::default(),
+            10,
+            Rng::default(),
+            Engine::Interpreter,
+        );
+        let state = source.save_state();
+        source.run_frame([0; 16]);
+
+        for engine in engines() {
+            let (_, mut other) = machines(
+                &[0x60, 0x01, 0x12, 0x00],
+                Quirks::default(),
+                10,
+                Rng::default(),
+                engine,
+            );
+            other.run_frame([0; 16]);
+            let before = other.engine();
+            other.load_state(&state).unwrap();
+            other.run_frame([0; 16]);
+            assert_eq!(before, other.engine());
+            assert_eq!(7, other.registers()[0]);
+            assert_eq!(source.save_state(), other.save_state());
+        }
+    }
+
+    #[test]
+    fn switching_engines_mid_run() {
+        let rom = assemble(
+            &fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/bounce.asm"))
+                .unwrap(),
+        )
+        .unwrap();
+        let (mut plain, mut switched) = machines(
+            &rom,
+            Quirks::default(),
+            7,
+            Rng::default(),
+            Engine::Interpreter,
+        );
+        for engine in engines().chain([Engine::Interpreter]) {
+            switched.set_engine(engine);
+            check("bounce", &mut plain, &mut switched, &[0; 20]);
+        }
+    }
+
+    #[test]
+    fn changing_quirks_mid_run() {
+        // Shifts and logic, whose results depend on the quirks
+        let source = "
+            ld v0, 0x81
+            ld v1, 0x42
+        loop:
+            shr v0, v1
+            or v0, v1
+            shl v1, v0
+            add v0, 3
+            jp loop
+        ";
+        let rom = assemble(source).unwrap();
+        for engine in engines() {
+            let (mut plain, mut other) =
+                machines(&rom, Quirks::default(), 10, Rng::default(), engine);
+            for bits in [0, 31, 5, 0] {
+                for chip in [&mut plain, &mut other] {
+                    chip.set_quirks(Quirks::from_bits(bits));
+                }
+                check("quirk switch", &mut plain, &mut other, &[0; 3]);
+            }
+        }
+    }
+
+    #[test]
+    fn engine_names() {
+        for engine in Engine::ALL {
+            assert_eq!(Some(*engine), Engine::from_name(engine.name()));
+        }
+        assert_eq!(None, Engine::from_name("turbo"));
+        assert_eq!(Engine::Interpreter, Chip8::new().engine());
+    }
+}
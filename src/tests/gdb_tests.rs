@@ -0,0 +1,254 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::gdb::{GdbStub, TARGET_XML};
+    use crate::hex;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+
+    // Draw font 0, then spin at 0x206 until key 5 is held
+    const ROM: [u8; 14] = [
+        0xA0, 0x50, 0xD0, 0x05, 0x62, 0x05, 0xE2, 0x9E, 0x12, 0x06, 0x61, 0x01, 0x12, 0x0C,
+    ];
+
+    ///The client end of the protocol, as gdb speaks it
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, data: &str) {
+            let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+            write!(self.stream, "${data}#{checksum:02x}").unwrap();
+        }
+
+        fn reply(&mut self) -> String {
+            let mut byte = [0u8];
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut body = Vec::new();
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                if byte[0] == b'#' {
+                    break;
+                }
+                body.push(byte[0]);
+            }
+            let mut checksum = [0u8; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            let expected = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            assert_eq!(format!("{expected:02x}").as_bytes(), checksum);
+            String::from_utf8(body).unwrap()
+        }
+
+        fn ask(&mut self, data: &str) -> String {
+            self.send(data);
+            self.reply()
+        }
+
+        ///Runs a `monitor` command, returning its console output
+        fn monitor(&mut self, command: &str) -> String {
+            self.send(&format!("qRcmd,{}", hex::encode(command.as_bytes())));
+            let mut output = String::new();
+            loop {
+                match self.reply() {
+                    reply if reply == "OK" => return output,
+                    reply => output.push_str(&console(&reply)),
+                }
+            }
+        }
+    }
+
+    ///Decodes an `O` console output packet
+    fn console(packet: &str) -> String {
+        let bytes = hex::decode(packet.strip_prefix('O').unwrap()).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn debug_session(script: impl FnOnce(&mut Client) + Send + 'static) -> Chip8 {
+        let mut stub = GdbStub::bind("127.0.0.1:0").unwrap();
+        let address = stub.local_addr().unwrap();
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&ROM).unwrap();
+
+        let client = thread::spawn(move || {
+            let mut client = Client {
+                stream: TcpStream::connect(address).unwrap(),
+            };
+            script(&mut client);
+            client.send("k");
+        });
+        while !stub.killed() {
+            // A script that panics never sends the kill
+            let finished = client.is_finished();
+            stub.poll(&mut chip).unwrap();
+            if finished {
+                break;
+            }
+            // Hold the ROM at its first instruction until the client attaches
+            if stub.attached() && stub.running() {
+                stub.run_frame(&mut chip, [0; 16]).unwrap();
+            } else {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        client.join().unwrap();
+        assert!(!stub.attached());
+        chip
+    }
+
+    #[test]
+    fn describes_the_target() {
+        debug_session(|gdb| {
+            assert!(gdb
+                .ask("qSupported:multiprocess+")
+                .contains("qXfer:features:read+"));
+            let first = gdb.ask("qXfer:features:read:target.xml:0,40");
+            assert_eq!(format!("m{}", &TARGET_XML[..0x40]), first);
+            let all = gdb.ask("qXfer:features:read:target.xml:0,1000");
+            assert_eq!(format!("l{TARGET_XML}"), all);
+            let rest = gdb.ask("qXfer:features:read:target.xml:1,ffffffffffffffff");
+            assert_eq!(format!("l{}", &TARGET_XML[1..]), rest);
+            assert!(TARGET_XML.contains(r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#));
+            assert_eq!("S05", gdb.ask("?"));
+            assert_eq!("", gdb.ask("vMustReplyEmpty"));
+        });
+    }
+
+    #[test]
+    fn reads_and_writes_registers_and_memory() {
+        let chip = debug_session(|gdb| {
+            // V0-VF, I, PC, SP, DT, ST
+            let registers = gdb.ask("g");
+            assert_eq!(16 * 2 + 4 + 4 + 3 * 2, registers.len());
+            assert_eq!("0002", &registers[36..40]);
+            assert_eq!("0002", gdb.ask("p11"));
+
+            assert_eq!("a050d005", gdb.ask("m200,4"));
+            assert_eq!("OK", gdb.ask("M300,2:beef"));
+            assert_eq!("beef", gdb.ask("m300,2"));
+            assert_eq!("E01", gdb.ask("M ffe,4:00000000"));
+            assert_eq!("E01", gdb.ask("Mffe,4:00000000"));
+            assert_eq!("E01", gdb.ask("M1,ffffffffffffffff:00"));
+
+            assert_eq!("OK", gdb.ask("P3=2a"));
+            assert_eq!("2a", gdb.ask("p3"));
+            assert_eq!("OK", gdb.ask("P10=3412"));
+            assert_eq!("E01", gdb.ask("p15"));
+
+            let mut written = gdb.ask("g");
+            written.replace_range(0..2, "07");
+            assert_eq!("OK", gdb.ask(&format!("G{written}")));
+            assert_eq!("07", gdb.ask("p0"));
+        });
+        assert_eq!(0x1234, chip.index_register());
+        assert_eq!(0x2A, chip.registers()[3]);
+        assert_eq!([0xBE, 0xEF], chip.memory()[0x300..0x302]);
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_steps() {
+        let chip = debug_session(|gdb| {
+            assert_eq!("OK", gdb.ask("Z0,206,2"));
+            gdb.send("c");
+            assert_eq!("S05", gdb.reply());
+            assert_eq!("0602", gdb.ask("p11"));
+            assert_eq!("f0", gdb.ask("m50,1"), "font 0 starts at 0x50");
+
+            // Step over the breakpointed instruction
+            assert_eq!("S05", gdb.ask("s"));
+            assert_eq!("0802", gdb.ask("p11"));
+            gdb.send("c");
+            assert_eq!("S05", gdb.reply(), "back round the loop");
+            assert_eq!("0602", gdb.ask("p11"));
+
+            // Without the breakpoint it spins until interrupted
+            assert_eq!("OK", gdb.ask("z0,206,2"));
+            gdb.send("c");
+            thread::sleep(Duration::from_millis(20));
+            gdb.stream.write_all(&[0x03]).unwrap();
+            assert_eq!("S02", gdb.reply());
+        });
+        let pc = chip.program_counter();
+        assert!(pc == 0x206 || pc == 0x208);
+    }
+
+    #[test]
+    fn ignores_corrupt_packets() {
+        debug_session(|gdb| {
+            gdb.stream.write_all(b"$g#00").unwrap();
+            let mut nak = [0u8];
+            gdb.stream.read_exact(&mut nak).unwrap();
+            assert_eq!(b'-', nak[0]);
+            // A packet that never ends is dropped rather than buffered forever
+            gdb.stream.write_all(b"$").unwrap();
+            gdb.stream.write_all(&[b'a'; 3 * 0x1000]).unwrap();
+            gdb.stream.read_exact(&mut nak).unwrap();
+            assert_eq!(b'-', nak[0]);
+            assert_eq!("S05", gdb.ask("?"));
+            assert_eq!("OK", gdb.ask("QStartNoAckMode"));
+            assert_eq!("S05", gdb.ask("?"));
+        });
+    }
+
+    #[test]
+    fn watchpoints_and_monitor_commands() {
+        debug_session(|gdb| {
+            // The sprite of font 0 is read by the draw at 0x202
+            assert_eq!("OK", gdb.ask("Z3,52,1"));
+            gdb.send("c");
+            assert_eq!("T05rwatch:52;", gdb.reply());
+            assert_eq!("0402", gdb.ask("p11"));
+            assert_eq!("OK", gdb.ask("z3,52,1"));
+
+            assert_eq!("", gdb.monitor("watch v2"));
+            gdb.send("c");
+            assert_eq!("S05", gdb.reply());
+            assert_eq!("0602", gdb.ask("p11"));
+            assert_eq!("05", gdb.ask("p2"));
+
+            assert_eq!("", gdb.monitor("delete V2"));
+            assert_eq!("", gdb.monitor("trace 0x206 v2={V2} at {PC:x}"));
+            assert_eq!("", gdb.monitor("break 0x208 if V2 == 6"));
+            assert_eq!("", gdb.monitor("break 0x20a"));
+            assert_eq!("", gdb.monitor("ignore 0x20a 3"));
+            // gdb removes its own breakpoints at each stop; these stay
+            assert_eq!("OK", gdb.ask("z0,206,2"));
+            let info = gdb.monitor("info");
+            assert!(info.contains("0x206 hits 0 trace v2={V2} at {PC:x}\n"), "{info}");
+            assert!(info.contains("0x208 hits 0 if V2 == 6\n"), "{info}");
+            assert!(info.contains("0x20a hits 0 ignore 3\n"), "{info}");
+
+            gdb.send("c");
+            assert_eq!("v2=5 at 0x206\n", console(&gdb.reply()));
+            assert_eq!("v2=5 at 0x206\n", console(&gdb.reply()));
+            gdb.stream.write_all(&[0x03]).unwrap();
+            loop {
+                let reply = gdb.reply();
+                if !reply.starts_with('O') {
+                    assert_eq!("S02", reply);
+                    break;
+                }
+            }
+
+            assert!(gdb.monitor("frobnicate").starts_with("unknown command `frobnicate`"));
+            assert_eq!("bad address `zz`\n", gdb.monitor("break zz"));
+            assert_eq!("no breakpoint at 0x300\n", gdb.monitor("ignore 0x300 1"));
+            assert_eq!("nothing set at `v7`\n", gdb.monitor("delete v7"));
+            assert!(gdb.monitor("break 0x206 if V2 ==").contains("ends too soon"));
+
+            assert_eq!(
+                "self-modifying code: 0 writes over code, 0 runs of written code\n",
+                gdb.monitor("smc")
+            );
+            assert_eq!("", gdb.monitor("smc stop"));
+            assert_eq!("usage: smc [stop|ignore]\n", gdb.monitor("smc maybe"));
+        });
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use crate::gif::{decode, encode, Image};
+
+    #[test]
+    fn reads_a_standard_file() {
+        // The usual 1x1 GIF: black and white palette, a graphic control
+        // extension, one image with LZW code size 2
+        let data = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\xff\xff\xff\x00\x00\x00\
+                     !\xf9\x04\x01\x00\x00\x00\x00\
+                     ,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+        let image = decode(data).unwrap();
+        assert_eq!((1, 1), (image.width, image.height));
+        assert_eq!(vec![0xFFFFFF, 0x000000], image.palette);
+        assert_eq!(vec![vec![0]], image.frames);
+    }
+
+    #[test]
+    fn round_trips() {
+        // Noisy enough to fill the code table and start it over
+        let mut state = 1u32;
+        let mut noise = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 80) as u8
+        };
+        let image = Image {
+            width: 160,
+            height: 128,
+            palette: (0..80).map(|i| i * 0x030303).collect(),
+            frames: vec![
+                (0..160 * 128).map(|_| noise()).collect(),
+                vec![7; 160 * 128],
+                (0..160 * 128).map(|i| (i / 160 % 80) as u8).collect(),
+            ],
+        };
+        let decoded = decode(&encode(&image)).unwrap();
+        assert_eq!(image.frames, decoded.frames);
+        // The palette is padded to 128
+        assert_eq!(128, decoded.palette.len());
+        assert_eq!(image.palette, decoded.palette[..80]);
+    }
+
+    #[test]
+    fn places_smaller_interlaced_images() {
+        // A 1x1 screen then a 1x4 interlaced image at (1, 0) on a 2x4 screen
+        let mut image = encode(&Image {
+            width: 2,
+            height: 4,
+            palette: vec![0, 0xFFFFFF, 0xFF0000, 0x00FF00],
+            frames: vec![vec![0; 8]],
+        });
+        image.pop();
+        let rows = encode(&Image {
+            width: 1,
+            height: 4,
+            palette: vec![0, 0xFFFFFF, 0xFF0000, 0x00FF00],
+            // Stored in the order 0, 2, 1, 3
+            frames: vec![vec![1, 3, 2, 0]],
+        });
+        let descriptor = rows.iter().position(|&b| b == 0x2C).unwrap();
+        let mut frame = rows[descriptor..].to_vec();
+        frame[1] = 1;
+        frame[9] |= 0x40;
+        image.extend_from_slice(&frame);
+
+        let decoded = decode(&image).unwrap();
+        assert_eq!(vec![0, 1, 0, 2, 0, 3, 0, 0], decoded.frames[1]);
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(decode(b"\x89PNG").is_err());
+        assert!(decode(b"GIF89a\x01\x00").is_err());
+        let mut image = encode(&Image {
+            width: 4,
+            height: 4,
+            palette: vec![0, 0xFFFFFF],
+            frames: vec![vec![1; 16]],
+        });
+        image.truncate(image.len() - 4);
+        assert!(decode(&image).is_err());
+    }
+
+    #[test]
+    fn refuses_sizes_no_cartridge_has() {
+        let image = encode(&Image {
+            width: 4,
+            height: 4,
+            palette: vec![0, 0xFFFFFF],
+            frames: vec![vec![1; 16]],
+        });
+        assert!(decode(&image).is_ok());
+        // A 65535x65535 screen, as a 30 byte file
+        let mut huge = image.clone();
+        huge[6..10].copy_from_slice(&[0xFF; 4]);
+        let error = decode(&huge).unwrap_err().to_string();
+        assert!(error.contains("screen too big"), "{error}");
+        // An image bigger than the screen it's on
+        let descriptor = image.iter().position(|&b| b == 0x2C).unwrap();
+        let mut huge = image.clone();
+        huge[descriptor + 5..descriptor + 9].copy_from_slice(&[0xFF; 4]);
+        let error = decode(&huge).unwrap_err().to_string();
+        assert!(error.contains("image too big"), "{error}");
+
+        let mut many = image.clone();
+        let last = many.pop().unwrap();
+        let frame = image[descriptor..image.len() - 1].to_vec();
+        for _ in 0..256 {
+            many.extend_from_slice(&frame);
+        }
+        many.push(last);
+        let error = decode(&many).unwrap_err().to_string();
+        assert!(error.contains("too many frames"), "{error}");
+    }
+}
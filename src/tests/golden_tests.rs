@@ -0,0 +1,189 @@
+//! Runs the programs under `roms/` headlessly and compares the final frame
+//! with `roms/golden/<name>.txt`. Each program's header comments say how to
+//! run it:
+//!
+//! ```text
+//! ; frames: 10          frames to run, required
+//! ; tick_rate: 10       instructions per frame
+//! ; quirks: chip8       a preset or `name=0|1` pairs
+//! ; keys 3: 5 6         hold keys 5 and 6 from frame 3 on; none releases all
+//! ```
+//!
+//! `BLESS=1 cargo test golden` rewrites the goldens from what the programs
+//! draw now.
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::chip::{Chip8, DEFAULT_TICK_RATE};
+    use crate::quirks::Quirks;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    const WIDTH: usize = 64;
+
+    struct Script {
+        frames: u32,
+        tick_rate: u32,
+        quirks: Quirks,
+        ///The keys held from each frame on, in frame order
+        keys: Vec<(u32, [u8; 16])>,
+    }
+
+    fn script(name: &str, source: &str) -> Script {
+        let mut frames = None;
+        let mut script = Script {
+            frames: 0,
+            tick_rate: DEFAULT_TICK_RATE,
+            quirks: Quirks::default(),
+            keys: Vec::new(),
+        };
+        let directives = source
+            .lines()
+            .map_while(|line| line.trim().strip_prefix(';'))
+            .filter_map(|line| line.split_once(':'));
+        for (key, value) in directives {
+            let (key, value) = (key.trim(), value.trim());
+            let bad = || -> ! { panic!("{name}: bad `{key}: {value}`") };
+            match key.split_once(' ') {
+                Some(("keys", frame)) => {
+                    let mut held = [0; 16];
+                    for k in value.split_whitespace() {
+                        held[usize::from_str_radix(k, 16).unwrap_or_else(|_| bad())] = 1;
+                    }
+                    let frame = frame.parse().unwrap_or_else(|_| bad());
+                    script.keys.push((frame, held));
+                }
+                _ => match key {
+                    "frames" => frames = Some(value.parse().unwrap_or_else(|_| bad())),
+                    "tick_rate" => script.tick_rate = value.parse().unwrap_or_else(|_| bad()),
+                    "quirks" => script.quirks = value.parse().unwrap_or_else(|_| bad()),
+                    // Free text that happens to have a colon in it
+                    _ => {}
+                },
+            }
+        }
+        script.frames = frames.unwrap_or_else(|| panic!("{name}: no `; frames: N` header"));
+        script
+    }
+
+    ///The display after the scripted run, as rows of `#` and `.`
+    fn run(name: &str, source: &str) -> String {
+        let script = script(name, source);
+        let rom = assemble(source).unwrap_or_else(|e| panic!("{name}.asm {e}"));
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(script.tick_rate);
+        chip.set_quirks(script.quirks);
+        chip.load_rom_bytes(&rom).unwrap();
+
+        let mut held = [0; 16];
+        for frame in 0..script.frames {
+            for (from, keys) in &script.keys {
+                if *from == frame {
+                    held = *keys;
+                }
+            }
+            chip.run_frame(held);
+        }
+
+        let mut text = String::new();
+        for row in chip.display().chunks(WIDTH) {
+            text.extend(row.iter().map(|p| if *p != 0 { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The rows that differ, each shown expected over actual with a caret
+    /// under every pixel that changed
+    fn diff(expected: &str, actual: &str) -> String {
+        let mut out = String::new();
+        let mut pixels = 0;
+        for (y, (want, got)) in expected.lines().zip(actual.lines()).enumerate() {
+            if want == got {
+                continue;
+            }
+            let carets: String = want
+                .chars()
+                .zip(got.chars())
+                .map(|(a, b)| if a == b { ' ' } else { '^' })
+                .collect();
+            pixels += carets.matches('^').count();
+            out.push_str(&format!(
+                "row {y:2} expected {want}\n       actual   {got}\n                {}\n",
+                carets.trim_end()
+            ));
+        }
+        if expected.lines().count() != actual.lines().count() {
+            out.push_str("the images have different heights\n");
+        }
+        format!("{pixels} pixels differ\n{out}")
+    }
+
+    fn check(name: &str) {
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        let source = fs::read_to_string(roms.join(format!("{name}.asm")))
+            .unwrap_or_else(|e| panic!("Could not read {name}.asm: {e}"));
+        let actual = run(name, &source);
+
+        let golden = roms.join("golden").join(format!("{name}.txt"));
+        if env::var_os("BLESS").is_some() {
+            fs::write(&golden, &actual)
+                .unwrap_or_else(|e| panic!("Could not write {}: {e}", golden.display()));
+            return;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+            panic!(
+                "No golden image at {}, run with BLESS=1 to create it",
+                golden.display()
+            )
+        });
+        if expected != actual {
+            panic!(
+                "{name} doesn't match {}: {}Run with BLESS=1 if the new output is right.",
+                golden.display(),
+                diff(&expected, &actual)
+            );
+        }
+    }
+
+    #[test]
+    fn golden_font() {
+        check("font");
+    }
+
+    #[test]
+    fn golden_bcd() {
+        check("bcd");
+    }
+
+    #[test]
+    fn golden_keypad() {
+        check("keypad");
+    }
+
+    #[test]
+    fn golden_collision() {
+        check("collision");
+    }
+
+    #[test]
+    fn golden_subroutines() {
+        check("subroutines");
+    }
+
+    #[test]
+    fn golden_bounce() {
+        check("bounce");
+    }
+
+    #[test]
+    fn diffs_mark_changed_pixels() {
+        let diff = diff("....\n.##.\n", "....\n.#.#\n");
+        assert_eq!(
+            "2 pixels differ\nrow  1 expected .##.\n       actual   .#.#\n                  ^^\n",
+            diff
+        );
+    }
+}
@@ -0,0 +1,769 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::quirks::Quirks;
+    use crate::rng::RandomSource;
+    use crate::screen::Screen;
+    use crate::tests::snapshot::State;
+    use std::ops::{Deref, DerefMut};
+
+    ///Where the tests keep sprites and data
+    const DATA: u16 = 0x300;
+
+    ///`CXNN` always rolls 0xA5
+    struct Fixed;
+
+    impl RandomSource for Fixed {
+        fn next_byte(&mut self) -> u8 {
+            0xA5
+        }
+    }
+
+    struct Blank;
+
+    impl Screen for Blank {
+        fn set_pixel(&mut self, _x: usize, _y: usize, _on: bool) {}
+    }
+
+    type Machine = Chip8<Fixed>;
+
+    ///The machine after an instruction, and whether it asked for a redraw
+    #[derive(Clone, Debug)]
+    struct Outcome {
+        state: State,
+        drawn: bool,
+    }
+
+    impl Deref for Outcome {
+        type Target = State;
+
+        fn deref(&self) -> &State {
+            &self.state
+        }
+    }
+
+    impl DerefMut for Outcome {
+        fn deref_mut(&mut self) -> &mut State {
+            &mut self.state
+        }
+    }
+
+    impl Outcome {
+        fn of(chip: &Machine) -> Self {
+            Outcome {
+                state: State::of(chip),
+                drawn: chip.display_changed(),
+            }
+        }
+
+        fn pixel(&mut self, x: usize, y: usize) {
+            self.display[x + y * 64] ^= true;
+            self.drawn = true;
+        }
+
+        ///XORs a sprite that doesn't cross an edge
+        fn sprite(&mut self, x: usize, y: usize, rows: &[u8]) {
+            for (dy, row) in rows.iter().enumerate() {
+                for dx in 0..8 {
+                    if row & 0x80 >> dx != 0 {
+                        self.pixel(x + dx, y + dy);
+                    }
+                }
+            }
+        }
+
+        ///What differs from `actual`, field by field
+        fn diff(&self, actual: &Outcome) -> Vec<String> {
+            let mut out = self.state.diff(&actual.state);
+            if self.drawn != actual.drawn {
+                out.push(format!(
+                    "redraw should be {}, is {}",
+                    self.drawn, actual.drawn
+                ));
+            }
+            out
+        }
+    }
+
+    struct Case {
+        name: &'static str,
+        opcode: u16,
+        ///Prepares the machine; the opcode then goes wherever the PC is
+        setup: fn(&mut Machine),
+        ///Turns the state before the opcode into the one expected after it,
+        ///with the PC already moved past it
+        expect: fn(&mut Outcome, Quirks),
+    }
+
+    ///Executes `opcodes` from the PC
+    fn run(chip: &mut Machine, opcodes: &[u16]) {
+        for opcode in opcodes {
+            chip.write_memory(chip.program_counter(), &opcode.to_be_bytes());
+            chip.cycle();
+        }
+    }
+
+    fn none(_: &mut Machine) {}
+
+    fn no_change(_: &mut Outcome, _: Quirks) {}
+
+    const CASES: &[Case] = &[
+        // 0NNN
+        Case {
+            name: "00E0 clears the display",
+            opcode: 0x00E0,
+            setup: |c| {
+                c.set_index_register(0x50);
+                run(c, &[0xD125]);
+            },
+            expect: |s, _| {
+                s.display.fill(false);
+                s.drawn = true;
+            },
+        },
+        Case {
+            name: "00EE returns",
+            opcode: 0x00EE,
+            setup: |c| run(c, &[0x2300]),
+            expect: |s, _| s.pc = s.stack.pop().unwrap(),
+        },
+        Case {
+            name: "0NNN is ignored",
+            opcode: 0x0123,
+            setup: none,
+            expect: no_change,
+        },
+        // Flow
+        Case {
+            name: "1NNN jumps",
+            opcode: 0x1345,
+            setup: none,
+            expect: |s, _| s.pc = 0x345,
+        },
+        Case {
+            name: "2NNN calls",
+            opcode: 0x2345,
+            setup: none,
+            expect: |s, _| {
+                let pc = s.pc;
+                s.stack.push(pc);
+                s.pc = 0x345;
+            },
+        },
+        Case {
+            name: "BNNN jumps with an offset",
+            opcode: 0xB345,
+            setup: |c| {
+                c.set_register(0, 0x10);
+                c.set_register(3, 0x20);
+            },
+            expect: |s, q| s.pc = if q.jump_uses_vx { 0x365 } else { 0x355 },
+        },
+        // Skips
+        Case {
+            name: "3XNN skips when equal",
+            opcode: 0x3122,
+            setup: |c| c.set_register(1, 0x22),
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "3XNN doesn't skip when different",
+            opcode: 0x3123,
+            setup: |c| c.set_register(1, 0x22),
+            expect: no_change,
+        },
+        Case {
+            name: "4XNN skips when different",
+            opcode: 0x4123,
+            setup: |c| c.set_register(1, 0x22),
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "4XNN doesn't skip when equal",
+            opcode: 0x4122,
+            setup: |c| c.set_register(1, 0x22),
+            expect: no_change,
+        },
+        Case {
+            name: "5XY0 skips when equal",
+            opcode: 0x5120,
+            setup: |c| {
+                c.set_register(1, 0x22);
+                c.set_register(2, 0x22);
+            },
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "5XY0 doesn't skip when different",
+            opcode: 0x5120,
+            setup: |c| c.set_register(1, 0x22),
+            expect: no_change,
+        },
+        Case {
+            name: "9XY0 skips when different",
+            opcode: 0x9120,
+            setup: |c| c.set_register(1, 0x22),
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "9XY0 doesn't skip when equal",
+            opcode: 0x9120,
+            setup: |c| {
+                c.set_register(1, 0x22);
+                c.set_register(2, 0x22);
+            },
+            expect: no_change,
+        },
+        Case {
+            name: "EX9E skips when the key is down",
+            opcode: 0xE19E,
+            setup: |c| {
+                c.set_register(1, 5);
+                c.get_input([0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            },
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "EX9E doesn't skip when the key is up",
+            opcode: 0xE19E,
+            setup: |c| {
+                c.set_register(1, 5);
+                c.get_input([1, 1, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]);
+            },
+            expect: no_change,
+        },
+        Case {
+            name: "EXA1 skips when the key is up",
+            opcode: 0xE1A1,
+            setup: |c| {
+                c.set_register(1, 5);
+                c.get_input([1, 1, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]);
+            },
+            expect: |s, _| s.pc += 2,
+        },
+        Case {
+            name: "EXA1 doesn't skip when the key is down",
+            opcode: 0xE1A1,
+            setup: |c| {
+                c.set_register(1, 5);
+                c.get_input([0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            },
+            expect: no_change,
+        },
+        // Registers
+        Case {
+            name: "6XNN loads",
+            opcode: 0x6122,
+            setup: none,
+            expect: |s, _| s.v[1] = 0x22,
+        },
+        Case {
+            name: "7XNN adds without touching VF",
+            opcode: 0x7102,
+            setup: |c| {
+                c.set_register(1, 0xFF);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, _| s.v[1] = 0x01,
+        },
+        Case {
+            name: "8XY0 copies",
+            opcode: 0x8120,
+            setup: |c| c.set_register(2, 0x22),
+            expect: |s, _| s.v[1] = 0x22,
+        },
+        Case {
+            name: "8XY1 ors",
+            opcode: 0x8121,
+            setup: |c| {
+                c.set_register(1, 0x0C);
+                c.set_register(2, 0xAA);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, q| {
+                s.v[1] = 0xAE;
+                if q.vf_reset {
+                    s.v[0xF] = 0;
+                }
+            },
+        },
+        Case {
+            name: "8XY2 ands",
+            opcode: 0x8122,
+            setup: |c| {
+                c.set_register(1, 0x0C);
+                c.set_register(2, 0xAA);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, q| {
+                s.v[1] = 0x08;
+                if q.vf_reset {
+                    s.v[0xF] = 0;
+                }
+            },
+        },
+        Case {
+            name: "8XY3 xors",
+            opcode: 0x8123,
+            setup: |c| {
+                c.set_register(1, 0x0C);
+                c.set_register(2, 0xAA);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, q| {
+                s.v[1] = 0xA6;
+                if q.vf_reset {
+                    s.v[0xF] = 0;
+                }
+            },
+        },
+        Case {
+            name: "8XY4 adds",
+            opcode: 0x8124,
+            setup: |c| {
+                c.set_register(1, 0x10);
+                c.set_register(2, 0x20);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, _| {
+                s.v[1] = 0x30;
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "8XY4 carries",
+            opcode: 0x8124,
+            setup: |c| {
+                c.set_register(1, 0xF0);
+                c.set_register(2, 0x20);
+            },
+            expect: |s, _| {
+                s.v[1] = 0x10;
+                s.v[0xF] = 1;
+            },
+        },
+        Case {
+            name: "8XY5 subtracts",
+            opcode: 0x8125,
+            setup: |c| {
+                c.set_register(1, 0x30);
+                c.set_register(2, 0x10);
+            },
+            expect: |s, _| {
+                s.v[1] = 0x20;
+                s.v[0xF] = 1;
+            },
+        },
+        Case {
+            name: "8XY5 doesn't borrow on equal values",
+            opcode: 0x8125,
+            setup: |c| {
+                c.set_register(1, 0x30);
+                c.set_register(2, 0x30);
+            },
+            expect: |s, _| {
+                s.v[1] = 0;
+                s.v[0xF] = 1;
+            },
+        },
+        Case {
+            name: "8XY5 borrows",
+            opcode: 0x8125,
+            setup: |c| {
+                c.set_register(1, 0x10);
+                c.set_register(2, 0x30);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, _| {
+                s.v[1] = 0xE0;
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "8XY7 subtracts the other way",
+            opcode: 0x8127,
+            setup: |c| {
+                c.set_register(1, 0x10);
+                c.set_register(2, 0x30);
+            },
+            expect: |s, _| {
+                s.v[1] = 0x20;
+                s.v[0xF] = 1;
+            },
+        },
+        Case {
+            name: "8XY7 borrows",
+            opcode: 0x8127,
+            setup: |c| {
+                c.set_register(1, 0x30);
+                c.set_register(2, 0x10);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, _| {
+                s.v[1] = 0xE0;
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "8XY6 shifts right",
+            opcode: 0x8126,
+            setup: |c| {
+                c.set_register(1, 0x05);
+                c.set_register(2, 0x0C);
+            },
+            expect: |s, q| {
+                if q.shift_uses_vy {
+                    s.v[1] = 0x06;
+                    s.v[0xF] = 0;
+                } else {
+                    s.v[1] = 0x02;
+                    s.v[0xF] = 1;
+                }
+            },
+        },
+        Case {
+            name: "8XYE shifts left",
+            opcode: 0x812E,
+            setup: |c| {
+                c.set_register(1, 0x81);
+                c.set_register(2, 0x40);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, q| {
+                if q.shift_uses_vy {
+                    s.v[1] = 0x80;
+                    s.v[0xF] = 0;
+                } else {
+                    s.v[1] = 0x02;
+                    s.v[0xF] = 1;
+                }
+            },
+        },
+        Case {
+            name: "8FY4 keeps the carry over the sum",
+            opcode: 0x8F24,
+            setup: |c| {
+                c.set_register(0xF, 0xF0);
+                c.set_register(2, 0x20);
+            },
+            expect: |s, _| s.v[0xF] = 1,
+        },
+        Case {
+            name: "8FY5 keeps the flag over the difference",
+            opcode: 0x8F25,
+            setup: |c| {
+                c.set_register(0xF, 0x30);
+                c.set_register(2, 0x10);
+            },
+            expect: |s, _| s.v[0xF] = 1,
+        },
+        Case {
+            name: "8FY6 keeps the shifted out bit over the result",
+            opcode: 0x8F26,
+            setup: |c| {
+                c.set_register(0xF, 0x03);
+                c.set_register(2, 0x02);
+            },
+            expect: |s, q| s.v[0xF] = if q.shift_uses_vy { 0 } else { 1 },
+        },
+        Case {
+            name: "8FYE keeps the shifted out bit over the result",
+            opcode: 0x8F2E,
+            setup: |c| {
+                c.set_register(0xF, 0x80);
+                c.set_register(2, 0x7F);
+            },
+            expect: |s, q| s.v[0xF] = if q.shift_uses_vy { 0 } else { 1 },
+        },
+        Case {
+            name: "8XY8 is ignored",
+            opcode: 0x8128,
+            setup: |c| c.set_register(2, 0x22),
+            expect: no_change,
+        },
+        Case {
+            name: "CXNN masks a random byte",
+            opcode: 0xC10F,
+            setup: none,
+            expect: |s, _| s.v[1] = 0x05,
+        },
+        // Timers and keys
+        Case {
+            name: "FX07 reads the delay timer",
+            opcode: 0xF107,
+            setup: |c| c.set_delay_timer(0x42),
+            expect: |s, _| s.v[1] = 0x42,
+        },
+        Case {
+            name: "FX15 sets the delay timer",
+            opcode: 0xF115,
+            setup: |c| {
+                c.set_register(1, 0x42);
+                c.set_sound_timer(0x07);
+            },
+            expect: |s, _| s.dt = 0x42,
+        },
+        Case {
+            name: "FX18 sets the sound timer",
+            opcode: 0xF118,
+            setup: |c| {
+                c.set_register(1, 0x42);
+                c.set_delay_timer(0x07);
+            },
+            expect: |s, _| s.st = 0x42,
+        },
+        Case {
+            name: "FX0A waits for a key",
+            opcode: 0xF10A,
+            setup: none,
+            expect: |s, _| s.pc -= 2,
+        },
+        Case {
+            name: "FX0A reads the key that's down",
+            opcode: 0xF10A,
+            setup: |c| c.get_input([0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]),
+            expect: |s, _| s.v[1] = 7,
+        },
+        // Index and memory
+        Case {
+            name: "ANNN loads I",
+            opcode: 0xA345,
+            setup: none,
+            expect: |s, _| s.i = 0x345,
+        },
+        Case {
+            name: "FX1E adds to I",
+            opcode: 0xF11E,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.set_register(1, 0x10);
+            },
+            expect: |s, _| s.i = DATA + 0x10,
+        },
+        Case {
+            name: "FX29 points I at a glyph",
+            opcode: 0xF129,
+            setup: |c| c.set_register(1, 0xA),
+            expect: |s, _| s.i = 0x50 + 0xA * 5,
+        },
+        Case {
+            name: "FX33 stores decimal digits",
+            opcode: 0xF133,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.set_register(1, 137);
+            },
+            expect: |s, _| s.memory[DATA as usize..][..3].copy_from_slice(&[1, 3, 7]),
+        },
+        Case {
+            name: "FX55 stores V0 to VX",
+            opcode: 0xF255,
+            setup: |c| {
+                c.set_index_register(DATA);
+                for x in 0..4 {
+                    c.set_register(x, x as u8 + 1);
+                }
+            },
+            expect: |s, q| {
+                s.memory[DATA as usize..][..3].copy_from_slice(&[1, 2, 3]);
+                if q.load_store_increments_i {
+                    s.i += 3;
+                }
+            },
+        },
+        Case {
+            name: "F055 stores V0 alone",
+            opcode: 0xF055,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.set_register(0, 9);
+                c.set_register(1, 9);
+            },
+            expect: |s, q| {
+                s.memory[DATA as usize] = 9;
+                if q.load_store_increments_i {
+                    s.i += 1;
+                }
+            },
+        },
+        Case {
+            name: "FX65 loads V0 to VX",
+            opcode: 0xF265,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.write_memory(DATA, &[4, 5, 6, 7]);
+            },
+            expect: |s, q| {
+                s.v[..3].copy_from_slice(&[4, 5, 6]);
+                if q.load_store_increments_i {
+                    s.i += 3;
+                }
+            },
+        },
+        Case {
+            name: "F065 loads V0 alone",
+            opcode: 0xF065,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.write_memory(DATA, &[4, 5]);
+            },
+            expect: |s, q| {
+                s.v[0] = 4;
+                if q.load_store_increments_i {
+                    s.i += 1;
+                }
+            },
+        },
+        Case {
+            name: "EXNN gaps are ignored",
+            opcode: 0xE1FF,
+            setup: none,
+            expect: no_change,
+        },
+        Case {
+            name: "FXNN gaps are ignored",
+            opcode: 0xF1FF,
+            setup: none,
+            expect: no_change,
+        },
+        // Drawing
+        Case {
+            name: "DXYN draws",
+            opcode: 0xD125,
+            setup: |c| {
+                c.set_index_register(0x50);
+                c.set_register(1, 2);
+                c.set_register(2, 3);
+                c.set_register(0xF, 0x55);
+            },
+            expect: |s, _| {
+                s.sprite(2, 3, &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "DXYN erases and reports the collision",
+            opcode: 0xD125,
+            setup: |c| {
+                c.set_index_register(0x50);
+                c.set_register(1, 2);
+                c.set_register(2, 3);
+                run(c, &[0xD125]);
+            },
+            expect: |s, _| {
+                s.display.fill(false);
+                s.drawn = true;
+                s.v[0xF] = 1;
+            },
+        },
+        Case {
+            name: "DXYN wraps the start position",
+            opcode: 0xD121,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.write_memory(DATA, &[0x80]);
+                c.set_register(1, 64 + 3);
+                c.set_register(2, 32 + 1);
+            },
+            expect: |s, _| {
+                s.pixel(3, 1);
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "DXYN clips or wraps at the edges",
+            opcode: 0xD122,
+            setup: |c| {
+                c.set_index_register(DATA);
+                c.write_memory(DATA, &[0xC0, 0xC0]);
+                c.set_register(1, 63);
+                c.set_register(2, 31);
+            },
+            expect: |s, q| {
+                s.pixel(63, 31);
+                if !q.clip_sprites {
+                    s.pixel(0, 31);
+                    s.pixel(63, 0);
+                    s.pixel(0, 0);
+                }
+                s.v[0xF] = 0;
+            },
+        },
+        Case {
+            name: "DXY0 draws nothing",
+            opcode: 0xD120,
+            setup: |c| {
+                c.set_index_register(0x50);
+                c.set_register(0xF, 1);
+            },
+            expect: |s, _| {
+                s.drawn = true;
+                s.v[0xF] = 0;
+            },
+        },
+    ];
+
+    ///Every combination of quirk flags
+    fn all_quirks() -> impl Iterator<Item = Quirks> {
+        (0..32).map(Quirks::from_bits)
+    }
+
+    fn check(case: &Case, quirks: Quirks) {
+        let mut chip = Chip8::with_rng(Fixed);
+        chip.set_quirks(quirks);
+        (case.setup)(&mut chip);
+        chip.write_memory(chip.program_counter(), &case.opcode.to_be_bytes());
+        chip.present(&mut Blank);
+
+        let mut expected = Outcome::of(&chip);
+        expected.pc += 2;
+        (case.expect)(&mut expected, quirks);
+        chip.cycle();
+
+        let differences = expected.diff(&Outcome::of(&chip));
+        assert!(
+            differences.is_empty(),
+            "{} ({:04X}) with {quirks}:\n  {}",
+            case.name,
+            case.opcode,
+            differences.join("\n  ")
+        );
+    }
+
+    #[test]
+    fn every_instruction_under_every_quirk() {
+        for case in CASES {
+            for quirks in all_quirks() {
+                check(case, quirks);
+            }
+        }
+    }
+
+    #[test]
+    fn every_opcode_class_is_covered() {
+        use crate::profiler::class;
+        let covered: Vec<&str> = CASES.iter().map(|case| class(case.opcode)).collect();
+        for opcode in 0..=0xFFFFu16 {
+            let class = class(opcode);
+            assert!(
+                class == "????" || covered.contains(&class),
+                "no test for {class}"
+            );
+        }
+    }
+
+    #[test]
+    fn failures_name_what_differs() {
+        let chip = Chip8::with_rng(Fixed);
+        let mut expected = Outcome::of(&chip);
+        expected.v[3] = 1;
+        expected.pixel(2, 1);
+        assert_eq!(
+            vec![
+                "V3 should be 0x01, is 0x00",
+                "pixel 2,1 should be true, is false"
+            ],
+            expected.diff(&Outcome::of(&chip))
+        );
+    }
+}
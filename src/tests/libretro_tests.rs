@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod tests {
+    //! A tiny libretro frontend: it feeds callbacks to the exported entry
+    //! points the way RetroArch does and records what the core sends back.
+    use crate::chip::STATE_SIZE;
+    use crate::libretro::*;
+    use std::ffi::{c_uint, c_void, CStr};
+    use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // Draw font 0 at (0, 0), beep, wait for key 5 and draw font 5 at (8, 8)
+    const ROM: [u8; 22] = [
+        0xA0, 0x50, 0xD0, 0x05, 0x60, 0x20, 0xF0, 0x18, 0x61, 0x05, 0xE1, 0x9E, 0x12, 0x0A, 0xF1,
+        0x29, 0x60, 0x08, 0xD0, 0x05, 0x12, 0x14,
+    ];
+
+    static FRAME: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+    static AUDIO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+    static AUDIO_LOUD: AtomicBool = AtomicBool::new(false);
+    static JOYPAD: AtomicU32 = AtomicU32::new(0);
+    static OPTIONS_SET: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+        match cmd {
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+                *(data as *const c_uint) == RETRO_PIXEL_FORMAT_XRGB8888
+            }
+            RETRO_ENVIRONMENT_SET_VARIABLES => {
+                let first = &*(data as *const RetroVariable);
+                let key = CStr::from_ptr(first.key);
+                OPTIONS_SET.store(key == c"chip8_quirks", Ordering::SeqCst);
+                true
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE => {
+                let variable = &mut *(data as *mut RetroVariable);
+                if CStr::from_ptr(variable.key) == c"chip8_tick_rate" {
+                    variable.value = c"20".as_ptr();
+                    return true;
+                }
+                false
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+                assert_eq!(STATE_SIZE, retro_serialize_size());
+                *(data as *mut bool) = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    unsafe extern "C" fn video_refresh(
+        data: *const c_void,
+        width: c_uint,
+        height: c_uint,
+        pitch: usize,
+    ) {
+        assert_eq!((64, 32, 256), (width, height, pitch));
+        // Frontends may call back into the core from inside a callback
+        assert_eq!(4096, retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM));
+        let pixels = std::slice::from_raw_parts(data as *const u32, 64 * 32);
+        *FRAME.lock().unwrap() = pixels.to_vec();
+    }
+
+    unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+        let samples = std::slice::from_raw_parts(data, frames * 2);
+        AUDIO_FRAMES.store(frames, Ordering::SeqCst);
+        AUDIO_LOUD.store(samples.iter().any(|s| *s != 0), Ordering::SeqCst);
+        frames
+    }
+
+    unsafe extern "C" fn input_poll() {}
+
+    unsafe extern "C" fn input_state(
+        port: c_uint,
+        device: c_uint,
+        _index: c_uint,
+        id: c_uint,
+    ) -> i16 {
+        let held = port == 0
+            && device == RETRO_DEVICE_JOYPAD
+            && JOYPAD.load(Ordering::SeqCst) & 1 << id != 0;
+        held as i16
+    }
+
+    fn lit(x: usize, y: usize) -> bool {
+        FRAME.lock().unwrap()[y * 64 + x] != 0
+    }
+
+    #[test]
+    fn reports_system_info() {
+        let mut info = std::mem::MaybeUninit::<RetroSystemInfo>::uninit();
+        let info = unsafe {
+            retro_get_system_info(info.as_mut_ptr());
+            info.assume_init()
+        };
+        let extensions = unsafe { CStr::from_ptr(info.valid_extensions) };
+        assert_eq!(c"ch8|c8", extensions);
+        assert!(!info.need_fullpath);
+
+        let mut av = std::mem::MaybeUninit::<RetroSystemAvInfo>::uninit();
+        let av = unsafe {
+            retro_get_system_av_info(av.as_mut_ptr());
+            av.assume_init()
+        };
+        assert_eq!((64, 32), (av.geometry.base_width, av.geometry.base_height));
+        assert_eq!(60.0, av.timing.fps);
+        assert_eq!(RETRO_API_VERSION, retro_api_version());
+    }
+
+    // The core is a process-wide singleton, so the whole session is one test
+    #[test]
+    fn runs_a_game_session() {
+        unsafe { retro_set_environment(environment) };
+        assert!(OPTIONS_SET.load(Ordering::SeqCst));
+        retro_set_video_refresh(video_refresh);
+        retro_set_audio_sample_batch(audio_sample_batch);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: ROM.as_ptr() as *const c_void,
+            size: ROM.len(),
+            meta: std::ptr::null(),
+        };
+        assert!(unsafe { retro_load_game(&game) });
+        assert_eq!(4096, retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM));
+        let memory = retro_get_memory_data(RETRO_MEMORY_SYSTEM_RAM) as *mut u8;
+        assert_eq!(0xA0, unsafe { *memory.add(0x200) });
+        // Written the way cheat and achievement code pokes RAM
+        unsafe { *memory.add(0xF00) = 0x5A };
+
+        retro_run();
+        assert!(lit(0, 1) && lit(3, 1), "font 0 drawn");
+        assert!(!lit(8, 8), "still waiting for key 5");
+        assert_eq!(735, AUDIO_FRAMES.load(Ordering::SeqCst));
+        assert!(AUDIO_LOUD.load(Ordering::SeqCst), "sound timer running");
+
+        assert_eq!(STATE_SIZE, retro_serialize_size());
+        let mut state = vec![0u8; retro_serialize_size()];
+        assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+        // The poke reached the machine
+        assert_eq!(0x5A, state[5 + 0xF00]);
+        // Tick rate came from the core option, stored at the end of the state
+        assert_eq!(20u32.to_le_bytes(), state[state.len() - 4..]);
+
+        // D-pad up is key 5 in the default layout
+        JOYPAD.store(1 << RETRO_DEVICE_ID_JOYPAD_UP, Ordering::SeqCst);
+        retro_run();
+        assert!(lit(8, 8) && lit(8, 9) && !lit(11, 9), "font 5 drawn");
+
+        JOYPAD.store(0, Ordering::SeqCst);
+        assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+        retro_run();
+        assert!(!lit(8, 8), "state restored to before the key press");
+        assert!(!unsafe { retro_unserialize(state.as_ptr() as *const c_void, 3) });
+        assert!(!unsafe { retro_unserialize(std::ptr::null(), state.len()) });
+        assert!(!unsafe { retro_serialize(std::ptr::null_mut(), state.len()) });
+
+        for _ in 0..0x20 {
+            retro_run();
+        }
+        assert!(!AUDIO_LOUD.load(Ordering::SeqCst), "sound timer expired");
+
+        retro_reset();
+        retro_run();
+        assert!(lit(0, 0) && !lit(8, 8));
+
+        retro_unload_game();
+        assert_eq!(0, retro_serialize_size());
+        retro_deinit();
+    }
+}
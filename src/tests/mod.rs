@@ -1 +1,69 @@
-pub mod chip_tests;
\ No newline at end of file
+pub mod asm_tests;
+pub mod bench_tests;
+#[cfg(feature = "octo")]
+pub mod cartridge_tests;
+pub mod chip_tests;
+pub mod code_map_tests;
+pub mod debugger_tests;
+pub mod detect_tests;
+pub mod differential_tests;
+pub mod engine_tests;
+pub mod gdb_tests;
+pub mod gif_tests;
+pub mod golden_tests;
+pub mod instruction_tests;
+#[cfg(feature = "libretro")]
+pub mod libretro_tests;
+pub mod movie_tests;
+pub mod octo_tests;
+pub mod osd_tests;
+pub mod png_tests;
+pub mod profiler_tests;
+pub mod property_tests;
+pub mod quirks_tests;
+#[cfg(test)]
+mod reference;
+#[cfg(feature = "remote")]
+pub mod remote_tests;
+pub mod rng_tests;
+#[cfg(feature = "romdb")]
+pub mod romdb_tests;
+pub mod scheduler_tests;
+pub mod screen_tests;
+#[cfg(test)]
+mod snapshot;
+pub mod speed_tests;
+pub mod state_tests;
+#[cfg(feature = "tui")]
+pub mod tui_tests;
+pub mod video_tests;
+
+/// The test programs in `roms/`, assembled, with their file names
+#[cfg(test)]
+fn test_programs() -> Vec<(String, Vec<u8>)> {
+    use std::fs;
+    use std::path::Path;
+
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(&roms).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "asm") {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let rom = crate::asm::assemble(&fs::read_to_string(&path).unwrap()).unwrap();
+        programs.push((name, rom));
+    }
+    assert!(!programs.is_empty(), "no programs in {}", roms.display());
+    programs
+}
+
+/// Keys held for each of 64 frames, bit n for key n: each key in turn for
+/// two frames, then nothing for two
+#[cfg(test)]
+fn key_schedule() -> Vec<u16> {
+    (0..64)
+        .map(|f| if f % 4 < 2 { 1 << (f / 4) } else { 0 })
+        .collect()
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::movie::{keys_to_mask, mask_to_keys, Movie, MoviePlayer};
+    use crate::quirks::Quirks;
+    use crate::rng::{Rng, RngMode};
+
+    // Random values into V0..V3, then loop until the key in V0 is down
+    const ROM: [u8; 14] = [
+        0xC0, 0x0F, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF, 0xE0, 0x9E, 0x12, 0x00, 0x12, 0x0C,
+    ];
+
+    #[test]
+    fn mask_round_trip() {
+        let mut keys = [0u8; 16];
+        keys[0x0] = 1;
+        keys[0xA] = 1;
+        keys[0xF] = 1;
+        assert_eq!(0x8401, keys_to_mask(keys));
+        assert_eq!(keys, mask_to_keys(0x8401));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut movie = Movie::new(&ROM, 42, RngMode::Vip, 15, Quirks::chip8());
+        movie.record([0; 16]);
+        movie.record(mask_to_keys(0x0012));
+        let parsed = Movie::parse(&movie.to_string()).unwrap();
+        assert_eq!(movie, parsed);
+        assert!(parsed.matches_rom(&ROM));
+        assert!(!parsed.matches_rom(&[0x00, 0xE0]));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Movie::parse("hello").is_err());
+        assert!(Movie::parse("CHIP8MOVIE 2\nrom_sha1 00\nseed x\n").is_err());
+        assert!(Movie::parse("CHIP8MOVIEFOO 2\n").is_err());
+    }
+
+    #[test]
+    fn rejects_version_1() {
+        // Written before `tick_rate`, when a frame was one instruction
+        let v1 = "CHIP8MOVIE 1\nrom_sha1 00\nseed 1\nrng xorshift\nquirks \nframes\n0000\n";
+        let error = Movie::parse(v1).unwrap_err().to_string();
+        assert!(error.contains("format 1"), "{error}");
+        assert!(Movie::new(&ROM, 1, RngMode::Vip, 10, Quirks::chip8())
+            .to_string()
+            .starts_with("CHIP8MOVIE 2\n"));
+    }
+
+    fn run(movie: &Movie) -> Chip8 {
+        let mut chip = Chip8::with_rng(Rng::new(movie.seed, movie.rng_mode));
+        chip.set_quirks(movie.quirks);
+        chip.set_tick_rate(movie.tick_rate);
+        chip.load_rom_bytes(&ROM).unwrap();
+        let mut player = MoviePlayer::new(movie.clone());
+        while let Some(keys) = player.next_input() {
+            chip.run_frame(keys);
+        }
+        chip
+    }
+
+    #[test]
+    fn replay_matches_the_recording() {
+        let mut movie = Movie::new(&ROM, 7, RngMode::Xorshift, 10, Quirks::default());
+        let mut live = Chip8::with_rng(Rng::new(movie.seed, movie.rng_mode));
+        live.set_quirks(movie.quirks);
+        live.set_tick_rate(movie.tick_rate);
+        live.load_rom_bytes(&ROM).unwrap();
+        for frame in 0..200u16 {
+            let keys = mask_to_keys(frame % 3);
+            movie.record(keys);
+            live.run_frame(keys);
+        }
+
+        let replayed = run(&movie);
+        assert_eq!(live.rng(), replayed.rng());
+        assert_eq!(live.registers(), replayed.registers());
+        assert_eq!(live.memory()[..], replayed.memory()[..]);
+        assert_eq!(live.display(), replayed.display());
+    }
+}
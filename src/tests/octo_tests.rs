@@ -0,0 +1,280 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::{assemble, AsmError};
+    use crate::chip::Chip8;
+    use crate::octo::compile;
+    use std::fs;
+    use std::path::Path;
+
+    fn words(source: &str) -> Vec<u16> {
+        compile(source)
+            .unwrap()
+            .chunks(2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn compiles_every_statement() {
+        let source = "
+            : main
+            clear return ; jump 0x234 jump0 0x234 :call 0x234
+            v1 := 0x22 v1 := v2 v1 := random 0x22 v1 := key v1 := delay
+            v1 += 0x22 v1 += v2 v1 -= 2 v1 -= v2 v1 =- v2
+            v1 |= v2 v1 &= v2 v1 ^= v2 v1 >>= v2 v1 <<= v2
+            i := 0x234 i := hex v1 i += v1
+            delay := v1 buzzer := v1 bcd v1 save v1 load v1
+            sprite v1 v2 5
+            if v1 == 0x22 then clear if v1 != 0x22 then clear
+            if v1 == v2 then clear if v1 != v2 then clear
+            if v1 key then clear if v1 -key then clear
+        ";
+        assert_eq!(
+            vec![
+                0x00E0, 0x00EE, 0x00EE, 0x1234, 0xB234, 0x2234, 0x6122, 0x8120, 0xC122, 0xF10A,
+                0xF107, 0x7122, 0x8124, 0x71FE, 0x8125, 0x8127, 0x8121, 0x8122, 0x8123, 0x8126,
+                0x812E, 0xA234, 0xF129, 0xF11E, 0xF115, 0xF118, 0xF133, 0xF155, 0xF165, 0xD125,
+                0x4122, 0x00E0, 0x3122, 0x00E0, 0x9120, 0x00E0, 0x5120, 0x00E0, 0xE1A1, 0x00E0,
+                0xE19E, 0x00E0,
+            ],
+            words(source)
+        );
+    }
+
+    #[test]
+    fn compiles_extensions() {
+        let source = "
+            : main
+            hires lores exit scroll-left scroll-right scroll-down 3 scroll-up 3
+            i := bighex v1 saveflags v1 loadflags v1
+            save v1 - v3 load v1 - v3 plane 2 audio pitch := v1
+            i := long 0x1234
+        ";
+        assert_eq!(
+            vec![
+                0x00FF, 0x00FE, 0x00FD, 0x00FC, 0x00FB, 0x00C3, 0x00D3, 0xF130, 0xF175, 0xF185,
+                0x5132, 0x5133, 0xF201, 0xF002, 0xF13A, 0xF000, 0x1234,
+            ],
+            words(source)
+        );
+    }
+
+    /// `roms/bounce.asm`, written the Octo way, comes out byte for byte the
+    /// same
+    #[test]
+    fn matches_the_assembler() {
+        let octo = "
+            : main
+                v5 := 10   v6 := 5   # position
+                v7 := 1    v8 := 1   # direction
+                v9 := 0
+                score
+            : loop
+                i := ball
+                sprite v5 v6 2
+                if v5 != 0 then jump right
+                v7 := 1
+                bounce
+            : right
+                if v5 != 62 then jump top
+                v7 := 255
+                bounce
+            : top
+                if v6 != 0 then jump bottom
+                v8 := 1
+                bounce
+            : bottom
+                if v6 != 30 then jump move
+                v8 := 255
+                bounce
+            : move
+                i := ball
+                sprite v5 v6 2
+                v5 += v7
+                v6 += v8
+                jump loop
+            : bounce
+                score
+                v9 += 1
+            : score
+                i := digits
+                bcd v9
+                load v2
+                va := 1
+                vb := 1
+                i := hex v0  sprite va vb 5  va += 5
+                i := hex v1  sprite va vb 5  va += 5
+                i := hex v2  sprite va vb 5
+                return
+            : ball   0b11000000 0b11000000
+            : digits 0 0 0
+        ";
+        let asm = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/bounce.asm"))
+            .unwrap();
+        assert_eq!(assemble(&asm).unwrap(), compile(octo).unwrap());
+    }
+
+    #[test]
+    fn starts_at_main() {
+        assert_eq!(vec![0x1204, 0x0102, 0x1204], words("1 2 : main jump main"));
+        assert_eq!(
+            vec![0x6105, 0x1200],
+            words(":const five 5 :alias x v1 : main x := five jump main")
+        );
+        // Labels before `main` come after the jump
+        assert_eq!(vec![0x1204, 0x00EE, 0x2202], words(": f return : main f"));
+        assert_eq!(
+            "line 1: unknown label `main`",
+            compile("clear").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let source = "
+            : main
+            if v1 == 2 begin
+                v2 := 1
+            else
+                v2 := 2
+            end
+            loop
+                while v3 != 4
+                v3 += 1
+            again
+        ";
+        assert_eq!(
+            vec![
+                0x3102, 0x1208, 0x6201, 0x120A, 0x6202, // if
+                0x4304, 0x1212, 0x7301, 0x120A, // loop
+            ],
+            words(source)
+        );
+        // Without else
+        assert_eq!(
+            vec![0x9120, 0x1206, 0x00E0],
+            words(": main if v1 != v2 begin clear end")
+        );
+    }
+
+    /// Runs the comparison and returns whether it held
+    fn compare(a: u8, operator: &str, b: u8, left_number: bool) -> bool {
+        let condition = if left_number {
+            format!("{a} {operator} v2")
+        } else {
+            format!("v1 {operator} v2")
+        };
+        let source = format!(
+            ": main v1 := {a} v2 := {b} v0 := 0 if {condition} then v0 := 1 : end jump end"
+        );
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&compile(&source).unwrap()).unwrap();
+        for _ in 0..8 {
+            chip.cycle();
+        }
+        chip.registers()[0] == 1
+    }
+
+    #[test]
+    fn comparisons_run_true() {
+        for (a, b) in [(0, 0), (1, 2), (2, 1), (0, 255), (255, 0), (7, 7)] {
+            for left_number in [false, true] {
+                assert_eq!(a < b, compare(a, "<", b, left_number), "{a} < {b}");
+                assert_eq!(a > b, compare(a, ">", b, left_number), "{a} > {b}");
+                assert_eq!(a <= b, compare(a, "<=", b, left_number), "{a} <= {b}");
+                assert_eq!(a >= b, compare(a, ">=", b, left_number), "{a} >= {b}");
+            }
+        }
+        // Against a number on the right
+        let source = ": main v1 := 5 v0 := 0 if v1 > 4 then v0 := 1 : end jump end";
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&compile(source).unwrap()).unwrap();
+        for _ in 0..6 {
+            chip.cycle();
+        }
+        assert_eq!(1, chip.registers()[0]);
+    }
+
+    #[test]
+    fn directives() {
+        let source = "
+            :const speed 3
+            :alias x v4
+            : main
+            x := speed
+            :next target
+            v5 := 0
+            :unpack 0xA sprite
+            :unpack long far
+            :byte 255
+            speed
+            :org 0x300
+            : sprite 0x3C
+            :org 0x1234
+            : far
+        ";
+        let rom = compile(source).unwrap();
+        assert_eq!(
+            [0x64, 0x03, 0x65, 0x00, 0x60, 0xA3, 0x61, 0x00, 0x60, 0x12, 0x61, 0x34, 0xFF, 0x03],
+            rom[..14]
+        );
+        assert_eq!(0x3C, rom[0x100]);
+        // Labels alone don't make the ROM longer
+        assert_eq!(0x101, rom.len());
+        // `:next` names the second byte of the instruction after it
+        let rom = compile(": main :next here v5 := 0 i := here").unwrap();
+        assert_eq!([0x65, 0x00, 0xA2, 0x01], rom[..]);
+    }
+
+    #[test]
+    fn reports_the_line() {
+        let error = |source: &str| compile(source).unwrap_err();
+        assert_eq!(
+            AsmError {
+                line: 3,
+                message: "unknown label `nowhere`".to_string()
+            },
+            error(": main\n\njump nowhere")
+        );
+        assert_eq!(
+            "line 2: `a` is defined twice",
+            error(": main\n: a : a").to_string()
+        );
+        assert_eq!(
+            "line 1: a byte out of range: 256",
+            error(": main v0 := 256").to_string()
+        );
+        assert_eq!(
+            "line 1: `:macro` is not supported",
+            error(": main :macro foo { }").to_string()
+        );
+        assert_eq!(
+            "line 2: `loop` without `again`",
+            error(": main\nloop\nclear").to_string()
+        );
+        assert_eq!(
+            "line 1: `else` without `if ... begin`",
+            error(": main else").to_string()
+        );
+        assert_eq!(
+            "line 1: VF can't be compared, it holds the result",
+            error(": main if vf < v1 then clear").to_string()
+        );
+        assert_eq!(
+            "line 1: `far` is past 0xFFF, use `i := long`",
+            error(": main i := far :org 0x1000 : far").to_string()
+        );
+        assert_eq!(
+            "line 1: expected a byte, found `--2147483648`",
+            error(": main :byte --2147483648").to_string()
+        );
+        assert_eq!(
+            "line 1: expected a byte, found `-0x-80000000`",
+            error(": main :byte -0x-80000000").to_string()
+        );
+        assert_eq!(
+            "line 1: program runs past the end of memory",
+            error(": main :org 0xFFFF :next foo clear").to_string()
+        );
+    }
+}
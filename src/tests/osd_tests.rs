@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use crate::osd::{Canvas, Osd, MESSAGE_TIMEOUT};
+    use crate::video::{render, VideoSettings};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn draws_text_pixels() {
+        let mut pixels = vec![0u32; 32 * 16];
+        Canvas::new(&mut pixels, 32, 16).draw_text((1, 1), "I", 1, 0xFFFFFF, 1.0);
+        // Top bar of the I is columns 1..=3 of the glyph
+        assert_eq!(&[0, 0, 0xFFFFFF, 0xFFFFFF, 0xFFFFFF, 0], &pixels[32..38]);
+    }
+
+    #[test]
+    fn messages_expire() {
+        let now = Instant::now();
+        let mut osd = Osd::new(1);
+        osd.message("State saved", now);
+        assert_eq!(1, osd.messages(now + Duration::from_secs(1)).count());
+        assert_eq!(0, osd.messages(now + MESSAGE_TIMEOUT).count());
+    }
+
+    #[test]
+    fn hidden_osd_leaves_frame_alone() {
+        let display = [0xFFFFFFFFu32; 64 * 32];
+        let mut frame = vec![0u32; 128 * 64];
+        render(&display, &mut frame, 128, 64, &VideoSettings::default());
+        assert!(frame.iter().all(|pixel| *pixel == 0xFFFFFF));
+        let before = frame.clone();
+
+        let now = Instant::now();
+        let mut osd = Osd::new(1);
+        osd.set_status(vec!["FPS 60".to_string()]);
+        osd.toggle();
+        osd.draw(&mut Canvas::new(&mut frame, 128, 64), now);
+        assert_eq!(before, frame);
+
+        osd.toggle();
+        osd.draw(&mut Canvas::new(&mut frame, 128, 64), now);
+        assert_ne!(before, frame);
+    }
+}
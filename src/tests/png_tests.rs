@@ -0,0 +1,29 @@
+#[cfg(test)]
+mod tests {
+    use crate::png::encode;
+
+    #[test]
+    fn writes_valid_chunks() {
+        let image = encode(&[0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF], 2, 2);
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &image[..8]);
+        // IHDR: 2x2, 8 bit truecolour
+        assert_eq!(b"IHDR", &image[12..16]);
+        assert_eq!([0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0], image[16..29]);
+        // The IEND chunk and its well known CRC close the file
+        assert_eq!(
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82],
+            image[image.len() - 12..]
+        );
+        // Stored deflate data holds the filtered rows verbatim
+        let rows = [0, 0xFF, 0, 0, 0, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert!(image.windows(rows.len()).any(|w| w == rows));
+    }
+
+    #[test]
+    fn splits_large_images_into_blocks() {
+        let pixels = vec![0x123456; 200 * 200];
+        let image = encode(&pixels, 200, 200);
+        // 200 rows of 601 bytes need two stored blocks of at most 65535
+        assert!(image.len() > 200 * 601 + 2 * 5);
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::profiler::{class, Profiler, Subroutine};
+
+    // Calls a one-row sprite drawing subroutine at 0x208 twice, then spins
+    const ROM: [u8; 15] = [
+        0x22, 0x08, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, 0xA2, 0x0E, 0xD0, 0x01, 0x00, 0xEE, 0x80,
+    ];
+
+    fn profile(rom: &[u8], tick_rate: u32) -> (Chip8, Profiler) {
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(tick_rate);
+        chip.load_rom_bytes(rom).unwrap();
+        let mut profiler = Profiler::new(rom.len());
+        profiler.run_frame(&mut chip, [0; 16]);
+        (chip, profiler)
+    }
+
+    #[test]
+    fn runs_frames_like_the_interpreter() {
+        let (profiled, _) = profile(&ROM, 10);
+        let mut plain = Chip8::new();
+        plain.set_tick_rate(10);
+        plain.load_rom_bytes(&ROM).unwrap();
+        plain.run_frame([0; 16]);
+        assert_eq!(plain.save_state(), profiled.save_state());
+    }
+
+    #[test]
+    fn counts_addresses_classes_and_subroutines() {
+        let (chip, profiler) = profile(&ROM, 10);
+        assert_eq!(10, profiler.total());
+        assert_eq!(2, profiler.count(0x204));
+        assert_eq!(2, profiler.count(0x208));
+        assert_eq!(0, profiler.count(0x206));
+        let classes: Vec<_> = profiler.classes().collect();
+        assert_eq!(
+            vec![
+                ("00EE", 2),
+                ("1NNN", 2),
+                ("2NNN", 2),
+                ("ANNN", 2),
+                ("DXYN", 2)
+            ],
+            classes
+        );
+        let subroutine = Subroutine {
+            calls: 2,
+            own: 6,
+            total: 6,
+        };
+        assert_eq!(Some(&subroutine), profiler.subroutines().get(&0x208));
+        assert_eq!("main 4\nmain;sub_208 6\n", profiler.folded());
+
+        let report = profiler.report(&chip);
+        assert!(report.starts_with("10 instructions\n"), "{report}");
+        assert!(
+            report.contains("  0x204              2  20.00%  1204\n"),
+            "{report}"
+        );
+        assert!(
+            report.contains("  2NNN                2  20.00%\n"),
+            "{report}"
+        );
+        assert!(
+            report.contains("  0x208          2            6  60.00%            6  60.00%\n"),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn nested_calls_count_towards_callers() {
+        // 0x206 calls 0x20A, which returns straight away
+        let rom = [
+            0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE,
+        ];
+        let (_, profiler) = profile(&rom, 6);
+        let subroutines = profiler.subroutines();
+        assert_eq!(2, subroutines[&0x206].own);
+        assert_eq!(3, subroutines[&0x206].total);
+        assert_eq!(1, subroutines[&0x20A].total);
+        assert_eq!(
+            "main 3\nmain;sub_206 2\nmain;sub_206;sub_20a 1\n",
+            profiler.folded()
+        );
+    }
+
+    #[test]
+    fn follows_the_machine_stack() {
+        // Calls itself forever; the stack fills and later calls are dropped
+        let (_, profiler) = profile(&[0x22, 0x00], 100);
+        assert_eq!(16, profiler.subroutines()[&0x200].calls);
+        let deepest = profiler
+            .folded()
+            .lines()
+            .map(|l| l.matches(';').count())
+            .max();
+        assert_eq!(Some(16), deepest);
+        assert_eq!(17, profiler.folded().lines().count());
+    }
+
+    #[test]
+    fn maps_rom_coverage() {
+        let (_, profiler) = profile(&ROM, 10);
+        assert_eq!(
+            "ROM coverage: 12 of 15 bytes executed, 1 read as data, 2 untouched\n\
+             0x200 ######..######r\n",
+            profiler.coverage()
+        );
+    }
+
+    #[test]
+    fn classifies_opcodes() {
+        assert_eq!("00E0", class(0x00E0));
+        assert_eq!("8XYE", class(0x812E));
+        assert_eq!("FX65", class(0xF365));
+        assert_eq!("????", class(0x5121));
+        assert_eq!("????", class(0x0123));
+    }
+}
@@ -0,0 +1,203 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::{assemble, disassemble};
+    use crate::chip::{Chip8, MAX_ROM_SIZE};
+    use crate::hex;
+    use crate::movie::mask_to_keys;
+    use crate::profiler::class;
+    use crate::quirks::Quirks;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    ///A machine part way through some program: any PC, I and registers
+    #[derive(Clone, Debug)]
+    struct Start {
+        rom: Vec<u8>,
+        quirks: u8,
+        pc: u16,
+        i: u16,
+        registers: [u8; 16],
+    }
+
+    impl Start {
+        fn machine(&self) -> Chip8 {
+            let mut chip = Chip8::new();
+            chip.load_rom_bytes(&self.rom).unwrap();
+            chip.set_quirks(Quirks::from_bits(self.quirks));
+            chip.set_program_counter(self.pc);
+            chip.set_index_register(self.i);
+            for (x, value) in self.registers.into_iter().enumerate() {
+                chip.set_register(x, value);
+            }
+            chip
+        }
+    }
+
+    fn start() -> impl Strategy<Value = Start> {
+        (
+            vec(any::<u8>(), 1..=MAX_ROM_SIZE),
+            0u8..32,
+            0u16..0x1000,
+            0u16..0x1000,
+            any::<[u8; 16]>(),
+        )
+            .prop_map(|(rom, quirks, pc, i, registers)| Start {
+                rom,
+                quirks,
+                pc,
+                i,
+                registers,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_programs_stay_in_bounds(
+            start in start(),
+            frames in vec(any::<u16>(), 1..30),
+        ) {
+            let mut chip = start.machine();
+            for keys in frames {
+                chip.get_input(mask_to_keys(keys));
+                for _ in 0..chip.tick_rate() {
+                    chip.cycle();
+                    prop_assert!(chip.program_counter() < 0x1000);
+                    prop_assert!(chip.index_register() < 0x1000);
+                }
+                chip.tick_timers();
+            }
+        }
+
+        #[test]
+        fn unknown_opcodes_only_move_the_pc(start in start(), opcode in any::<u16>()) {
+            let mut chip = start.machine();
+            prop_assume!(class(opcode) == "????");
+            let pc = chip.program_counter().min(0xFFD);
+            chip.set_program_counter(pc);
+            chip.write_memory(pc, &opcode.to_be_bytes());
+            let before = chip.clone();
+            chip.cycle();
+
+            prop_assert_eq!(pc + 2, chip.program_counter());
+            prop_assert_eq!(before.index_register(), chip.index_register());
+            prop_assert_eq!(before.registers(), chip.registers());
+            prop_assert_eq!(before.stack(), chip.stack());
+            prop_assert_eq!(before.memory(), chip.memory());
+            prop_assert_eq!(before.display(), chip.display());
+        }
+
+        #[test]
+        fn states_round_trip(start in start(), frames in 0usize..10) {
+            let mut chip = start.machine();
+            for _ in 0..frames {
+                chip.run_frame([0; 16]);
+            }
+            let state = chip.save_state();
+            let mut restored = Chip8::new();
+            restored.load_state(&state).unwrap();
+            prop_assert_eq!(state, restored.save_state());
+        }
+
+        #[test]
+        fn hex_round_trips(bytes in vec(any::<u8>(), 0..64)) {
+            prop_assert_eq!(Some(bytes.clone()), hex::decode(&hex::encode(&bytes)));
+        }
+    }
+
+    // There are few enough opcodes to check them all
+    #[test]
+    fn every_opcode_disassembles_and_reassembles() {
+        for opcode in 0..=0xFFFFu16 {
+            let text = disassemble(opcode);
+            assert_eq!(
+                Ok(opcode.to_be_bytes().to_vec()),
+                assemble(&text),
+                "{opcode:04X} disassembles to `{text}`"
+            );
+            assert_eq!(
+                class(opcode) == "????",
+                text.starts_with("dw"),
+                "{opcode:04X} is `{text}` but its class is {}",
+                class(opcode)
+            );
+        }
+    }
+
+    ///Runs `rom` from `pc` for three frames, with registers and I as given
+    fn survive(rom: &[u8], pc: u16, i: u16, registers: &[(usize, u8)]) -> Chip8 {
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&[0x00]).unwrap();
+        chip.write_memory(pc, rom);
+        chip.set_program_counter(pc);
+        chip.set_index_register(i);
+        for (x, value) in registers {
+            chip.set_register(*x, *value);
+        }
+        for _ in 0..3 {
+            chip.run_frame([0; 16]);
+        }
+        chip
+    }
+
+    // Inputs that used to panic
+
+    #[test]
+    fn instruction_at_the_last_byte() {
+        let mut chip = Chip8::new();
+        chip.load_rom_bytes(&[0x00]).unwrap();
+        // 0xFFF holds 0x12 and the fetch wraps to 0x000 for the 0x34
+        chip.write_memory(0xFFF, &[0x12]);
+        chip.write_memory(0x000, &[0x34]);
+        chip.set_program_counter(0xFFF);
+        chip.cycle();
+        assert_eq!(0x234, chip.program_counter());
+    }
+
+    #[test]
+    fn running_off_the_end() {
+        let chip = survive(&[0x60, 0x01], 0xFFE, 0, &[]);
+        assert!(chip.program_counter() < 0x1000);
+        let chip = survive(&[0x30, 0x00], 0xFFE, 0, &[]);
+        assert!(chip.program_counter() < 0x1000);
+    }
+
+    #[test]
+    fn jump_with_offset_past_the_end() {
+        let mut chip = survive(&[0xBF, 0xFF], 0x200, 0, &[(0, 0xFF)]);
+        chip.set_program_counter(0x200);
+        chip.cycle();
+        assert_eq!(0x0FE, chip.program_counter());
+    }
+
+    #[test]
+    fn index_past_the_end() {
+        let mut chip = survive(&[0xF1, 0x1E], 0x200, 0xFFF, &[(1, 0xFF)]);
+        chip.set_program_counter(0x200);
+        chip.set_index_register(0xFFF);
+        chip.cycle();
+        assert_eq!(0x0FE, chip.index_register());
+        survive(&[0xD0, 0x0F], 0x200, 0xFFA, &[]);
+        survive(&[0xF0, 0x33], 0x200, 0xFFF, &[(0, 255)]);
+        survive(&[0xFF, 0x55], 0x200, 0xFF8, &[]);
+        survive(&[0xFF, 0x65], 0x200, 0xFF8, &[]);
+        survive(&[0xFF, 0x65], 0x200, 0xFFFF, &[]);
+    }
+
+    #[test]
+    fn keys_and_digits_above_f() {
+        survive(&[0xE0, 0x9E, 0xE0, 0xA1], 0x200, 0, &[(0, 0x42)]);
+        let mut chip = survive(&[0xF0, 0x29], 0x200, 0, &[(0, 0x5A)]);
+        chip.set_program_counter(0x200);
+        chip.cycle();
+        assert_eq!(0x050 + 0xA * 5, chip.index_register());
+    }
+
+    #[test]
+    fn call_stack_overflow_and_underflow() {
+        // Calls itself forever
+        let chip = survive(&[0x22, 0x00], 0x200, 0, &[]);
+        assert_eq!(crate::chip::STACK_SIZE, chip.stack().len());
+        let chip = survive(&[0x00, 0xEE], 0x200, 0, &[]);
+        assert!(chip.stack().is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn presets_have_names() {
+        assert_eq!(Some("chip8"), Quirks::chip8().preset_name());
+        assert_eq!(Some("schip"), Quirks::schip().preset_name());
+        assert_eq!(Some("default"), Quirks::default().preset_name());
+        let custom = Quirks {
+            vf_reset: true,
+            ..Quirks::default()
+        };
+        assert_eq!(None, custom.preset_name());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let custom = Quirks {
+            jump_uses_vx: true,
+            clip_sprites: false,
+            ..Quirks::default()
+        };
+        assert_eq!(custom, custom.to_string().parse().unwrap());
+        assert_eq!(Quirks::schip(), "schip".parse().unwrap());
+        assert!("bogus=1".parse::<Quirks>().is_err());
+    }
+}
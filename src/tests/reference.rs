@@ -0,0 +1,219 @@
+//! A deliberately plain CHIP-8 interpreter for differential testing, written
+//! from the instruction table rather than from `chip.rs`. It favours being
+//! obviously right over being fast: one `match` on the opcode's nibbles and no
+//! state beyond what the spec describes.
+//!
+//! Where the spec is silent it makes the same choices `Chip8` documents:
+//! addresses wrap at 4 KiB, a call with a full stack and a return with an
+//! empty one do nothing, flags are written after results, and `FX0A` takes
+//! the lowest key that's down.
+
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, Rng};
+
+const FONT_START: usize = 0x50;
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+pub struct Reference {
+    pub memory: [u8; 4096],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub dt: u8,
+    pub st: u8,
+    ///`display[y][x]`
+    pub display: [[bool; 64]; 32],
+    pub keys: [bool; 16],
+    pub quirks: Quirks,
+    rng: Rng,
+}
+
+impl Reference {
+    ///Takes the same generator as the machine it's compared with
+    pub fn new(rom: &[u8], quirks: Quirks, rng: Rng) -> Self {
+        let mut memory = [0; 4096];
+        memory[FONT_START..FONT_START + FONT.len()].copy_from_slice(&FONT);
+        memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+        Reference {
+            memory,
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            dt: 0,
+            st: 0,
+            display: [[false; 64]; 32],
+            keys: [false; 16],
+            quirks,
+            rng,
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        u16::from_be_bytes([self.read(self.pc), self.read(self.pc + 1)])
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        self.memory[address as usize % 4096]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize % 4096] = value;
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+    }
+
+    ///Executes one instruction
+    pub fn step(&mut self) {
+        let opcode = self.opcode();
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = opcode & 0xF;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+        let mut next = self.pc + 2;
+        let skip = next + 2;
+
+        match (opcode >> 12, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => self.display = [[false; 64]; 32],
+            (0x0, 0x0, 0xE, 0xE) => next = self.stack.pop().unwrap_or(next),
+            (0x1, _, _, _) => next = nnn,
+            (0x2, _, _, _) if self.stack.len() < 16 => {
+                self.stack.push(next);
+                next = nnn;
+            }
+            (0x3, _, _, _) if vx == nn => next = skip,
+            (0x4, _, _, _) if vx != nn => next = skip,
+            (0x5, _, _, 0x0) if vx == vy => next = skip,
+            (0x9, _, _, 0x0) if vx != vy => next = skip,
+            (0x6, _, _, _) => self.v[x] = nn,
+            (0x7, _, _, _) => self.v[x] = vx.wrapping_add(nn),
+            (0x8, _, _, 0x0) => self.v[x] = vy,
+            (0x8, _, _, 0x1..=0x3) => {
+                self.v[x] = match n {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
+            }
+            (0x8, _, _, 0x4) => {
+                let sum = vx as u16 + vy as u16;
+                self.v[x] = sum as u8;
+                self.v[0xF] = (sum > 0xFF) as u8;
+            }
+            (0x8, _, _, 0x5) => {
+                self.v[x] = vx.wrapping_sub(vy);
+                self.v[0xF] = (vx >= vy) as u8;
+            }
+            (0x8, _, _, 0x7) => {
+                self.v[x] = vy.wrapping_sub(vx);
+                self.v[0xF] = (vy >= vx) as u8;
+            }
+            (0x8, _, _, 0x6) => {
+                let value = if self.quirks.shift_uses_vy { vy } else { vx };
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 1;
+            }
+            (0x8, _, _, 0xE) => {
+                let value = if self.quirks.shift_uses_vy { vy } else { vx };
+                self.v[x] = value << 1;
+                self.v[0xF] = value >> 7;
+            }
+            (0xA, _, _, _) => self.i = nnn,
+            (0xB, _, _, _) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    vx
+                } else {
+                    self.v[0]
+                };
+                next = nnn + offset as u16;
+            }
+            (0xC, _, _, _) => self.v[x] = self.rng.next_byte() & nn,
+            (0xD, _, _, _) => self.draw(vx as usize % 64, vy as usize % 32, n),
+            (0xE, _, 0x9, 0xE) if self.keys[vx as usize & 0xF] => next = skip,
+            (0xE, _, 0xA, 0x1) if !self.keys[vx as usize & 0xF] => next = skip,
+            (0xF, _, 0x0, 0x7) => self.v[x] = self.dt,
+            (0xF, _, 0x0, 0xA) => match self.keys.iter().position(|down| *down) {
+                Some(key) => self.v[x] = key as u8,
+                None => next = self.pc,
+            },
+            (0xF, _, 0x1, 0x5) => self.dt = vx,
+            (0xF, _, 0x1, 0x8) => self.st = vx,
+            (0xF, _, 0x1, 0xE) => self.i = (self.i + vx as u16) % 4096,
+            (0xF, _, 0x2, 0x9) => self.i = FONT_START as u16 + (vx & 0xF) as u16 * 5,
+            (0xF, _, 0x3, 0x3) => {
+                self.write(self.i, vx / 100);
+                self.write(self.i + 1, vx / 10 % 10);
+                self.write(self.i + 2, vx % 10);
+            }
+            (0xF, _, 0x5, 0x5) => {
+                for r in 0..=x {
+                    self.write(self.i + r as u16, self.v[r]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = (self.i + x as u16 + 1) % 4096;
+                }
+            }
+            (0xF, _, 0x6, 0x5) => {
+                for r in 0..=x {
+                    self.v[r] = self.read(self.i + r as u16);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = (self.i + x as u16 + 1) % 4096;
+                }
+            }
+            // Everything else, including skips not taken, does nothing
+            _ => {}
+        }
+        self.pc = next % 4096;
+    }
+
+    fn draw(&mut self, x: usize, y: usize, height: u16) {
+        self.v[0xF] = 0;
+        for row in 0..height {
+            let bits = self.read(self.i + row);
+            for col in 0..8 {
+                if bits & 0x80 >> col == 0 {
+                    continue;
+                }
+                let (mut px, mut py) = (x + col, y + row as usize);
+                if px >= 64 || py >= 32 {
+                    if self.quirks.clip_sprites {
+                        continue;
+                    }
+                    px %= 64;
+                    py %= 32;
+                }
+                if self.display[py][px] {
+                    self.v[0xF] = 1;
+                }
+                self.display[py][px] ^= true;
+            }
+        }
+    }
+}
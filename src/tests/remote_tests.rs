@@ -0,0 +1,289 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::{Chip8, Engine};
+    use crate::hex;
+    use crate::remote::{Address, RemoteServer, Session};
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+
+    // Draw font 0 at (V0, V0), then wait until key 5 is held and set V1
+    const ROM: &str = "a050d0056205e29e12066101120c";
+
+    fn request(session: &mut Session, chip: &mut Chip8, line: &str) -> Value {
+        let response = session.handle(line, chip, &mut |chip, keys| chip.run_frame(keys));
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn loaded() -> (Session, Chip8) {
+        let mut session = Session::default();
+        let mut chip = Chip8::new();
+        let reply = request(
+            &mut session,
+            &mut chip,
+            &format!(r#"{{"cmd": "load_rom", "data": "{ROM}"}}"#),
+        );
+        assert_eq!(Value::Bool(true), reply["ok"]);
+        (session, chip)
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!("00a0ff", hex::encode(&[0x00, 0xA0, 0xFF]));
+        assert_eq!(Some(vec![0x00, 0xA0, 0xFF]), hex::decode("00A0ff"));
+        assert_eq!(None, hex::decode("abc"));
+        assert_eq!(None, hex::decode("zz"));
+    }
+
+    #[test]
+    fn parses_addresses() {
+        let tcp = "127.0.0.1:4000".parse().unwrap();
+        assert_eq!(Address::Tcp(tcp), "tcp:127.0.0.1:4000".parse().unwrap());
+        assert_eq!(Address::Tcp(tcp), "127.0.0.1:4000".parse().unwrap());
+        #[cfg(unix)]
+        assert_eq!(
+            Address::Unix("/tmp/c8.sock".into()),
+            "unix:/tmp/c8.sock".parse().unwrap()
+        );
+        assert!("nowhere".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn load_rom_keeps_the_engine() {
+        for engine in Engine::ALL {
+            let mut session = Session::default();
+            let mut chip = Chip8::new();
+            chip.set_engine(*engine);
+            let load = format!(r#"{{"cmd": "load_rom", "data": "{ROM}"}}"#);
+            request(&mut session, &mut chip, &load);
+            assert_eq!(*engine, chip.engine());
+
+            request(&mut session, &mut chip, r#"{"cmd": "press", "key": 5}"#);
+            request(&mut session, &mut chip, r#"{"cmd": "step", "frames": 2}"#);
+            let registers = request(&mut session, &mut chip, r#"{"cmd": "read_registers"}"#);
+            assert_eq!(1, registers["v"][1], "{engine:?}");
+        }
+    }
+
+    #[test]
+    fn steps_with_remote_keys() {
+        let (mut session, mut chip) = loaded();
+        let reply = request(&mut session, &mut chip, r#"{"cmd": "step", "frames": 3}"#);
+        assert_eq!(3, reply["frames"]);
+        let registers = request(&mut session, &mut chip, r#"{"cmd": "read_registers"}"#);
+        assert_eq!(0, registers["v"][1]);
+
+        request(&mut session, &mut chip, r#"{"cmd": "press", "key": 5}"#);
+        assert_eq!(1, session.keys()[5]);
+        request(&mut session, &mut chip, r#"{"cmd": "step"}"#);
+        let registers = request(&mut session, &mut chip, r#"{"cmd": "read_registers"}"#);
+        assert_eq!(1, registers["v"][1]);
+        assert_eq!(0x50, registers["i"]);
+
+        request(&mut session, &mut chip, r#"{"cmd": "release", "key": 5}"#);
+        assert_eq!([0; 16], session.keys());
+    }
+
+    #[test]
+    fn reads_and_writes_memory_and_registers() {
+        let (mut session, mut chip) = loaded();
+        let reply = request(
+            &mut session,
+            &mut chip,
+            r#"{"cmd": "read_memory", "address": 512, "length": 4}"#,
+        );
+        assert_eq!("a050d005", reply["data"]);
+
+        request(
+            &mut session,
+            &mut chip,
+            r#"{"cmd": "write_memory", "address": 3840, "data": "c0ffee"}"#,
+        );
+        assert_eq!([0xC0, 0xFF, 0xEE], chip.memory()[0xF00..0xF03]);
+        let reply = request(
+            &mut session,
+            &mut chip,
+            r#"{"cmd": "write_memory", "address": 4095, "data": "0102"}"#,
+        );
+        assert_eq!(Value::Bool(false), reply["ok"]);
+
+        for (name, value) in [("vA", 7), ("i", 0x300), ("pc", 0x204), ("dt", 9), ("st", 4)] {
+            let line =
+                format!(r#"{{"cmd": "write_register", "name": "{name}", "value": {value}}}"#);
+            assert_eq!(
+                Value::Bool(true),
+                request(&mut session, &mut chip, &line)["ok"]
+            );
+        }
+        assert_eq!(7, chip.registers()[0xA]);
+        assert_eq!(0x300, chip.index_register());
+        assert_eq!(0x204, chip.program_counter());
+        assert_eq!((9, 4), (chip.delay_timer(), chip.sound_timer()));
+    }
+
+    #[test]
+    fn screenshots_and_states() {
+        let (mut session, mut chip) = loaded();
+        request(&mut session, &mut chip, r#"{"cmd": "step"}"#);
+        let shot = request(&mut session, &mut chip, r#"{"cmd": "screenshot"}"#);
+        assert_eq!(32, shot["rows"].as_array().unwrap().len());
+        assert_eq!(
+            Value::from(format!("#..#{}", ".".repeat(60))),
+            shot["rows"][1]
+        );
+
+        let saved = request(&mut session, &mut chip, r#"{"cmd": "save_state"}"#);
+        let state = saved["state"].as_str().unwrap().to_string();
+        request(
+            &mut session,
+            &mut chip,
+            r#"{"cmd": "write_register", "name": "v0", "value": 99}"#,
+        );
+        let line = format!(r#"{{"cmd": "load_state", "state": "{state}"}}"#);
+        assert_eq!(
+            Value::Bool(true),
+            request(&mut session, &mut chip, &line)["ok"]
+        );
+        assert_eq!(0, chip.registers()[0]);
+
+        let bad = request(
+            &mut session,
+            &mut chip,
+            r#"{"cmd": "load_state", "state": "00"}"#,
+        );
+        assert_eq!("not a save state", bad["error"]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let (mut session, mut chip) = loaded();
+        for line in [
+            "not json",
+            r#"{"frames": 1}"#,
+            r#"{"cmd": "dance"}"#,
+            r#"{"cmd": "press", "key": 16}"#,
+            r#"{"cmd": "write_register", "name": "vg", "value": 1}"#,
+            r#"{"cmd": "write_register", "name": "i", "value": 4096}"#,
+            r#"{"cmd": "write_register", "name": "pc", "value": 4096}"#,
+            r#"{"cmd": "load_rom"}"#,
+            r#"{"cmd": "load_rom", "data": ""}"#,
+        ] {
+            let reply = request(&mut session, &mut chip, line);
+            assert_eq!(Value::Bool(false), reply["ok"], "{line}");
+            assert!(reply["error"].is_string());
+        }
+        assert!(!session.quit_requested());
+        request(&mut session, &mut chip, r#"{"cmd": "quit"}"#);
+        assert!(session.quit_requested());
+    }
+
+    /// Plays the client side of a script over `stream`, returning the replies
+    fn client<S: std::io::Read + Write>(mut stream: S, lines: &[&str]) -> Vec<Value> {
+        let mut replies = Vec::new();
+        for line in lines {
+            stream.write_all(format!("{line}\n").as_bytes()).unwrap();
+            let mut reader = BufReader::new(&mut stream);
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            replies.push(serde_json::from_str(&reply).unwrap());
+        }
+        replies
+    }
+
+    const SCRIPT: [&str; 4] = [
+        r#"{"cmd": "press", "key": 5}"#,
+        r#"{"cmd": "step", "frames": 2}"#,
+        r#"{"cmd": "read_registers"}"#,
+        r#"{"cmd": "quit"}"#,
+    ];
+
+    fn serve(server: &mut RemoteServer, chip: &mut Chip8) {
+        while !server.session().quit_requested() {
+            server
+                .poll(chip, |chip, keys| chip.run_frame(keys))
+                .unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn serves_tcp_clients() {
+        let mut server = RemoteServer::bind(&"tcp:127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.local_addr().unwrap();
+        let (_, mut chip) = loaded();
+
+        let script = thread::spawn(move || client(TcpStream::connect(address).unwrap(), &SCRIPT));
+        serve(&mut server, &mut chip);
+        let replies = script.join().unwrap();
+        assert!(replies.iter().all(|reply| reply["ok"] == Value::Bool(true)));
+        assert_eq!(1, replies[2]["v"][1]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_unix_clients() {
+        use std::os::unix::net::UnixStream;
+        let path = std::env::temp_dir().join(format!("chip8-remote-{}.sock", std::process::id()));
+        let mut server = RemoteServer::bind(&Address::Unix(path.clone())).unwrap();
+        let (_, mut chip) = loaded();
+
+        let socket = path.clone();
+        let script = thread::spawn(move || client(UnixStream::connect(socket).unwrap(), &SCRIPT));
+        serve(&mut server, &mut chip);
+        let replies = script.join().unwrap();
+        assert_eq!(1, replies[2]["v"][1]);
+
+        drop(server);
+        assert!(!path.exists(), "socket file cleaned up");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_replaces_stale_sockets() {
+        use std::os::unix::net::UnixListener;
+        let path = std::env::temp_dir().join(format!("chip8-stale-{}.sock", std::process::id()));
+
+        std::fs::create_dir(&path).unwrap();
+        assert!(RemoteServer::bind(&Address::Unix(path.clone())).is_err());
+        assert!(path.is_dir(), "directory left alone");
+        std::fs::remove_dir(&path).unwrap();
+
+        // Dropping a listener leaves its socket file behind
+        drop(UnixListener::bind(&path).unwrap());
+        let server = RemoteServer::bind(&Address::Unix(path.clone())).unwrap();
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn drops_clients_that_never_end_a_line() {
+        let mut server = RemoteServer::bind(&"tcp:127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.local_addr().unwrap();
+        let mut chip = Chip8::new();
+
+        let script = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            // The server may hang up before it has read everything
+            let _ = stream.write_all(&[b'x'; 0x20000]);
+            let mut byte = [0];
+            std::io::Read::read(&mut stream, &mut byte)
+        });
+        while !script.is_finished() {
+            server
+                .poll(&mut chip, |chip, keys| chip.run_frame(keys))
+                .unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let read = script.join().unwrap();
+        assert!(!matches!(read, Ok(n) if n > 0), "client hung up on");
+    }
+
+    #[test]
+    fn clamps_long_steps() {
+        let (mut session, mut chip) = loaded();
+        let line = format!(r#"{{"cmd": "step", "frames": {}}}"#, u32::MAX);
+        assert_eq!(3600, request(&mut session, &mut chip, &line)["frames"]);
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::rng::{RandomSource, Rng, RngMode};
+
+    fn bytes(rng: &mut Rng, count: usize) -> Vec<u8> {
+        (0..count).map(|_| rng.next_byte()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(1234, RngMode::Xorshift);
+        let mut b = Rng::new(1234, RngMode::Xorshift);
+        assert_eq!(bytes(&mut a, 64), bytes(&mut b, 64));
+    }
+
+    #[test]
+    fn different_seed_different_sequence() {
+        let mut a = Rng::new(1, RngMode::Xorshift);
+        let mut b = Rng::new(2, RngMode::Xorshift);
+        assert_ne!(bytes(&mut a, 64), bytes(&mut b, 64));
+    }
+
+    #[test]
+    fn cloned_rng_continues_identically() {
+        let mut a = Rng::new(99, RngMode::Vip);
+        bytes(&mut a, 10);
+        let mut b = a.clone();
+        assert_eq!(bytes(&mut a, 32), bytes(&mut b, 32));
+    }
+
+    #[test]
+    fn vip_mode_reads_interpreter_page() {
+        let mut rng = Rng::new(0x1233, RngMode::Vip);
+        // R9 0x1233 -> 0x1234, the byte at 0x134 is 0x06: 0x06 + 0x12 = 0x18,
+        // plus 0x18 shifted right is 0x24
+        assert_eq!(0x24, rng.next_byte());
+        assert_eq!(0x2434, rng.state());
+        assert_eq!(0x54, rng.next_byte());
+        assert_eq!(0x5A, rng.next_byte());
+    }
+
+    #[test]
+    fn vip_mode_ignores_the_rom() {
+        // Whatever sits in emulated memory, the VIP read its own code
+        let mut a = Chip8::with_rng(Rng::new(5, RngMode::Vip));
+        let mut b = Chip8::with_rng(Rng::new(5, RngMode::Vip));
+        a.load_rom_bytes(&[0xC0, 0xFF]).unwrap();
+        b.load_rom_bytes(&[0xC0, 0xFF, 0xAA, 0x55]).unwrap();
+        b.write_memory(0x000, &[0xFF; 0x200]);
+        a.cycle();
+        b.cycle();
+        assert_eq!(a.registers()[0], b.registers()[0]);
+    }
+
+    #[test]
+    fn cloned_chip_keeps_rng_state() {
+        let chip = Chip8::with_rng(Rng::new(7, RngMode::Xorshift));
+        assert_eq!(chip.rng(), chip.clone().rng());
+        assert_eq!(7, chip.rng().seed());
+    }
+
+    struct Constant(u8);
+
+    impl RandomSource for Constant {
+        fn next_byte(&mut self) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn custom_source_feeds_cxnn() {
+        let mut chip = Chip8::with_rng(Constant(0xA5));
+        // C0F0 C1FF
+        chip.load_rom_bytes(&[0xC0, 0xF0, 0xC1, 0xFF]).unwrap();
+        chip.cycle();
+        chip.cycle();
+        assert_eq!([0xA0, 0xA5], chip.registers()[..2]);
+    }
+}
@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::chip::{Chip8, DEFAULT_TICK_RATE};
+    use crate::movie::sha1_hex;
+    use crate::quirks::Quirks;
+    use crate::romdb::Database;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    const PLATFORMS: &str = r#"[
+        {
+            "id": "originalChip8",
+            "name": "Cosmac VIP",
+            "defaultTickrate": 15,
+            "quirks": {
+                "shift": false, "memoryIncrementByX": false,
+                "memoryLeaveIUnchanged": false, "wrap": false,
+                "jump": false, "vblank": true, "logic": true
+            }
+        },
+        {
+            "id": "superchip",
+            "name": "SUPER-CHIP 1.1",
+            "defaultTickrate": 30,
+            "quirks": {
+                "shift": true, "memoryIncrementByX": false,
+                "memoryLeaveIUnchanged": true, "wrap": false,
+                "jump": true, "vblank": false, "logic": false
+            }
+        }
+    ]"#;
+
+    const PROGRAMS: &str = r##"[
+        {
+            "title": "Pong",
+            "authors": ["Paul Vervalin"],
+            "release": "1990",
+            "roms": {
+                "AAAA": {
+                    "file": "pong.ch8",
+                    "platforms": ["originalChip8"],
+                    "keys": { "up": 1, "down": 4, "player2Up": 12 },
+                    "colors": { "pixels": ["#101010", "#e0c080"], "buzzer": "#ffffff" }
+                }
+            }
+        },
+        {
+            "title": "Blinky",
+            "description": "Pac-Man",
+            "roms": {
+                "bbbb": {
+                    "file": "blinky.ch8",
+                    "platforms": ["superchip", "originalChip8"],
+                    "quirkyPlatforms": { "superchip": { "wrap": true } },
+                    "tickrate": 40
+                },
+                "cccc": { "file": "blinky-alt.ch8", "platforms": ["megachip8"] }
+            }
+        }
+    ]"##;
+
+    const HASHES: &str = r#"{ "AAAA": 0, "bbbb": 1, "cccc": 1 }"#;
+
+    fn database() -> Database {
+        Database::parse(PROGRAMS, HASHES, PLATFORMS).unwrap()
+    }
+
+    #[test]
+    fn platform_quirks_match_the_presets() {
+        let db = database();
+        let pong = db.get("aaaa").unwrap();
+        assert_eq!(Some(Quirks::chip8()), pong.quirks);
+        assert_eq!(Some(15), pong.tick_rate);
+        let blinky = db.get("BBBB").unwrap();
+        let wrapping = Quirks {
+            clip_sprites: false,
+            ..Quirks::schip()
+        };
+        assert_eq!(Some(wrapping), blinky.quirks);
+        assert_eq!(Some(40), blinky.tick_rate);
+    }
+
+    #[test]
+    fn reads_the_rest_of_an_entry() {
+        let db = database();
+        assert_eq!(3, db.len());
+        let pong = db.get("aaaa").unwrap();
+        assert_eq!("Pong", pong.title);
+        assert_eq!(vec!["Paul Vervalin".to_string()], pong.authors);
+        assert_eq!(Some("1990"), pong.release.as_deref());
+        assert_eq!(Some([0x101010, 0xE0C080]), pong.colors);
+        assert_eq!(
+            vec![
+                ("down".to_string(), 4),
+                ("player2Up".to_string(), 12),
+                ("up".to_string(), 1),
+            ],
+            pong.keys
+        );
+
+        // Unknown platforms leave the settings alone
+        let alt = db.get("cccc").unwrap();
+        assert_eq!("Blinky", alt.title);
+        assert_eq!(Some("Pac-Man"), alt.description.as_deref());
+        assert_eq!((None, None), (alt.quirks, alt.tick_rate));
+    }
+
+    #[test]
+    fn info_lists_what_is_known() {
+        let text = database().get("aaaa").unwrap().to_string();
+        let expected = "Title: Pong\n\
+                        Authors: Paul Vervalin\n\
+                        Release: 1990\n\
+                        Platform: originalChip8\n\
+                        Quirks: shift_uses_vy=1 load_store_increments_i=1 jump_uses_vx=0 \
+                        vf_reset=1 clip_sprites=1\n\
+                        Tick rate: 15\n\
+                        Colors: #101010 #e0c080\n\
+                        Keys: down=4 player2Up=C up=1\n\
+                        SHA-1: aaaa";
+        assert_eq!(expected, text);
+    }
+
+    #[test]
+    fn rejects_broken_databases() {
+        assert!(Database::parse("{", HASHES, PLATFORMS).is_err());
+        assert!(Database::parse(PROGRAMS, r#"{ "aaaa": 7 }"#, PLATFORMS).is_err());
+        assert!(Database::parse(PROGRAMS, r#"{ "dddd": 0 }"#, PLATFORMS).is_err());
+        assert!(Database::parse(PROGRAMS, HASHES, "{}").is_err());
+    }
+
+    /// The bundled entries are the programs in `roms/`, with the settings
+    /// their golden test headers give.
+    #[test]
+    fn bundled_database_covers_the_test_programs() {
+        let db = Database::bundled();
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms");
+        let mut programs = 0;
+        for entry in fs::read_dir(&roms).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "asm") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let header = |key: &str| {
+                source
+                    .lines()
+                    .find_map(|line| line.strip_prefix(&format!("; {key}: ")))
+            };
+            let rom = assemble(&source).unwrap();
+            let info = db
+                .lookup(&rom)
+                .unwrap_or_else(|| panic!("{} ({}) is missing", path.display(), sha1_hex(&rom)));
+            let quirks = header("quirks").map_or(Quirks::default(), |q| q.parse().unwrap());
+            let tick_rate = header("tick_rate").map_or(DEFAULT_TICK_RATE, |t| t.parse().unwrap());
+            assert_eq!(Some(quirks), info.quirks, "{}", path.display());
+            assert_eq!(Some(tick_rate), info.tick_rate, "{}", path.display());
+            programs += 1;
+        }
+        assert_eq!(programs, db.len());
+    }
+
+    #[test]
+    fn load_rom_applies_the_database() {
+        let source =
+            fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/collision.asm"))
+                .unwrap();
+        let path = env::temp_dir().join(format!("romdb-{}.ch8", std::process::id()));
+        fs::write(&path, assemble(&source).unwrap()).unwrap();
+
+        let mut chip = Chip8::new();
+        chip.set_quirks(Quirks::chip8());
+        chip.set_tick_rate(3);
+        chip.load_rom(path.to_string_lossy().into_owned()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!chip.quirks().clip_sprites);
+        assert_eq!(10, chip.tick_rate());
+
+        // Bytes loaded directly are left to the caller
+        let mut chip = Chip8::new();
+        chip.set_tick_rate(3);
+        chip.load_rom_bytes(&assemble(&source).unwrap()).unwrap();
+        assert_eq!(3, chip.tick_rate());
+    }
+
+    #[test]
+    fn loads_a_directory() {
+        let dir = env::temp_dir().join(format!("romdb-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in [
+            ("programs.json", PROGRAMS),
+            ("sha1-hashes.json", HASHES),
+            ("platforms.json", PLATFORMS),
+        ] {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let loaded = Database::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(3, loaded.len());
+        assert!(Database::load(env::temp_dir().join("no-such-romdb")).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod tests {
+    use crate::scheduler::Scheduler;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn one_frame_per_interval() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::starting_at(start, 60, 6);
+        let frame = scheduler.frame_duration();
+        assert_eq!(1, scheduler.due_frames(start));
+        assert_eq!(0, scheduler.due_frames(start));
+        assert_eq!(0, scheduler.due_frames(start + frame / 2));
+        assert_eq!(1, scheduler.due_frames(start + frame));
+    }
+
+    #[test]
+    fn no_drift_over_a_second() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::starting_at(start, 60, 6);
+        let total: u32 = (0..1000)
+            .map(|ms| scheduler.due_frames(start + Duration::from_millis(ms)))
+            .sum();
+        assert_eq!(60, total);
+    }
+
+    #[test]
+    fn catches_up_with_a_cap() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::starting_at(start, 60, 6);
+        let frame = scheduler.frame_duration();
+        scheduler.due_frames(start);
+        assert_eq!(3, scheduler.due_frames(start + frame * 3));
+        // A long stall only replays the cap, then carries on from there
+        let later = start + Duration::from_secs(2);
+        assert_eq!(6, scheduler.due_frames(later));
+        assert_eq!(0, scheduler.due_frames(later));
+        assert_eq!(1, scheduler.due_frames(later + frame));
+    }
+
+    #[test]
+    fn measures_speed() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::starting_at(start, 60, 6);
+        scheduler.record_frames(30, start + Duration::from_millis(500));
+        assert_eq!(0.0, scheduler.speed());
+        scheduler.record_frames(30, start + Duration::from_secs(2));
+        assert!((scheduler.speed() - 0.5).abs() < 1e-6);
+    }
+}
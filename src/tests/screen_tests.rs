@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::screen::Screen;
+
+    #[derive(Default)]
+    struct Panel {
+        lit: Vec<(usize, usize)>,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl Screen for Panel {
+        fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+            self.writes += 1;
+            if on {
+                self.lit.push((x, y));
+            }
+        }
+
+        fn flush(&mut self) {
+            self.flushes += 1;
+        }
+    }
+
+    #[test]
+    fn presents_only_changed_frames() {
+        let mut chip = Chip8::new();
+        // Point I at font 1, draw it at (0, 0), then loop on a jump
+        chip.load_rom_bytes(&[0xA0, 0x55, 0xD0, 0x01, 0x12, 0x04])
+            .unwrap();
+        let mut panel = Panel::default();
+
+        assert!(chip.present(&mut panel), "first frame is always sent");
+        assert_eq!((64 * 32, 1), (panel.writes, panel.flushes));
+        assert!(panel.lit.is_empty());
+
+        chip.run_frame([0; 16]);
+        panel = Panel::default();
+        assert!(chip.present(&mut panel));
+        // Top row of the 1 glyph is 0x20
+        assert_eq!(vec![(2, 0)], panel.lit);
+
+        chip.run_frame([0; 16]);
+        assert!(!chip.present(&mut panel), "nothing drawn since");
+        assert_eq!(1, panel.flushes);
+    }
+
+    #[test]
+    fn stack_is_fixed_depth() {
+        let mut chip = Chip8::new();
+        // 2200 calls itself, nesting one level per cycle
+        chip.load_rom_bytes(&[0x22, 0x00]).unwrap();
+        for depth in 1..=16 {
+            chip.cycle();
+            assert_eq!(depth, chip.stack().len());
+        }
+        assert!(chip.stack().iter().all(|a| *a == 0x202));
+    }
+}
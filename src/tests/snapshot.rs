@@ -0,0 +1,96 @@
+//! Everything an instruction can change, taken from a machine or from the
+//! reference interpreter, so harnesses can say field by field where two
+//! disagree.
+
+use crate::chip::Chip8;
+use crate::rng::RandomSource;
+use crate::tests::reference::Reference;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 16],
+    pub stack: Vec<u16>,
+    pub dt: u8,
+    pub st: u8,
+    pub memory: Vec<u8>,
+    ///Row by row, `true` where a pixel is lit
+    pub display: Vec<bool>,
+}
+
+impl State {
+    pub fn of<R: RandomSource>(chip: &Chip8<R>) -> Self {
+        State {
+            pc: chip.program_counter(),
+            i: chip.index_register(),
+            v: *chip.registers(),
+            stack: chip.stack().to_vec(),
+            dt: chip.delay_timer(),
+            st: chip.sound_timer(),
+            memory: chip.memory().to_vec(),
+            display: chip.display().iter().map(|pixel| *pixel != 0).collect(),
+        }
+    }
+
+    pub fn of_reference(reference: &Reference) -> Self {
+        State {
+            pc: reference.pc,
+            i: reference.i,
+            v: reference.v,
+            stack: reference.stack.clone(),
+            dt: reference.dt,
+            st: reference.st,
+            memory: reference.memory.to_vec(),
+            display: reference.display.as_flattened().to_vec(),
+        }
+    }
+
+    ///What differs from `actual`, field by field
+    pub fn diff(&self, actual: &State) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut field = |name: &str, want: String, got: String| {
+            if want != got {
+                out.push(format!("{name} should be {want}, is {got}"));
+            }
+        };
+        field(
+            "PC",
+            format!("{:#05x}", self.pc),
+            format!("{:#05x}", actual.pc),
+        );
+        field(
+            "I",
+            format!("{:#05x}", self.i),
+            format!("{:#05x}", actual.i),
+        );
+        for x in 0..16 {
+            field(
+                &format!("V{x:X}"),
+                format!("{:#04x}", self.v[x]),
+                format!("{:#04x}", actual.v[x]),
+            );
+        }
+        field(
+            "stack",
+            format!("{:x?}", self.stack),
+            format!("{:x?}", actual.stack),
+        );
+        field("DT", self.dt.to_string(), actual.dt.to_string());
+        field("ST", self.st.to_string(), actual.st.to_string());
+        let changed = self.memory.iter().zip(&actual.memory).enumerate();
+        for (address, (want, got)) in changed.filter(|(_, (a, b))| a != b) {
+            field(
+                &format!("[{address:#05x}]"),
+                format!("{want:#04x}"),
+                format!("{got:#04x}"),
+            );
+        }
+        let changed = self.display.iter().zip(&actual.display).enumerate();
+        for (i, (want, got)) in changed.filter(|(_, (a, b))| a != b) {
+            let (x, y) = (i % 64, i / 64);
+            field(&format!("pixel {x},{y}"), want.to_string(), got.to_string());
+        }
+        out
+    }
+}
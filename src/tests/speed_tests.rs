@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use crate::speed::SpeedControl;
+
+    #[test]
+    fn pause_and_advance() {
+        let mut speed = SpeedControl::new(4, 4);
+        assert_eq!(1, speed.frames_to_run());
+        speed.toggle_pause();
+        assert_eq!(0, speed.frames_to_run());
+        speed.advance_frame();
+        assert_eq!(1, speed.frames_to_run());
+        assert_eq!(0, speed.frames_to_run());
+        speed.toggle_pause();
+        speed.advance_frame();
+        assert_eq!(1, speed.frames_to_run());
+    }
+
+    #[test]
+    fn fast_forward_and_slow_motion() {
+        let mut speed = SpeedControl::new(3, 2);
+        speed.set_fast_forward(true);
+        assert_eq!(3, speed.frames_to_run());
+        speed.set_fast_forward(false);
+        speed.toggle_slow_motion();
+        let frames: Vec<u32> = (0..4).map(|_| speed.frames_to_run()).collect();
+        assert_eq!(vec![0, 1, 0, 1], frames);
+        assert_eq!("Slow motion 1/2", speed.label());
+    }
+
+    #[test]
+    fn uncapped_only_while_held() {
+        let mut speed = SpeedControl::new(0, 2);
+        assert!(!speed.uncapped());
+        speed.set_fast_forward(true);
+        assert!(speed.uncapped());
+        speed.toggle_pause();
+        assert!(!speed.uncapped());
+    }
+}
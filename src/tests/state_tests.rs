@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::{Chip8, StateError, STATE_SIZE};
+    use crate::quirks::Quirks;
+    use crate::rng::{Rng, RngMode};
+
+    // Draw font 0, call a subroutine that sets the timers and a random V2
+    const ROM: [u8; 16] = [
+        0xA0, 0x50, 0xD0, 0x05, 0x22, 0x08, 0x12, 0x06, 0x60, 0x20, 0xF0, 0x15, 0xF0, 0x18, 0xC2,
+        0xFF,
+    ];
+
+    fn running_chip() -> Chip8 {
+        let mut chip = Chip8::with_rng(Rng::new(99, RngMode::Vip));
+        chip.set_quirks(Quirks::chip8());
+        chip.set_tick_rate(7);
+        chip.load_rom_bytes(&ROM).unwrap();
+        chip.run_frame([0; 16]);
+        chip
+    }
+
+    #[test]
+    fn round_trip_restores_everything() {
+        let chip = running_chip();
+        let state = chip.save_state();
+
+        let mut restored = Chip8::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(state, restored.save_state());
+        assert_eq!(chip.display(), restored.display());
+        assert_eq!(chip.rng(), restored.rng());
+        assert_eq!(chip.stack(), restored.stack());
+        assert_eq!(Quirks::chip8(), restored.quirks());
+        assert_eq!(7, restored.tick_rate());
+    }
+
+    #[test]
+    fn restored_machine_runs_identically() {
+        let mut chip = running_chip();
+        let mut restored = Chip8::new();
+        restored.load_state(&chip.save_state()).unwrap();
+        for _ in 0..30 {
+            chip.run_frame([0; 16]);
+            restored.run_frame([0; 16]);
+        }
+        assert_eq!(chip.save_state(), restored.save_state());
+    }
+
+    #[test]
+    fn size_doesnt_depend_on_call_depth() {
+        let mut chip = running_chip();
+        assert_eq!(1, chip.stack().len());
+        assert_eq!(STATE_SIZE, chip.save_state().len());
+        // Return from the subroutine
+        chip.load_rom_bytes(&[0x00, 0xEE]).unwrap();
+        chip.set_program_counter(0x200);
+        chip.cycle();
+        assert!(chip.stack().is_empty());
+        assert_eq!(STATE_SIZE, chip.save_state().len());
+        assert_eq!(STATE_SIZE, Chip8::new().save_state().len());
+    }
+
+    #[test]
+    fn rejects_bad_states() {
+        let mut chip = running_chip();
+        let before = chip.save_state();
+
+        assert_eq!(Err(StateError::NotAState), chip.load_state(b"nope"));
+        let mut truncated = before.clone();
+        truncated.truncate(100);
+        assert_eq!(Err(StateError::Truncated), chip.load_state(&truncated));
+        let mut future = before.clone();
+        future[4] = 99;
+        assert_eq!(
+            Err(StateError::UnsupportedVersion(99)),
+            chip.load_state(&future)
+        );
+        assert_eq!(before, chip.save_state());
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use crate::chip::Chip8;
+    use crate::tui::RELEASE_TIMEOUT;
+    use crate::tui::{keypad_index, register_panel, render, TerminalKeypad, TuiStyle};
+    use std::time::{Duration, Instant};
+
+    fn strip_escapes(line: &str) -> String {
+        let mut out = String::new();
+        let mut in_escape = false;
+        for c in line.chars() {
+            match c {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if in_escape => {}
+                _ => out.push(c),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn braille_packs_2x4_cells() {
+        let mut display = [0u32; 64 * 32];
+        // Left column of the first cell plus the bottom right dot
+        for y in 0..4 {
+            display[y * 64] = 0xFFFFFFFF;
+        }
+        display[3 * 64 + 1] = 0xFFFFFFFF;
+        let lines = render(&display, TuiStyle::Braille);
+        assert_eq!(8, lines.len());
+        let text = strip_escapes(&lines[0]);
+        assert_eq!(32, text.chars().count());
+        assert_eq!(Some('\u{28C7}'), text.chars().next());
+        assert_eq!(Some('\u{2800}'), text.chars().nth(1));
+    }
+
+    #[test]
+    fn half_blocks_only_recolour_on_change() {
+        let display = [0u32; 64 * 32];
+        let lines = render(&display, TuiStyle::HalfBlock);
+        assert_eq!(16, lines.len());
+        assert_eq!(1, lines[0].matches("\x1b[38;2").count());
+        assert_eq!(64, strip_escapes(&lines[0]).chars().count());
+    }
+
+    #[test]
+    fn keys_release_after_timeout() {
+        let now = Instant::now();
+        let mut keypad = TerminalKeypad::default();
+        let key = keypad_index('w').unwrap();
+        keypad.press(key, now);
+        assert_eq!(1, keypad.keys(now)[0x5]);
+        assert_eq!(0, keypad.keys(now + RELEASE_TIMEOUT)[0x5]);
+
+        keypad.hold(key);
+        assert_eq!(1, keypad.keys(now + Duration::from_secs(5))[0x5]);
+        keypad.release(key);
+        assert_eq!(0, keypad.keys(now)[0x5]);
+    }
+
+    #[test]
+    fn panel_shows_registers() {
+        let panel = register_panel(&Chip8::new());
+        assert_eq!("PC 0200  I 0000", panel[0]);
+        assert_eq!("VC 00  VD 00  VE 00  VF 00", panel[5]);
+    }
+}
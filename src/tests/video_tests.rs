@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use crate::video::{render, viewport, Filter, VideoSettings, Viewport};
+
+    #[test]
+    fn integer_viewport_is_centred() {
+        // 3x fits 200x100, leaving 8 px either side and 2 px top and bottom
+        let view = viewport(208, 100, Filter::Integer);
+        let expected = Viewport {
+            x: 8,
+            y: 2,
+            width: 192,
+            height: 96,
+        };
+        assert_eq!(expected, view);
+    }
+
+    #[test]
+    fn smooth_viewport_keeps_aspect() {
+        let view = viewport(300, 300, Filter::Smooth);
+        let expected = Viewport {
+            x: 0,
+            y: 75,
+            width: 300,
+            height: 150,
+        };
+        assert_eq!(expected, view);
+    }
+
+    #[test]
+    fn render_letterboxes_in_border_colour() {
+        let mut display = [0u32; 64 * 32];
+        display[0] = 0xFFFFFF;
+        let settings = VideoSettings {
+            border: 0x112233,
+            ..VideoSettings::default()
+        };
+        let (width, height) = (130, 70);
+        let mut out = vec![0u32; width * height];
+        render(&display, &mut out, width, height, &settings);
+
+        // 2x image at (1, 3): border, then the lit 2x2 block, then black
+        let row = &out[3 * width..][..5];
+        assert_eq!(&[0x112233, 0xFFFFFF, 0xFFFFFF, 0, 0], row);
+        assert_eq!(0x112233, out[0]);
+    }
+
+    #[test]
+    fn render_uses_the_palette() {
+        let mut display = [0u32; 64 * 32];
+        display[0] = 0xFFFFFFFF;
+        let settings = VideoSettings {
+            palette: [0x101010, 0xE0C080],
+            ..VideoSettings::default()
+        };
+        for filter in [Filter::Integer, Filter::Smooth] {
+            let mut out = vec![0u32; 64 * 32];
+            render(
+                &display,
+                &mut out,
+                64,
+                32,
+                &VideoSettings { filter, ..settings },
+            );
+            // Under Smooth the second output pixel is a blend of the first two
+            assert_eq!((0xE0C080, 0x101010), (out[0], out[2]), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn grid_lines_between_pixels() {
+        let display = [0xFFFFFFu32; 64 * 32];
+        let settings = VideoSettings {
+            grid: true,
+            grid_color: 0x010101,
+            ..VideoSettings::default()
+        };
+        let (width, height) = (256, 128);
+        let mut out = vec![0u32; width * height];
+        render(&display, &mut out, width, height, &settings);
+        assert_eq!(&[0xFFFFFF, 0xFFFFFF, 0xFFFFFF, 0x010101], &out[..4]);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::chip::Chip8;
+use crate::scheduler::Scheduler;
+use crate::video::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, terminal};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Most terminals only send key presses, repeating them while a key is held.
+/// A key counts as released once no repeat has arrived for this long, which
+/// has to cover the initial delay before auto-repeat kicks in.
+pub const RELEASE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TuiStyle {
+    ///One character per 1x2 pixels using ▀ with fore and background colours
+    HalfBlock,
+    ///One character per 2x4 pixels using braille dots
+    Braille,
+}
+
+///Keyboard character to CHIP-8 key, same layout as the window frontend
+pub fn keypad_index(c: char) -> Option<usize> {
+    let key = match c.to_ascii_lowercase() {
+        '1' => 0x1,
+        '2' => 0x2,
+        '3' => 0x3,
+        '4' => 0xC,
+        'q' => 0x4,
+        'w' => 0x5,
+        'e' => 0x6,
+        'r' => 0xD,
+        'a' => 0x7,
+        's' => 0x8,
+        'd' => 0x9,
+        'f' => 0xE,
+        'z' => 0xA,
+        'x' => 0x0,
+        'c' => 0xB,
+        'v' => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Keypad state built from terminal key events. Keys are released either
+/// by a real release event, when the terminal reports them, or after
+/// `RELEASE_TIMEOUT` without a repeat.
+#[derive(Clone, Debug, Default)]
+pub struct TerminalKeypad {
+    last_seen: [Option<Instant>; 16],
+    held: [bool; 16],
+}
+
+impl TerminalKeypad {
+    pub fn press(&mut self, key: usize, now: Instant) {
+        self.last_seen[key] = Some(now);
+    }
+
+    ///Held until `release`, for terminals that report releases
+    pub fn hold(&mut self, key: usize) {
+        self.held[key] = true;
+    }
+
+    pub fn release(&mut self, key: usize) {
+        self.held[key] = false;
+        self.last_seen[key] = None;
+    }
+
+    pub fn keys(&self, now: Instant) -> [u8; 16] {
+        let mut keys = [0u8; 16];
+        for (key, pressed) in keys.iter_mut().enumerate() {
+            let recent = self.last_seen[key]
+                .is_some_and(|seen| now.saturating_duration_since(seen) < RELEASE_TIMEOUT);
+            *pressed = (self.held[key] || recent) as u8;
+        }
+        keys
+    }
+}
+
+fn rgb(pixel: u32) -> (u8, u8, u8) {
+    ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+/// Renders the display as lines of text with 24-bit colour escapes. Colour
+/// codes are only emitted when they change, and each line ends reset.
+pub fn render(display: &[u32], style: TuiStyle) -> Vec<String> {
+    let at = |x: usize, y: usize| display[y * DISPLAY_WIDTH + x] & 0xFFFFFF;
+    let mut lines = Vec::new();
+    match style {
+        TuiStyle::HalfBlock => {
+            for row in 0..DISPLAY_HEIGHT / 2 {
+                let mut line = String::new();
+                let mut colors = None;
+                for x in 0..DISPLAY_WIDTH {
+                    let pair = (at(x, row * 2), at(x, row * 2 + 1));
+                    if colors != Some(pair) {
+                        let (tr, tg, tb) = rgb(pair.0);
+                        let (br, bg, bb) = rgb(pair.1);
+                        let _ = write!(line, "\x1b[38;2;{tr};{tg};{tb}m\x1b[48;2;{br};{bg};{bb}m");
+                        colors = Some(pair);
+                    }
+                    line.push('▀');
+                }
+                line.push_str("\x1b[0m");
+                lines.push(line);
+            }
+        }
+        TuiStyle::Braille => {
+            // Dot bit for each (x, y) inside a 2x4 cell
+            const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+            for row in 0..DISPLAY_HEIGHT / 4 {
+                let mut line = String::new();
+                let mut color = None;
+                for col in 0..DISPLAY_WIDTH / 2 {
+                    let mut bits = 0;
+                    let mut lit = 0;
+                    for (dx, column) in DOTS.iter().enumerate() {
+                        for (dy, bit) in column.iter().enumerate() {
+                            let pixel = at(col * 2 + dx, row * 4 + dy);
+                            if pixel != 0 {
+                                bits |= bit;
+                                lit = lit.max(pixel);
+                            }
+                        }
+                    }
+                    if bits != 0 && color != Some(lit) {
+                        let (r, g, b) = rgb(lit);
+                        let _ = write!(line, "\x1b[38;2;{r};{g};{b}m");
+                        color = Some(lit);
+                    }
+                    line.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+                }
+                line.push_str("\x1b[0m");
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+///Register side panel
+pub fn register_panel(chip: &Chip8) -> Vec<String> {
+    let v = chip.registers();
+    let mut lines = vec![
+        format!(
+            "PC {:04X}  I {:04X}",
+            chip.program_counter(),
+            chip.index_register()
+        ),
+        format!(
+            "SP {:<2}  DT {:02X}  ST {:02X}",
+            chip.stack().len(),
+            chip.delay_timer(),
+            chip.sound_timer()
+        ),
+    ];
+    for (row, values) in v.chunks(4).enumerate() {
+        let mut line = String::new();
+        for (i, value) in values.iter().enumerate() {
+            let _ = write!(line, "V{:X} {value:02X}  ", row * 4 + i);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    let stack: Vec<String> = chip.stack().iter().map(|a| format!("{a:04X}")).collect();
+    lines.push(format!("Stack {}", stack.join(" ")));
+    lines
+}
+
+/// Runs `chip` in the terminal until Esc or Ctrl-C. `step` runs one frame
+/// with the given keypad state, so the caller can hook in movies.
+pub fn run(
+    chip: &mut Chip8,
+    style: TuiStyle,
+    mut step: impl FnMut(&mut Chip8, [u8; 16]),
+) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let result = run_loop(chip, style, enhanced, &mut step);
+
+    if enhanced {
+        let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+fn run_loop(
+    chip: &mut Chip8,
+    style: TuiStyle,
+    enhanced: bool,
+    step: &mut impl FnMut(&mut Chip8, [u8; 16]),
+) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let mut keypad = TerminalKeypad::default();
+    let mut scheduler = Scheduler::default();
+    let mut paused = false;
+
+    loop {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.code == KeyCode::Esc || ctrl_c {
+                return Ok(());
+            }
+            let KeyCode::Char(c) = key.code else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Press && c.eq_ignore_ascii_case(&'p') {
+                paused = !paused;
+            }
+            if let Some(index) = keypad_index(c) {
+                match key.kind {
+                    KeyEventKind::Release => keypad.release(index),
+                    _ if enhanced => keypad.hold(index),
+                    _ => keypad.press(index, Instant::now()),
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let mut emulated = 0;
+        for _ in 0..scheduler.due_frames(now) {
+            if !paused {
+                step(chip, keypad.keys(now));
+                emulated += 1;
+            }
+        }
+        scheduler.record_frames(emulated, now);
+
+        let mut screen = String::from("\x1b[H");
+        let display = render(chip.display(), style);
+        let panel = register_panel(chip);
+        for i in 0..display.len().max(panel.len()) {
+            let left = display.get(i).map(String::as_str).unwrap_or("");
+            let right = panel.get(i).map(String::as_str).unwrap_or("");
+            let _ = write!(screen, "{left}  {right}\x1b[K\r\n");
+        }
+        let status = if paused { "Paused" } else { "Running" };
+        let _ = write!(
+            screen,
+            "{status} - ESC exit, P pause - {:.0}%\x1b[K",
+            scheduler.speed() * 100.0
+        );
+        stdout.write_all(screen.as_bytes())?;
+        stdout.flush()?;
+
+        scheduler.wait();
+    }
+}
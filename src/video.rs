@@ -0,0 +1,141 @@
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+///How the display is blown up to the output size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    ///Largest whole multiple that fits, sharp pixels
+    Integer,
+    ///Fills as much as the aspect ratio allows, bilinear filtered
+    Smooth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoSettings {
+    pub filter: Filter,
+    ///Draw a line between CHIP-8 pixels once they are big enough
+    pub grid: bool,
+    ///Colour of the letterbox around the image
+    pub border: u32,
+    pub grid_color: u32,
+    ///Unlit and lit pixels
+    pub palette: [u32; 2],
+}
+
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            filter: Filter::Integer,
+            grid: false,
+            border: 0x000000,
+            grid_color: 0x202020,
+            palette: [0x000000, 0xFFFFFF],
+        }
+    }
+}
+
+///Where the image lands inside the output, in output pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Centres a 2:1 image in a `width` x `height` output. Integer scaling
+/// never goes below 1x, so a tiny output shows the top left of the image.
+pub fn viewport(width: usize, height: usize, filter: Filter) -> Viewport {
+    let (w, h) = match filter {
+        Filter::Integer => {
+            let scale = (width / DISPLAY_WIDTH).min(height / DISPLAY_HEIGHT).max(1);
+            (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale)
+        }
+        Filter::Smooth => {
+            let w = width.min(height * DISPLAY_WIDTH / DISPLAY_HEIGHT);
+            (w, w * DISPLAY_HEIGHT / DISPLAY_WIDTH)
+        }
+    };
+    Viewport {
+        x: width.saturating_sub(w) / 2,
+        y: height.saturating_sub(h) / 2,
+        width: w,
+        height: h,
+    }
+}
+
+/// Draws the display into a `width` x `height` output: letterboxed in the
+/// border colour, in the palette's colours, scaled per `settings.filter`,
+/// with optional grid lines.
+pub fn render(
+    display: &[u32],
+    out: &mut [u32],
+    width: usize,
+    height: usize,
+    settings: &VideoSettings,
+) {
+    out.fill(settings.border);
+    let view = viewport(width, height, settings.filter);
+    if view.width == 0 || view.height == 0 {
+        return;
+    }
+
+    let cell = view.width / DISPLAY_WIDTH;
+    let grid = settings.grid && cell >= 3;
+    // Display position of an output pixel, in 16.16 fixed point
+    let fixed_x = |ox: usize| ((ox * DISPLAY_WIDTH) << 16) / view.width;
+    let fixed_y = |oy: usize| ((oy * DISPLAY_HEIGHT) << 16) / view.height;
+
+    for oy in 0..view.height.min(height - view.y) {
+        let row = &mut out[(view.y + oy) * width..][..width];
+        let sy = fixed_y(oy);
+        let edge_y = sy >> 16 != fixed_y(oy + 1) >> 16;
+        for ox in 0..view.width.min(width - view.x) {
+            let sx = fixed_x(ox);
+            let edge_x = sx >> 16 != fixed_x(ox + 1) >> 16;
+            row[view.x + ox] = if grid && (edge_x || edge_y) {
+                settings.grid_color
+            } else {
+                match settings.filter {
+                    Filter::Integer => {
+                        paint(display[(sy >> 16) * DISPLAY_WIDTH + (sx >> 16)], settings)
+                    }
+                    Filter::Smooth => bilinear(display, sx, sy, settings),
+                }
+            };
+        }
+    }
+}
+
+///The palette colour for a display pixel
+fn paint(pixel: u32, settings: &VideoSettings) -> u32 {
+    settings.palette[(pixel & 0xFFFFFF != 0) as usize]
+}
+
+///Samples between display pixels at a 16.16 fixed point position
+fn bilinear(display: &[u32], sx: usize, sy: usize, settings: &VideoSettings) -> u32 {
+    // Sample at pixel centres so the edges don't bleed half a pixel
+    let sx = sx.saturating_sub(1 << 15);
+    let sy = sy.saturating_sub(1 << 15);
+    let x0 = (sx >> 16).min(DISPLAY_WIDTH - 1);
+    let y0 = (sy >> 16).min(DISPLAY_HEIGHT - 1);
+    let x1 = (x0 + 1).min(DISPLAY_WIDTH - 1);
+    let y1 = (y0 + 1).min(DISPLAY_HEIGHT - 1);
+    let fx = (sx & 0xFFFF) as u32;
+    let fy = (sy & 0xFFFF) as u32;
+
+    let at = |x: usize, y: usize| paint(display[y * DISPLAY_WIDTH + x], settings);
+    let lerp = |a: u32, b: u32, f: u32| {
+        let mut out = 0;
+        for shift in [0, 8, 16] {
+            let ca = (a >> shift) & 0xFF;
+            let cb = (b >> shift) & 0xFF;
+            let c = (ca * (0x10000 - f) + cb * f) >> 16;
+            out |= c << shift;
+        }
+        out
+    };
+    let top = lerp(at(x0, y0), at(x1, y0), fx);
+    let bottom = lerp(at(x0, y1), at(x1, y1), fx);
+    lerp(top, bottom, fy)
+}
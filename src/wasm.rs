@@ -0,0 +1,165 @@
+//! Plain C ABI exports for `wasm32-unknown-unknown`, wrapped for JavaScript
+//! by `web/chip8.js`. No clock or random source is touched here: the host
+//! picks the seed and calls `chip8_run_frame` at 60 Hz itself.
+
+use crate::chip::Chip8;
+use crate::movie::mask_to_keys;
+use crate::quirks::Quirks;
+use crate::rng::{Rng, RngMode};
+use crate::video::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+pub struct Handle {
+    chip: Chip8,
+    keys: [u8; 16],
+    rgba: Vec<u8>,
+    state: Vec<u8>,
+}
+
+/// Borrows the handle behind a pointer from `chip8_new`.
+///
+/// # Safety
+/// `handle` must come from `chip8_new` and not have been freed.
+unsafe fn handle<'a>(handle: *mut Handle) -> &'a mut Handle {
+    &mut *handle
+}
+
+/// # Safety
+/// `ptr` must point to `len` readable bytes.
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    }
+}
+
+///Scratch memory the host copies ROMs and states into
+#[no_mangle]
+pub extern "C" fn chip8_alloc(len: usize) -> *mut u8 {
+    let mut buffer = vec![0u8; len].into_boxed_slice();
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// # Safety
+/// `ptr` and `len` must come from one `chip8_alloc` call.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_dealloc(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+///New machine, `vip_rng` non-zero for the VIP random source
+#[no_mangle]
+pub extern "C" fn chip8_new(seed: u32, vip_rng: u32) -> *mut Handle {
+    let mode = if vip_rng != 0 {
+        RngMode::Vip
+    } else {
+        RngMode::Xorshift
+    };
+    Box::into_raw(Box::new(Handle {
+        chip: Chip8::with_rng(Rng::new(seed as u64, mode)),
+        keys: [0; 16],
+        rgba: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT * 4],
+        state: Vec::new(),
+    }))
+}
+
+/// # Safety
+/// See `handle`. The pointer is dangling afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip: *mut Handle) {
+    drop(Box::from_raw(chip));
+}
+
+/// Returns 0 when the ROM is empty or too big to fit.
+///
+/// # Safety
+/// See `handle` and `bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip: *mut Handle, ptr: *const u8, len: usize) -> u32 {
+    handle(chip).chip.load_rom_bytes(bytes(ptr, len)).is_ok() as u32
+}
+
+/// Bit N of `mask` set while key N is down, latched at the next frame.
+///
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(chip: *mut Handle, mask: u32) {
+    handle(chip).keys = mask_to_keys(mask as u16);
+}
+
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_tick_rate(chip: *mut Handle, tick_rate: u32) {
+    handle(chip).chip.set_tick_rate(tick_rate);
+}
+
+/// Quirk flags packed as in `Quirks::to_bits`.
+///
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_quirks(chip: *mut Handle, bits: u32) {
+    handle(chip).chip.set_quirks(Quirks::from_bits(bits as u8));
+}
+
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip: *mut Handle) {
+    let handle = handle(chip);
+    handle.chip.run_frame(handle.keys);
+}
+
+/// Non-zero while the sound timer is running.
+///
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip: *mut Handle) -> u32 {
+    (handle(chip).chip.sound_timer() > 0) as u32
+}
+
+/// Converts the display to 64x32 RGBA8 and returns a pointer to it, valid
+/// until the next call on this handle.
+///
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip: *mut Handle) -> *const u8 {
+    let handle = handle(chip);
+    for (rgba, pixel) in handle.rgba.chunks_exact_mut(4).zip(handle.chip.display()) {
+        rgba.copy_from_slice(&[(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8, 0xFF]);
+    }
+    handle.rgba.as_ptr()
+}
+
+/// Takes a snapshot and returns its length; `chip8_state_ptr` points at it.
+///
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip: *mut Handle) -> usize {
+    let handle = handle(chip);
+    handle.state = handle.chip.save_state();
+    handle.state.len()
+}
+
+/// # Safety
+/// See `handle`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_state_ptr(chip: *mut Handle) -> *const u8 {
+    handle(chip).state.as_ptr()
+}
+
+/// Returns 0 and leaves the machine alone if the state is rejected.
+///
+/// # Safety
+/// See `handle` and `bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip: *mut Handle, ptr: *const u8, len: usize) -> u32 {
+    handle(chip).chip.load_state(bytes(ptr, len)).is_ok() as u32
+}
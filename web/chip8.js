@@ -0,0 +1,82 @@
+// Thin wrapper around the exports in src/wasm.rs.
+//
+//   const chip = await Chip8.instantiate(wasmBytes, seed);
+//   chip.loadRom(romBytes);
+//   // every 1/60 s:
+//   chip.setKeys(mask);
+//   chip.runFrame();
+//   ctx.putImageData(new ImageData(chip.framebuffer(), 64, 32), 0, 0);
+
+export const WIDTH = 64;
+export const HEIGHT = 32;
+
+export class Chip8 {
+  static async instantiate(wasmBytes, seed = Math.floor(Math.random() * 2 ** 32), vipRng = false) {
+    const { instance } = await WebAssembly.instantiate(wasmBytes, {});
+    return new Chip8(instance.exports, seed, vipRng);
+  }
+
+  constructor(exports, seed, vipRng) {
+    this.wasm = exports;
+    this.handle = exports.chip8_new(seed >>> 0, vipRng ? 1 : 0);
+  }
+
+  free() {
+    this.wasm.chip8_free(this.handle);
+    this.handle = 0;
+  }
+
+  // Copies `bytes` into wasm memory and calls `fn(ptr, len)`
+  withBytes(bytes, fn) {
+    const ptr = this.wasm.chip8_alloc(bytes.length);
+    new Uint8Array(this.wasm.memory.buffer, ptr, bytes.length).set(bytes);
+    try {
+      return fn(ptr, bytes.length);
+    } finally {
+      this.wasm.chip8_dealloc(ptr, bytes.length);
+    }
+  }
+
+  loadRom(bytes) {
+    const ok = this.withBytes(bytes, (ptr, len) => this.wasm.chip8_load_rom(this.handle, ptr, len));
+    if (!ok) throw new Error("ROM is empty or too large");
+  }
+
+  // Bit N set while key N is down
+  setKeys(mask) {
+    this.wasm.chip8_set_keys(this.handle, mask);
+  }
+
+  setTickRate(instructionsPerFrame) {
+    this.wasm.chip8_set_tick_rate(this.handle, instructionsPerFrame);
+  }
+
+  setQuirks(bits) {
+    this.wasm.chip8_set_quirks(this.handle, bits);
+  }
+
+  runFrame() {
+    this.wasm.chip8_run_frame(this.handle);
+  }
+
+  soundActive() {
+    return this.wasm.chip8_sound_active(this.handle) !== 0;
+  }
+
+  // RGBA8, WIDTH x HEIGHT, copied out of wasm memory
+  framebuffer() {
+    const ptr = this.wasm.chip8_framebuffer(this.handle);
+    return new Uint8ClampedArray(this.wasm.memory.buffer, ptr, WIDTH * HEIGHT * 4).slice();
+  }
+
+  getState() {
+    const len = this.wasm.chip8_save_state(this.handle);
+    const ptr = this.wasm.chip8_state_ptr(this.handle);
+    return new Uint8Array(this.wasm.memory.buffer, ptr, len).slice();
+  }
+
+  setState(bytes) {
+    const ok = this.withBytes(bytes, (ptr, len) => this.wasm.chip8_load_state(this.handle, ptr, len));
+    if (!ok) throw new Error("Invalid save state");
+  }
+}
@@ -0,0 +1,38 @@
+// Runs the wasm build headlessly under Node:
+//
+//   cargo build --lib --target wasm32-unknown-unknown --no-default-features --features std --release
+//   node web/test.mjs
+import { readFile } from "node:fs/promises";
+import assert from "node:assert/strict";
+import { Chip8, WIDTH } from "./chip8.js";
+
+const wasmPath = new URL(
+  "../target/wasm32-unknown-unknown/release/chip_8mulator.wasm",
+  import.meta.url
+);
+const wasm = await readFile(wasmPath);
+
+// I = font "0", draw it at (V0, V0), then loop forever
+const rom = Uint8Array.from([0xa0, 0x50, 0xd0, 0x05, 0x12, 0x04]);
+const lit = (fb, x, y) => fb[(y * WIDTH + x) * 4] === 0xff;
+
+const chip = await Chip8.instantiate(wasm, 1234);
+chip.loadRom(rom);
+chip.runFrame();
+let fb = chip.framebuffer();
+// Top row of the 0 glyph is 0xF0
+assert.deepEqual([0, 1, 2, 3, 4].map((x) => lit(fb, x, 0)), [true, true, true, true, false]);
+assert.equal(fb[3], 0xff);
+
+const state = chip.getState();
+const other = await Chip8.instantiate(wasm, 1);
+other.setState(state);
+assert.deepEqual(other.getState(), state);
+assert.deepEqual(other.framebuffer(), fb);
+
+assert.throws(() => other.setState(Uint8Array.from([1, 2, 3])));
+assert.throws(() => other.loadRom(new Uint8Array(0)));
+
+chip.free();
+other.free();
+console.log("wasm ok");